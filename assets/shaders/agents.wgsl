@@ -0,0 +1,35 @@
+#define_import_path compute_playground::agents
+
+// Agents that move over the trail map and steer by what they sense on
+// it, see `Agent` in physarum.rs.
+//
+// Needs `compute_playground::common` and `compute_playground::trail`,
+// and `input_tex` as the current trail map. The importing shader defines
+//
+//     // how much an agent is drawn to a pixel of the trail map
+//     fn smell(agent: Agent, trail: vec4<f32>) -> f32
+
+struct Agent {
+    position: vec2<f32>,
+    angle: f32,
+    species: u32,
+}
+
+// center of a sensor at `distance` ahead of the agent, turned by `sensor_angle`
+fn sensor_position(agent: Agent, sensor_angle: f32, distance: f32) -> vec2<f32> {
+    let angle = agent.angle + sensor_angle;
+    return agent.position + vec2<f32>(cos(angle), sin(angle)) * distance;
+}
+
+// what the agent smells in the square of `size` pixels around the sensor
+fn sensor(agent: Agent, sensor_angle: f32, distance: f32, size: i32) -> f32 {
+    let sensor_mid = vec2<i32>(sensor_position(agent, sensor_angle, distance));
+
+    var sum = 0.0;
+    for (var r = -size; r <= size; r++) {
+        for (var c = -size; c <= size; c++) {
+            sum += smell(agent, trail_load(input_tex, sensor_mid + vec2<i32>(r, c), false));
+        }
+    }
+    return sum;
+}
@@ -0,0 +1,216 @@
+#import compute_playground::common
+#import compute_playground::trail
+#import compute_playground::agents
+
+struct AntParams {
+    // center in xy as fractions of the window, radius in z and food per
+    // pixel in w
+    food_piles: array<vec4<f32>, 8>,
+    pile_count: u32,
+    nest: vec2<f32>,
+    nest_radius: f32,
+    sensor_size: i32,
+    sensor_distance: f32,
+    sensor_angle_between: f32,
+    turn_speed: f32,
+    move_speed: f32,
+    deposit: f32,
+    deposit_decay: f32,
+    diffusion: f32,
+    evaporation: f32,
+    food_attraction: f32,
+    bite: f32,
+    brush_radius: f32,
+}
+
+@group(0) @binding(1)
+var<uniform> params: AntParams;
+
+// the trail map: the to-home pheromone in red, the to-food pheromone in
+// green and food in blue
+@group(1) @binding(0)
+var output_tex: texture_storage_2d<rgba16float, write>;
+
+@group(1) @binding(1)
+var input_tex: texture_2d<f32>;
+
+@group(1) @binding(2)
+var display_tex: texture_storage_2d<rgba8unorm, write>;
+
+// an `Agent` whose species is 1 while it carries food, which is also the
+// channel of the pheromone it lays down
+struct Ant {
+    agent: Agent,
+    // steps since the ant left the nest or the food, its trail fades with it
+    time: f32,
+}
+
+@group(2) @binding(0)
+var<storage, read_write> ants: array<Ant>;
+
+const TAU: f32 = 6.283185307;
+
+fn inside(cell: vec2<i32>) -> bool {
+    return all(cell >= vec2<i32>(0)) && all(cell < vec2<i32>(frame.size));
+}
+
+fn nest_center() -> vec2<f32> {
+    return params.nest * frame.size;
+}
+
+fn in_nest(position: vec2<f32>) -> bool {
+    return distance(position, nest_center()) < params.nest_radius;
+}
+
+// every ant starts in the nest, facing away from it
+@compute @workgroup_size(64, 1, 1)
+fn spawn(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let id = invocation_id.x;
+    if id >= arrayLength(&ants) {
+        return;
+    }
+    let random = hash(id ^ hash(frame.seed));
+    let angle = randomFloat(random) * TAU;
+    let distance = sqrt(randomFloat(hash(random))) * params.nest_radius;
+    let position = nest_center() + vec2<f32>(cos(angle), sin(angle)) * distance;
+    ants[id] = Ant(Agent(position, angle, 0u), 0.0);
+}
+
+// no pheromones yet, only the food piles
+@compute @workgroup_size(8, 8, 1)
+fn setup(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let cell = vec2<i32>(invocation_id.xy);
+    if !inside(cell) {
+        return;
+    }
+    let position = vec2<f32>(cell) + 0.5;
+    var food = 0.0;
+    for (var i = 0u; i < params.pile_count; i++) {
+        let pile = params.food_piles[i];
+        if distance(position, pile.xy * frame.size) < pile.z {
+            food = max(food, pile.w);
+        }
+    }
+    textureStore(output_tex, cell, vec4<f32>(0.0, 0.0, food, 1.0));
+}
+
+// searching ants follow the to-food pheromone and smell the food itself,
+// ants carrying food follow the to-home pheromone
+fn smell(agent: Agent, trail: vec4<f32>) -> f32 {
+    if agent.species != 0u {
+        return trail.r;
+    }
+    return trail.g + trail.b * params.food_attraction;
+}
+
+// ants carrying food also see the nest
+fn ant_sensor(ant: Ant, sensor_angle: f32) -> f32 {
+    var sum = sensor(ant.agent, sensor_angle, params.sensor_distance, params.sensor_size);
+    let position = sensor_position(ant.agent, sensor_angle, params.sensor_distance);
+    if ant.agent.species != 0u && in_nest(position) {
+        sum += 1000.0;
+    }
+    return sum;
+}
+
+@compute @workgroup_size(64, 1, 1)
+fn update(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let id = invocation_id.x;
+    if id >= arrayLength(&ants) {
+        return;
+    }
+    let ant = ants[id];
+    var agent = ant.agent;
+    var time = ant.time;
+    let random = hash(id ^ hash(frame.step ^ hash(frame.seed)));
+
+    let w_forward = ant_sensor(ant, 0.0);
+    let w_left = ant_sensor(ant, params.sensor_angle_between);
+    let w_right = ant_sensor(ant, -params.sensor_angle_between);
+    let random_steer = randomFloat(random);
+    if w_forward > w_left && w_forward > w_right {
+    } else if w_forward < w_left && w_forward < w_right {
+        agent.angle += (random_steer - 0.5) * 2.0 * params.turn_speed;
+    } else if w_right > w_left {
+        agent.angle -= random_steer * params.turn_speed;
+    } else if w_left > w_right {
+        agent.angle += random_steer * params.turn_speed;
+    }
+    // a little wandering keeps them from walking in perfect lines
+    agent.angle += (randomFloat(hash(random)) - 0.5) * 0.1 * params.turn_speed;
+
+    // bounce off the edges
+    var direction = vec2<f32>(cos(agent.angle), sin(agent.angle));
+    var position = agent.position + direction * params.move_speed;
+    if position.x < 0.0 || position.x >= frame.size.x {
+        direction.x = -direction.x;
+    }
+    if position.y < 0.0 || position.y >= frame.size.y {
+        direction.y = -direction.y;
+    }
+    agent.angle = atan2(direction.y, direction.x);
+    agent.position = clamp(position, vec2<f32>(0.0), frame.size - 0.01);
+    time += 1.0;
+
+    let pixel = vec2<u32>(agent.position);
+    let food = textureLoad(input_tex, vec2<i32>(pixel), 0).b;
+    if agent.species == 0u && food > 0.0 {
+        // takes a bite and heads back the way it came
+        agent.species = 1u;
+        agent.angle += TAU / 2.0;
+        time = 0.0;
+        deposit_channel(pixel, 2u, 1.0);
+    } else if in_nest(agent.position) {
+        // drops the food, or starts over from the nest while searching
+        agent.angle += select(0.0, TAU / 2.0, agent.species != 0u);
+        agent.species = 0u;
+        time = 0.0;
+    }
+
+    // searching ants mark the way home, carrying ants the way to the food
+    deposit_channel(pixel, agent.species, params.deposit * exp(-time * params.deposit_decay));
+    ants[id] = Ant(agent, time);
+}
+
+// diffuses and evaporates the pheromones, then adds the deposits of this
+// step and takes the bites out of the food. The left mouse button adds
+// food, the right one removes it.
+@compute @workgroup_size(8, 8, 1)
+fn resolve(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let cell = vec2<i32>(invocation_id.xy);
+    if !inside(cell) {
+        return;
+    }
+    let original = textureLoad(input_tex, cell, 0);
+    // the parameters are per step
+    let evaporated = diffuse(input_tex, cell, params.diffusion, params.evaporation, 1.0, false).rg;
+    // both pheromones in red and green, the bites taken out of the food in blue
+    let added = take_deposits(invocation_id.xy);
+    var food = max(original.b - added.b * params.bite, 0.0);
+    if distance(vec2<f32>(cell) + 0.5, frame.pointer) < params.brush_radius {
+        if (frame.buttons & 1u) != 0u {
+            food = 1.0;
+        } else if (frame.buttons & 2u) != 0u {
+            food = 0.0;
+        }
+    }
+    let pheromones = min(evaporated + added.rg, vec2<f32>(1.0));
+    textureStore(output_tex, cell, vec4<f32>(pheromones, food, 1.0));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn display(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let cell = vec2<i32>(invocation_id.xy);
+    if !inside(cell) {
+        return;
+    }
+    let trail = textureLoad(input_tex, cell, 0);
+    var color = vec3<f32>(0.03, 0.03, 0.05);
+    color += vec3<f32>(0.2, 0.45, 1.0) * trail.r;
+    color += vec3<f32>(1.0, 0.4, 0.2) * trail.g;
+    color = mix(color, vec3<f32>(0.3, 0.9, 0.3), min(trail.b, 1.0));
+    if in_nest(vec2<f32>(cell) + 0.5) {
+        color = vec3<f32>(0.55, 0.4, 0.25);
+    }
+    textureStore(display_tex, cell, vec4<f32>(min(color, vec3<f32>(1.0)), 1.0));
+}
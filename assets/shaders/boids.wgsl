@@ -0,0 +1,180 @@
+#import compute_playground::common
+#import compute_playground::grid
+#import compute_playground::trail
+
+struct BoidsParams {
+    count: u32,
+    perception_radius: f32,
+    cell_size: f32,
+    separation_distance: f32,
+    separation: f32,
+    alignment: f32,
+    cohesion: f32,
+    min_speed: f32,
+    max_speed: f32,
+    trails: u32,
+    diffusion: f32,
+    evaporation: f32,
+    pointer_radius: f32,
+}
+
+@group(0) @binding(1)
+var<uniform> params: BoidsParams;
+
+// deposited heading colors, rgb
+@group(1) @binding(0)
+var output_tex: texture_storage_2d<rgba16float, write>;
+
+@group(1) @binding(1)
+var input_tex: texture_2d<f32>;
+
+@group(1) @binding(2)
+var display_tex: texture_storage_2d<rgba8unorm, write>;
+
+struct Boid {
+    position: vec2<f32>,
+    velocity: vec2<f32>,
+}
+
+@group(2) @binding(0)
+var<storage, read_write> boids: array<Boid>;
+
+// ordered by grid cell, written by `scatter`
+@group(2) @binding(4)
+var<storage, read_write> sorted: array<Boid>;
+
+fn hue(h: f32) -> vec3<f32> {
+    let k = vec3<f32>(0.0, 2.0 / 3.0, 1.0 / 3.0);
+    return clamp(abs(fract(h + k) * 6.0 - 3.0) - 1.0, vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+fn boid_count() -> u32 {
+    return min(params.count, arrayLength(&boids));
+}
+
+fn grid_cell_size() -> f32 {
+    return params.cell_size;
+}
+
+fn grid_item_count() -> u32 {
+    return boid_count();
+}
+
+fn grid_item_position(index: u32) -> vec2<f32> {
+    return boids[index].position;
+}
+
+fn grid_sort(index: u32, slot: u32) {
+    sorted[slot] = boids[index];
+}
+
+@compute @workgroup_size(64, 1, 1)
+fn init(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let index = invocation_id.x;
+    if index >= boid_count() {
+        return;
+    }
+    let seed = hash(index ^ hash(frame.seed));
+    let angle = randomFloat(seed + 2u) * 6.2831853;
+    let speed = mix(params.min_speed, params.max_speed, 0.5);
+    boids[index] = Boid(
+        vec2<f32>(randomFloat(seed), randomFloat(seed + 1u)) * frame.size,
+        vec2<f32>(cos(angle), sin(angle)) * speed,
+    );
+}
+
+@compute @workgroup_size(64, 1, 1)
+fn update(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let index = invocation_id.x;
+    if index >= boid_count() {
+        return;
+    }
+    var boid = sorted[index];
+
+    var separation = vec2<f32>(0.0);
+    var heading = vec2<f32>(0.0);
+    var center = vec2<f32>(0.0);
+    var neighbours = 0u;
+    // 3x3 cells around the boid, fewer if the grid is smaller than that
+    let grid = grid_size();
+    let cell = cell_of(boid.position);
+    let span = min(grid, vec2<u32>(3u));
+    for (var y = 0u; y < span.y; y++) {
+        for (var x = 0u; x < span.x; x++) {
+            let other_cell = (cell + grid - 1u + vec2<u32>(x, y)) % grid;
+            let c = cell_index(other_cell);
+            let start = cell_starts[c];
+            let end = start + atomicLoad(&cell_counts[c]);
+            for (var j = start; j < end; j++) {
+                let d = offset(boid.position, sorted[j].position);
+                let distance = length(d);
+                if j == index || distance >= params.perception_radius || distance == 0.0 {
+                    continue;
+                }
+                neighbours += 1u;
+                heading += sorted[j].velocity;
+                center += d;
+                if distance < params.separation_distance {
+                    separation -= d / (distance * distance);
+                }
+            }
+        }
+    }
+
+    var acceleration = separation * params.separation;
+    if neighbours > 0u {
+        let n = f32(neighbours);
+        acceleration += (heading / n - boid.velocity) * params.alignment;
+        acceleration += center / n * params.cohesion;
+    }
+
+    // left attracts, right scatters
+    let to_pointer = offset(boid.position, frame.pointer);
+    let pointer_distance = length(to_pointer);
+    if pointer_distance > 0.0 && pointer_distance < params.pointer_radius {
+        if (frame.buttons & 1u) != 0u {
+            acceleration += to_pointer * 2.0;
+        } else if (frame.buttons & 2u) != 0u {
+            acceleration -= to_pointer / pointer_distance * params.max_speed * 8.0;
+        }
+    }
+
+    // large frame times would let boids jump over their neighbours
+    let delta_time = min(frame.delta_time, 0.05);
+    var velocity = boid.velocity + acceleration * delta_time;
+    let speed = length(velocity);
+    if speed > 0.0 {
+        velocity *= clamp(speed, params.min_speed, params.max_speed) / speed;
+    }
+    boid.velocity = velocity;
+    boid.position = wrap(boid.position + velocity * delta_time);
+    boids[index] = boid;
+
+    let pixel = min(vec2<u32>(boid.position), vec2<u32>(frame.size) - 1u);
+    deposit(pixel, vec4<f32>(hue(atan2(velocity.y, velocity.x) / 6.2831853 + 0.5), 1.0));
+}
+
+// the blur and evaporation of the trail map on the wrapped state, then
+// the deposits of this step are added
+@compute @workgroup_size(8, 8, 1)
+fn fade(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    if any(vec2<f32>(invocation_id.xy) >= frame.size) {
+        return;
+    }
+    let cell = vec2<i32>(invocation_id.xy);
+    var trail = vec4<f32>(0.0);
+    if params.trails != 0u {
+        trail = diffuse(input_tex, cell, params.diffusion, params.evaporation, frame.delta_time, true);
+    }
+    textureStore(output_tex, cell, min(trail + take_deposits(invocation_id.xy), vec4<f32>(1.0)));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn display(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    if any(vec2<f32>(invocation_id.xy) >= frame.size) {
+        return;
+    }
+    let cell = vec2<i32>(invocation_id.xy);
+    let color = textureLoad(input_tex, cell, 0).rgb;
+    textureStore(display_tex, cell, vec4<f32>(color, 1.0));
+}
@@ -0,0 +1,132 @@
+#import compute_playground::common
+
+struct CellularParams {
+    birth_mask: u32,
+    survival_mask: u32,
+    birth_min: u32,
+    birth_max: u32,
+    survival_min: u32,
+    survival_max: u32,
+    range: u32,
+    states: u32,
+    // 0: Moore, 1: von Neumann
+    neighborhood: u32,
+    middle: u32,
+    random_init: u32,
+    density: f32,
+}
+
+@group(0) @binding(1)
+var<uniform> params: CellularParams;
+
+// 0: dead, 1: alive, 2 and up: decaying
+@group(1) @binding(0)
+var output_tex: texture_storage_2d<r32uint, write>;
+
+@group(1) @binding(1)
+var input_tex: texture_2d<u32>;
+
+@group(1) @binding(2)
+var display_tex: texture_storage_2d<rgba8unorm, write>;
+
+// width, height, then one state per cell
+@group(2) @binding(0)
+var<storage, read_write> pattern: array<u32>;
+
+fn inside(cell: vec2<u32>) -> bool {
+    return all(vec2<f32>(cell) < frame.size);
+}
+
+// the world wraps around at the edges
+fn load(cell: vec2<i32>) -> u32 {
+    let size = vec2<i32>(frame.size);
+    return textureLoad(input_tex, (cell % size + size) % size, 0).r;
+}
+
+fn store(cell: vec2<u32>, state: u32) {
+    textureStore(output_tex, vec2<i32>(cell), vec4<u32>(state, 0u, 0u, 1u));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn init(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let cell = invocation_id.xy;
+    if !inside(cell) {
+        return;
+    }
+    if params.random_init != 0u {
+        let random = randomFloat(cell.x ^ hash(cell.y ^ hash(frame.seed)));
+        store(cell, u32(random < params.density));
+        return;
+    }
+    // the pattern is centered
+    let pattern_size = vec2<u32>(pattern[0], pattern[1]);
+    let offset = (vec2<i32>(frame.size) - vec2<i32>(pattern_size)) / 2;
+    let local = vec2<i32>(cell) - offset;
+    var state = 0u;
+    if all(local >= vec2<i32>(0)) && all(local < vec2<i32>(pattern_size)) {
+        state = pattern[2u + u32(local.y) * pattern_size.x + u32(local.x)];
+    }
+    store(cell, min(state, params.states - 1u));
+}
+
+// counts below 32 are looked up in the mask, larger ones in the interval
+fn matches(count: u32, mask: u32, min_count: u32, max_count: u32) -> bool {
+    if count < 32u {
+        return ((mask >> count) & 1u) != 0u;
+    }
+    return count >= min_count && count <= max_count;
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn update(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let cell = invocation_id.xy;
+    if !inside(cell) {
+        return;
+    }
+    let center = vec2<i32>(cell);
+    let state = load(center);
+    let range = i32(params.range);
+
+    var count = 0u;
+    for (var y = -range; y <= range; y++) {
+        for (var x = -range; x <= range; x++) {
+            let skip = (x == 0 && y == 0 && params.middle == 0u)
+                || (params.neighborhood == 1u && abs(x) + abs(y) > range);
+            if !skip && load(center + vec2<i32>(x, y)) == 1u {
+                count++;
+            }
+        }
+    }
+
+    var next = 0u;
+    if state == 0u {
+        next = u32(matches(count, params.birth_mask, params.birth_min, params.birth_max));
+    } else if state == 1u {
+        if matches(count, params.survival_mask, params.survival_min, params.survival_max) {
+            next = 1u;
+        } else if params.states > 2u {
+            next = 2u;
+        }
+    } else {
+        // decaying cells neither count as alive nor come back to life
+        next = (state + 1u) % params.states;
+    }
+    store(cell, next);
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn display(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let cell = invocation_id.xy;
+    if !inside(cell) {
+        return;
+    }
+    let state = load(vec2<i32>(cell));
+    var color = vec3<f32>(0.0);
+    if state == 1u {
+        color = vec3<f32>(1.0, 0.95, 0.8);
+    } else if state > 1u {
+        let age = f32(state - 1u) / f32(max(params.states - 1u, 1u));
+        color = mix(vec3<f32>(0.9, 0.35, 0.1), vec3<f32>(0.1, 0.05, 0.3), age);
+    }
+    textureStore(display_tex, vec2<i32>(cell), vec4<f32>(color, 1.0));
+}
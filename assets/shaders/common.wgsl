@@ -0,0 +1,31 @@
+#define_import_path compute_playground::common
+
+// Uniforms of every simulation step, see `FrameParams` in simulation.rs.
+struct Frame {
+    size: vec2<f32>,
+    pointer: vec2<f32>,
+    pointer_delta: vec2<f32>,
+    buttons: u32,
+    delta_time: f32,
+    step: u32,
+    seed: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> frame: Frame;
+
+fn hash(value: u32) -> u32 {
+    var state = value;
+    state = state ^ 2747636419u;
+    state = state * 2654435769u;
+    state = state ^ (state >> 16u);
+    state = state * 2654435769u;
+    state = state ^ (state >> 16u);
+    state = state * 2654435769u;
+    return state;
+}
+
+// uniform in 0..=1
+fn randomFloat(value: u32) -> f32 {
+    return f32(hash(value)) / 4294967295.0;
+}
@@ -0,0 +1,157 @@
+#import compute_playground::common
+
+struct DlaParams {
+    seed_shape: u32,
+    stickiness: f32,
+    brush_radius: f32,
+}
+
+@group(0) @binding(1)
+var<uniform> params: DlaParams;
+
+// the step a pixel joined the cluster at plus one, 0 for empty
+@group(1) @binding(0)
+var output_tex: texture_storage_2d<r32uint, write>;
+
+@group(1) @binding(1)
+var input_tex: texture_2d<u32>;
+
+@group(1) @binding(2)
+var display_tex: texture_storage_2d<rgba8unorm, write>;
+
+@group(2) @binding(0)
+var<storage, read_write> walkers: array<vec2<i32>>;
+
+// set for every pixel with a walker on it, cleared again by `grow`
+@group(2) @binding(1)
+var<storage, read_write> occupied: array<atomic<u32>>;
+
+fn hue(h: f32) -> vec3<f32> {
+    let k = vec3<f32>(0.0, 2.0 / 3.0, 1.0 / 3.0);
+    return clamp(abs(fract(h + k) * 6.0 - 3.0) - 1.0, vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+fn inside(cell: vec2<i32>) -> bool {
+    return all(cell >= vec2<i32>(0)) && all(cell < vec2<i32>(frame.size));
+}
+
+fn index(cell: vec2<i32>) -> u32 {
+    return u32(cell.y) * u32(frame.size.x) + u32(cell.x);
+}
+
+// outside of the image counts as empty
+fn frozen(cell: vec2<i32>) -> bool {
+    return inside(cell) && textureLoad(input_tex, cell, 0).r != 0u;
+}
+
+fn random_cell(random: u32) -> vec2<i32> {
+    let position = vec2<f32>(randomFloat(random), randomFloat(hash(random))) * frame.size;
+    return min(vec2<i32>(position), vec2<i32>(frame.size) - 1);
+}
+
+// see `SeedShape` in dla.rs
+fn seeded(cell: vec2<i32>) -> bool {
+    let position = vec2<f32>(cell) + 0.5;
+    let center = frame.size / 2.0;
+    switch params.seed_shape {
+        case 0u: { return all(cell == vec2<i32>(center)); }
+        case 1u: { return cell.y == i32(frame.size.y) - 1; }
+        default: {
+            let radius = min(frame.size.x, frame.size.y) * 0.4;
+            return abs(distance(position, center) - radius) < 0.5;
+        }
+    }
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn seed(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let cell = vec2<i32>(invocation_id.xy);
+    if inside(cell) {
+        atomicStore(&occupied[index(cell)], 0u);
+        textureStore(output_tex, cell, vec4<u32>(u32(seeded(cell))));
+    }
+}
+
+@compute @workgroup_size(64, 1, 1)
+fn spawn(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let id = invocation_id.x;
+    if id < arrayLength(&walkers) {
+        walkers[id] = random_cell(hash(id ^ hash(frame.seed)));
+    }
+}
+
+// a random step to one of the eight neighbours, never onto the cluster.
+// Walkers that froze in the last step start over somewhere else.
+@compute @workgroup_size(64, 1, 1)
+fn walk(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let id = invocation_id.x;
+    if id >= arrayLength(&walkers) {
+        return;
+    }
+    let random = hash(id ^ hash(frame.step ^ hash(frame.seed)));
+    var cell = walkers[id];
+    if frozen(cell) {
+        cell = random_cell(random);
+    } else {
+        var offsets = array<vec2<i32>, 8>(
+            vec2<i32>(-1, -1), vec2<i32>(0, -1), vec2<i32>(1, -1), vec2<i32>(-1, 0),
+            vec2<i32>(1, 0), vec2<i32>(-1, 1), vec2<i32>(0, 1), vec2<i32>(1, 1),
+        );
+        let next = clamp(cell + offsets[random % 8u], vec2<i32>(0), vec2<i32>(frame.size) - 1);
+        if !frozen(next) {
+            cell = next;
+        }
+    }
+    walkers[id] = cell;
+    if !frozen(cell) {
+        atomicStore(&occupied[index(cell)], 1u);
+    }
+}
+
+// walkers next to the cluster freeze with the stick probability. The left
+// mouse button adds to the cluster, the right one erases.
+@compute @workgroup_size(8, 8, 1)
+fn grow(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let cell = vec2<i32>(invocation_id.xy);
+    if !inside(cell) {
+        return;
+    }
+    var value = textureLoad(input_tex, cell, 0).r;
+    let walker = atomicExchange(&occupied[index(cell)], 0u) != 0u;
+    if value == 0u && walker {
+        var touching = false;
+        for (var y = -1; y <= 1; y++) {
+            for (var x = -1; x <= 1; x++) {
+                touching = touching || frozen(cell + vec2<i32>(x, y));
+            }
+        }
+        let random = hash(invocation_id.x ^ hash(invocation_id.y ^ hash(frame.step ^ frame.seed)));
+        if touching && randomFloat(random) < params.stickiness {
+            value = frame.step + 1u;
+        }
+    }
+    if distance(vec2<f32>(cell) + 0.5, frame.pointer) < params.brush_radius {
+        if (frame.buttons & 1u) != 0u && value == 0u {
+            value = frame.step + 1u;
+        } else if (frame.buttons & 2u) != 0u {
+            value = 0u;
+        }
+    }
+    textureStore(output_tex, cell, vec4<u32>(value));
+}
+
+// colored by the time a pixel joined the cluster, relative to now
+@compute @workgroup_size(8, 8, 1)
+fn display(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let cell = vec2<i32>(invocation_id.xy);
+    if !inside(cell) {
+        return;
+    }
+    let value = textureLoad(input_tex, cell, 0).r;
+    var color = vec3<f32>(0.02, 0.02, 0.04);
+    if value != 0u {
+        let age = f32(value - 1u) / f32(max(frame.step, 1u));
+        color = mix(hue(0.6 - 0.6 * age), vec3<f32>(1.0), 0.15);
+    }
+    textureStore(display_tex, cell, vec4<f32>(color, 1.0));
+}
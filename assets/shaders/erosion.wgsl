@@ -0,0 +1,274 @@
+#import compute_playground::common
+
+struct ErosionParams {
+    from_file: u32,
+    noise_scale: f32,
+    octaves: u32,
+    persistence: f32,
+    lifetime: u32,
+    inertia: f32,
+    capacity: f32,
+    min_capacity: f32,
+    erosion: f32,
+    deposition: f32,
+    evaporation: f32,
+    gravity: f32,
+    brush_radius: u32,
+    relief: f32,
+}
+
+@group(0) @binding(1)
+var<uniform> params: ErosionParams;
+
+// unused, the terrain lives in the heights buffer
+@group(1) @binding(0)
+var output_tex: texture_storage_2d<rgba16float, write>;
+
+@group(1) @binding(1)
+var input_tex: texture_2d<f32>;
+
+@group(1) @binding(2)
+var display_tex: texture_storage_2d<rgba8unorm, write>;
+
+// one fixed point height per pixel, so droplets can erode and deposit
+// anywhere at the same time
+@group(2) @binding(0)
+var<storage, read_write> heights: array<atomic<i32>>;
+
+// width and height followed by the heights of the loaded file as f32 bits
+@group(2) @binding(1)
+var<storage, read_write> source: array<u32>;
+
+struct Droplet {
+    position: vec2<f32>,
+    direction: vec2<f32>,
+    speed: f32,
+    water: f32,
+    sediment: f32,
+    age: u32,
+}
+
+@group(2) @binding(2)
+var<storage, read_write> droplets: array<Droplet>;
+
+// see `HEIGHT_SCALE` and `MAX_BRUSH_RADIUS` in erosion.rs
+const HEIGHT_SCALE: f32 = 16777216.0;
+const MAX_BRUSH_RADIUS: i32 = 8;
+
+fn inside(cell: vec2<i32>) -> bool {
+    return all(cell >= vec2<i32>(0)) && all(cell < vec2<i32>(frame.size));
+}
+
+fn index(cell: vec2<i32>) -> u32 {
+    return u32(cell.y) * u32(frame.size.x) + u32(cell.x);
+}
+
+// clamped to the edges
+fn height_at(cell: vec2<i32>) -> f32 {
+    let clamped = clamp(cell, vec2<i32>(0), vec2<i32>(frame.size) - 1);
+    return f32(atomicLoad(&heights[index(clamped)])) / HEIGHT_SCALE;
+}
+
+fn add_height(cell: vec2<i32>, amount: f32) {
+    if inside(cell) {
+        atomicAdd(&heights[index(cell)], i32(round(amount * HEIGHT_SCALE)));
+    }
+}
+
+// the gradient in xy and the height in z, bilinear between the four
+// pixels around the position
+fn height_and_gradient(position: vec2<f32>) -> vec3<f32> {
+    let cell = vec2<i32>(floor(position));
+    let f = position - floor(position);
+    let nw = height_at(cell);
+    let ne = height_at(cell + vec2<i32>(1, 0));
+    let sw = height_at(cell + vec2<i32>(0, 1));
+    let se = height_at(cell + vec2<i32>(1, 1));
+    let gradient = vec2<f32>(
+        (ne - nw) * (1.0 - f.y) + (se - sw) * f.y,
+        (sw - nw) * (1.0 - f.x) + (se - ne) * f.x,
+    );
+    let height = mix(mix(nw, ne, f.x), mix(sw, se, f.x), f.y);
+    return vec3<f32>(gradient, height);
+}
+
+fn deposit(position: vec2<f32>, amount: f32) {
+    let cell = vec2<i32>(floor(position));
+    let f = position - floor(position);
+    add_height(cell, amount * (1.0 - f.x) * (1.0 - f.y));
+    add_height(cell + vec2<i32>(1, 0), amount * f.x * (1.0 - f.y));
+    add_height(cell + vec2<i32>(0, 1), amount * (1.0 - f.x) * f.y);
+    add_height(cell + vec2<i32>(1, 1), amount * f.x * f.y);
+}
+
+// spread over the pixels of the brush, weighted by their closeness
+fn erode(position: vec2<f32>, amount: f32) {
+    let radius = clamp(i32(params.brush_radius), 1, MAX_BRUSH_RADIUS);
+    let center = vec2<i32>(round(position));
+    var total = 0.0;
+    for (var y = -radius; y <= radius; y++) {
+        for (var x = -radius; x <= radius; x++) {
+            let cell = center + vec2<i32>(x, y);
+            if inside(cell) {
+                total += max(f32(radius) - distance(vec2<f32>(cell), position), 0.0);
+            }
+        }
+    }
+    if total <= 0.0 {
+        return;
+    }
+    for (var y = -radius; y <= radius; y++) {
+        for (var x = -radius; x <= radius; x++) {
+            let cell = center + vec2<i32>(x, y);
+            let weight = max(f32(radius) - distance(vec2<f32>(cell), position), 0.0);
+            add_height(cell, -amount * weight / total);
+        }
+    }
+}
+
+fn value_noise(position: vec2<f32>, seed: u32) -> f32 {
+    let cell = vec2<i32>(floor(position));
+    let f = position - floor(position);
+    let t = f * f * (3.0 - 2.0 * f);
+    let corner = array<f32, 4>(
+        randomFloat(bitcast<u32>(cell.x) ^ hash(bitcast<u32>(cell.y) ^ seed)),
+        randomFloat(bitcast<u32>(cell.x + 1) ^ hash(bitcast<u32>(cell.y) ^ seed)),
+        randomFloat(bitcast<u32>(cell.x) ^ hash(bitcast<u32>(cell.y + 1) ^ seed)),
+        randomFloat(bitcast<u32>(cell.x + 1) ^ hash(bitcast<u32>(cell.y + 1) ^ seed)),
+    );
+    return mix(mix(corner[0], corner[1], t.x), mix(corner[2], corner[3], t.x), t.y);
+}
+
+// the loaded file stretched over the image
+fn source_height(position: vec2<f32>) -> f32 {
+    let size = vec2<u32>(source[0], source[1]);
+    let scaled = position / frame.size * vec2<f32>(size) - 0.5;
+    let cell = vec2<i32>(floor(scaled));
+    let f = scaled - floor(scaled);
+    var corners = array<f32, 4>(0.0, 0.0, 0.0, 0.0);
+    for (var i = 0; i < 4; i++) {
+        let corner = clamp(cell + vec2<i32>(i % 2, i / 2), vec2<i32>(0), vec2<i32>(size) - 1);
+        corners[i] = bitcast<f32>(source[2u + u32(corner.y) * size.x + u32(corner.x)]);
+    }
+    return mix(mix(corners[0], corners[1], f.x), mix(corners[2], corners[3], f.x), f.y);
+}
+
+// fractal noise or the loaded file
+@compute @workgroup_size(8, 8, 1)
+fn generate(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let cell = vec2<i32>(invocation_id.xy);
+    if !inside(cell) {
+        return;
+    }
+    let position = vec2<f32>(cell) + 0.5;
+    var height = 0.0;
+    if params.from_file != 0u {
+        height = source_height(position);
+    } else {
+        var amplitude = 1.0;
+        var frequency = 1.0 / max(params.noise_scale, 1.0);
+        var total = 0.0;
+        for (var octave = 0u; octave < params.octaves; octave++) {
+            height += amplitude * value_noise(position * frequency, hash(octave ^ hash(frame.seed)));
+            total += amplitude;
+            amplitude *= params.persistence;
+            frequency *= 2.0;
+        }
+        height /= max(total, 1e-6);
+    }
+    atomicStore(&heights[index(cell)], i32(height * HEIGHT_SCALE));
+}
+
+// moves every droplet one pixel downhill, eroding where it can carry more
+// sediment and depositing where it carries too much
+@compute @workgroup_size(64, 1, 1)
+fn update(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let id = invocation_id.x;
+    if id >= arrayLength(&droplets) {
+        return;
+    }
+    var droplet = droplets[id];
+    let random = hash(id ^ hash(frame.step ^ hash(frame.seed)));
+    let limit = frame.size - 1.0;
+
+    // dried up droplets start over somewhere else, what is left of their
+    // sediment settles where they were
+    if droplet.water <= 0.0 || droplet.age >= params.lifetime {
+        deposit(droplet.position, droplet.sediment);
+        droplet.position = vec2<f32>(randomFloat(random), randomFloat(hash(random))) * limit;
+        droplet.direction = vec2<f32>(0.0);
+        droplet.speed = 1.0;
+        droplet.water = 1.0;
+        droplet.sediment = 0.0;
+        droplet.age = 0u;
+    }
+
+    let here = height_and_gradient(droplet.position);
+    droplet.direction = droplet.direction * params.inertia - here.xy * (1.0 - params.inertia);
+    let length = length(droplet.direction);
+    if length < 1e-9 {
+        let angle = randomFloat(hash(random + 1u)) * 6.283185307;
+        droplet.direction = vec2<f32>(cos(angle), sin(angle));
+    } else {
+        droplet.direction /= length;
+    }
+    let previous = droplet.position;
+    droplet.position += droplet.direction;
+    droplet.age += 1u;
+
+    // flowing off the map takes the sediment with it
+    if any(droplet.position < vec2<f32>(0.0)) || any(droplet.position >= limit) {
+        droplet.water = 0.0;
+        droplet.sediment = 0.0;
+        droplets[id] = droplet;
+        return;
+    }
+
+    let delta = height_and_gradient(droplet.position).z - here.z;
+    let capacity = max(-delta * droplet.speed * droplet.water * params.capacity, params.min_capacity);
+    if droplet.sediment > capacity || delta > 0.0 {
+        // uphill it fills the pit it came from
+        var amount = (droplet.sediment - capacity) * params.deposition;
+        if delta > 0.0 {
+            amount = min(delta, droplet.sediment);
+        }
+        droplet.sediment -= amount;
+        deposit(previous, amount);
+    } else {
+        // never digs deeper than the height it just went down
+        let amount = min((capacity - droplet.sediment) * params.erosion, -delta);
+        erode(previous, amount);
+        droplet.sediment += amount;
+    }
+    droplet.speed = sqrt(max(droplet.speed * droplet.speed - delta * params.gravity, 0.0));
+    droplet.water *= 1.0 - params.evaporation;
+    droplets[id] = droplet;
+}
+
+// see `terrain_color` in erosion.rs
+fn terrain_color(height: f32) -> vec3<f32> {
+    let low = vec3<f32>(0.2, 0.36, 0.2);
+    let mid = vec3<f32>(0.48, 0.4, 0.29);
+    let rock = vec3<f32>(0.58, 0.56, 0.54);
+    let snow = vec3<f32>(0.95, 0.95, 0.97);
+    var color = mix(low, mid, smoothstep(0.3, 0.55, height));
+    color = mix(color, rock, smoothstep(0.55, 0.75, height));
+    return mix(color, snow, smoothstep(0.8, 0.9, height));
+}
+
+// shaded relief lit from the top left
+@compute @workgroup_size(8, 8, 1)
+fn display(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let cell = vec2<i32>(invocation_id.xy);
+    if !inside(cell) {
+        return;
+    }
+    let height = height_at(cell);
+    let dx = height_at(cell + vec2<i32>(1, 0)) - height_at(cell - vec2<i32>(1, 0));
+    let dy = height_at(cell + vec2<i32>(0, 1)) - height_at(cell - vec2<i32>(0, 1));
+    let normal = normalize(vec3<f32>(-dx * params.relief * 0.5, -dy * params.relief * 0.5, 1.0));
+    let sun = normalize(vec3<f32>(-1.0, -1.0, 1.0));
+    let shade = max(dot(normal, sun), 0.0);
+    let color = terrain_color(height) * (0.3 + 0.7 * shade);
+    textureStore(display_tex, cell, vec4<f32>(color, 1.0));
+}
@@ -0,0 +1,90 @@
+#import compute_playground::common
+
+@group(1) @binding(1)
+var input_tex : texture_2d<f32>;
+
+struct Agent {
+    position: vec2<f32>,
+    angle: f32,
+    species: u32,
+}
+struct Agents {
+    agents: array<Agent>,
+}
+
+@group(2) @binding(0)
+var<storage, read_write> agents: Agents;
+
+struct ExposureParams {
+    decay: f32,
+    gain: f32,
+    exposure: f32,
+    reset: u32,
+}
+
+@group(3) @binding(0)
+var accumulation: texture_storage_2d<rgba32float, read_write>;
+
+@group(3) @binding(1)
+var display_tex: texture_storage_2d<rgba8unorm, write>;
+
+@group(3) @binding(2)
+var<uniform> exposure: ExposureParams;
+
+fn previous(location: vec2<i32>) -> vec4<f32> {
+    if exposure.reset != 0u {
+        return vec4<f32>(0.0);
+    }
+    return textureLoad(accumulation, location);
+}
+
+fn in_bounds(location: vec2<u32>) -> bool {
+    return all(location < vec2<u32>(frame.size));
+}
+
+// integrates whole trail map frames
+@compute @workgroup_size(8,8,1)
+fn accumulate_frame(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    if !in_bounds(invocation_id.xy) {
+        return;
+    }
+    let location = vec2<i32>(invocation_id.xy);
+    let trail = textureLoad(input_tex, location, 0);
+    textureStore(accumulation, location, previous(location) * exposure.decay + trail * exposure.gain);
+}
+
+@compute @workgroup_size(8,8,1)
+fn decay(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    if !in_bounds(invocation_id.xy) {
+        return;
+    }
+    let location = vec2<i32>(invocation_id.xy);
+    textureStore(accumulation, location, previous(location) * exposure.decay);
+}
+
+// integrates only where agents deposit this step
+@compute @workgroup_size(64,1,1)
+fn accumulate_deposits(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    if invocation_id.x >= arrayLength(&agents.agents) {
+        return;
+    }
+    let position = agents.agents[invocation_id.x].position;
+    if !in_bounds(vec2<u32>(max(position, vec2<f32>(0.0)))) {
+        return;
+    }
+    let location = vec2<i32>(position);
+    // agents on the same pixel race here, which only matters for display
+    let color = textureLoad(input_tex, location, 0);
+    textureStore(accumulation, location, textureLoad(accumulation, location) + color * exposure.gain);
+}
+
+@compute @workgroup_size(8,8,1)
+fn display(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    if !in_bounds(invocation_id.xy) {
+        return;
+    }
+    let location = vec2<i32>(invocation_id.xy);
+    let value = textureLoad(accumulation, location);
+    let mapped = vec3<f32>(1.0) - exp(-value.rgb * exposure.exposure);
+    textureStore(display_tex, location, vec4<f32>(mapped, 1.0));
+}
@@ -0,0 +1,184 @@
+#import compute_playground::common
+
+struct FluidParams {
+    velocity_dissipation: f32,
+    dye_dissipation: f32,
+    force: f32,
+    brush_radius: f32,
+}
+
+@group(0) @binding(1)
+var<uniform> params: FluidParams;
+
+// velocity in rg, pressure in b, divergence in a
+@group(1) @binding(0)
+var output_tex: texture_storage_2d<rgba32float, write>;
+
+@group(1) @binding(1)
+var input_tex: texture_2d<f32>;
+
+@group(1) @binding(2)
+var display_tex: texture_storage_2d<rgba8unorm, write>;
+
+// the dye of every pixel, followed by the advected dye
+@group(2) @binding(0)
+var<storage, read_write> dye: array<vec4<f32>>;
+
+fn hue(h: f32) -> vec3<f32> {
+    let k = vec3<f32>(0.0, 2.0 / 3.0, 1.0 / 3.0);
+    return clamp(abs(fract(h + k) * 6.0 - 3.0) - 1.0, vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+fn inside(cell: vec2<u32>) -> bool {
+    return all(vec2<f32>(cell) < frame.size);
+}
+
+fn pixel_count() -> u32 {
+    return u32(frame.size.x) * u32(frame.size.y);
+}
+
+fn clamp_cell(cell: vec2<i32>) -> vec2<i32> {
+    return clamp(cell, vec2<i32>(0), vec2<i32>(frame.size) - 1);
+}
+
+fn load(cell: vec2<i32>) -> vec4<f32> {
+    return textureLoad(input_tex, clamp_cell(cell), 0);
+}
+
+fn dye_index(cell: vec2<i32>) -> u32 {
+    let clamped = vec2<u32>(clamp_cell(cell));
+    return clamped.y * u32(frame.size.x) + clamped.x;
+}
+
+// velocity of a neighbour, walls mirror the velocity of the cell itself
+fn neighbour_velocity(cell: vec2<i32>, offset: vec2<i32>, own: vec2<f32>) -> vec2<f32> {
+    let neighbour = cell + offset;
+    if any(neighbour != clamp_cell(neighbour)) {
+        return -own;
+    }
+    return load(neighbour).xy;
+}
+
+fn bilinear_weights(position: vec2<f32>) -> vec2<f32> {
+    return fract(position - 0.5);
+}
+
+fn sample_velocity(position: vec2<f32>) -> vec2<f32> {
+    let base = vec2<i32>(floor(position - 0.5));
+    let t = bilinear_weights(position);
+    let top = mix(load(base).xy, load(base + vec2<i32>(1, 0)).xy, t.x);
+    let bottom = mix(load(base + vec2<i32>(0, 1)).xy, load(base + vec2<i32>(1, 1)).xy, t.x);
+    return mix(top, bottom, t.y);
+}
+
+fn sample_dye(position: vec2<f32>) -> vec4<f32> {
+    let base = vec2<i32>(floor(position - 0.5));
+    let t = bilinear_weights(position);
+    let top = mix(dye[dye_index(base)], dye[dye_index(base + vec2<i32>(1, 0))], t.x);
+    let bottom = mix(dye[dye_index(base + vec2<i32>(0, 1))], dye[dye_index(base + vec2<i32>(1, 1))], t.x);
+    return mix(top, bottom, t.y);
+}
+
+// still fluid, the advection reads the previous state
+@compute @workgroup_size(8, 8, 1)
+fn init(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    if !inside(invocation_id.xy) {
+        return;
+    }
+    textureStore(output_tex, vec2<i32>(invocation_id.xy), vec4<f32>(0.0));
+}
+
+// moves velocity and dye along the velocity by tracing back from every
+// pixel, then adds the force and dye of the pointer
+@compute @workgroup_size(8, 8, 1)
+fn advect(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    if !inside(invocation_id.xy) {
+        return;
+    }
+    let cell = vec2<i32>(invocation_id.xy);
+    let state = load(cell);
+    // large frame times would trace back across the whole image
+    let delta_time = min(frame.delta_time, 1.0 / 30.0);
+    let center = vec2<f32>(cell) + 0.5;
+    let back = center - state.xy * delta_time;
+    var velocity = sample_velocity(back) * pow(1.0 - params.velocity_dissipation, delta_time);
+    var color = sample_dye(back) * pow(1.0 - params.dye_dissipation, delta_time);
+
+    // left pushes and adds dye, right only pushes
+    let offset = center - frame.pointer;
+    let weight = exp(-dot(offset, offset) / (params.brush_radius * params.brush_radius));
+    if (frame.buttons & 3u) != 0u {
+        velocity += frame.pointer_delta * params.force * weight;
+    }
+    if (frame.buttons & 1u) != 0u {
+        let added = vec4<f32>(hue(f32(frame.step) * 0.002), 1.0);
+        color = max(color, added * weight);
+    }
+
+    dye[pixel_count() + dye_index(cell)] = color;
+    textureStore(output_tex, cell, vec4<f32>(velocity, state.z, 0.0));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn copy_dye(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    if !inside(invocation_id.xy) {
+        return;
+    }
+    let index = dye_index(vec2<i32>(invocation_id.xy));
+    dye[index] = dye[pixel_count() + index];
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn divergence(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    if !inside(invocation_id.xy) {
+        return;
+    }
+    let cell = vec2<i32>(invocation_id.xy);
+    let state = load(cell);
+    let left = neighbour_velocity(cell, vec2<i32>(-1, 0), state.xy);
+    let right = neighbour_velocity(cell, vec2<i32>(1, 0), state.xy);
+    let up = neighbour_velocity(cell, vec2<i32>(0, -1), state.xy);
+    let down = neighbour_velocity(cell, vec2<i32>(0, 1), state.xy);
+    let divergence = 0.5 * (right.x - left.x + down.y - up.y);
+    textureStore(output_tex, cell, vec4<f32>(state.xyz, divergence));
+}
+
+// one iteration of the pressure solve, the pressure is continued from the
+// previous step, walls clamp it
+@compute @workgroup_size(8, 8, 1)
+fn jacobi(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    if !inside(invocation_id.xy) {
+        return;
+    }
+    let cell = vec2<i32>(invocation_id.xy);
+    let state = load(cell);
+    let sum = load(cell + vec2<i32>(-1, 0)).z + load(cell + vec2<i32>(1, 0)).z
+        + load(cell + vec2<i32>(0, -1)).z + load(cell + vec2<i32>(0, 1)).z;
+    let pressure = (sum - state.w) * 0.25;
+    textureStore(output_tex, cell, vec4<f32>(state.xy, pressure, state.w));
+}
+
+// subtracts the pressure gradient, which leaves the velocity divergence free
+@compute @workgroup_size(8, 8, 1)
+fn project(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    if !inside(invocation_id.xy) {
+        return;
+    }
+    let cell = vec2<i32>(invocation_id.xy);
+    let state = load(cell);
+    let gradient = 0.5 * vec2<f32>(
+        load(cell + vec2<i32>(1, 0)).z - load(cell + vec2<i32>(-1, 0)).z,
+        load(cell + vec2<i32>(0, 1)).z - load(cell + vec2<i32>(0, -1)).z,
+    );
+    textureStore(output_tex, cell, vec4<f32>(state.xy - gradient, state.zw));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn display(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    if !inside(invocation_id.xy) {
+        return;
+    }
+    let cell = vec2<i32>(invocation_id.xy);
+    let color = 1.0 - exp(-2.0 * dye[dye_index(cell)].rgb);
+    textureStore(display_tex, cell, vec4<f32>(color, 1.0));
+}
@@ -0,0 +1,101 @@
+#import compute_playground::common
+
+struct GrayScottParams {
+    feed: f32,
+    kill: f32,
+    diffusion_u: f32,
+    diffusion_v: f32,
+    delta_time: f32,
+    seeds: u32,
+    brush_radius: f32,
+}
+
+@group(0) @binding(1)
+var<uniform> params: GrayScottParams;
+
+// U in red, V in green
+@group(1) @binding(0)
+var output_tex: texture_storage_2d<rgba16float, write>;
+
+@group(1) @binding(1)
+var input_tex: texture_2d<f32>;
+
+@group(1) @binding(2)
+var display_tex: texture_storage_2d<rgba8unorm, write>;
+
+fn inside(cell: vec2<u32>) -> bool {
+    return all(vec2<f32>(cell) < frame.size);
+}
+
+// the state wraps around at the edges
+fn load(cell: vec2<i32>) -> vec2<f32> {
+    let size = vec2<i32>(frame.size);
+    return textureLoad(input_tex, (cell % size + size) % size, 0).rg;
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn init(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    if !inside(invocation_id.xy) {
+        return;
+    }
+    let position = vec2<f32>(invocation_id.xy);
+    var v = 0.0;
+    for (var i = 0u; i < params.seeds; i++) {
+        let seed = hash(i ^ hash(frame.seed));
+        let center = vec2<f32>(randomFloat(seed), randomFloat(seed + 1u)) * frame.size;
+        if all(abs(position - center) < vec2<f32>(5.0)) {
+            v = 1.0;
+        }
+    }
+    textureStore(output_tex, vec2<i32>(invocation_id.xy), vec4<f32>(1.0 - 0.5 * v, v, 0.0, 1.0));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn update(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    if !inside(invocation_id.xy) {
+        return;
+    }
+    let cell = vec2<i32>(invocation_id.xy);
+    let uv = load(cell);
+
+    // 3x3 laplacian, 0.2 for edges and 0.05 for corners
+    var laplacian = -uv;
+    for (var x = -1; x <= 1; x++) {
+        for (var y = -1; y <= 1; y++) {
+            if x != 0 || y != 0 {
+                let weight = select(0.2, 0.05, x != 0 && y != 0);
+                laplacian += weight * load(cell + vec2<i32>(x, y));
+            }
+        }
+    }
+
+    let reaction = uv.x * uv.y * uv.y;
+    let du = params.diffusion_u * laplacian.x - reaction + params.feed * (1.0 - uv.x);
+    let dv = params.diffusion_v * laplacian.y + reaction - (params.kill + params.feed) * uv.y;
+    var next = clamp(uv + vec2<f32>(du, dv) * params.delta_time, vec2<f32>(0.0), vec2<f32>(1.0));
+
+    // left paints V, right wipes it
+    if distance(vec2<f32>(cell) + 0.5, frame.pointer) < params.brush_radius {
+        if (frame.buttons & 1u) != 0u {
+            next = vec2<f32>(0.5, 1.0);
+        } else if (frame.buttons & 2u) != 0u {
+            next = vec2<f32>(1.0, 0.0);
+        }
+    }
+    textureStore(output_tex, cell, vec4<f32>(next, 0.0, 1.0));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn display(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    if !inside(invocation_id.xy) {
+        return;
+    }
+    let cell = vec2<i32>(invocation_id.xy);
+    let uv = load(cell);
+    let t = smoothstep(0.0, 0.45, uv.y);
+    let low = vec3<f32>(0.02, 0.03, 0.08);
+    let mid = vec3<f32>(0.1, 0.5, 0.6);
+    let high = vec3<f32>(0.95, 0.95, 0.85);
+    let color = mix(mix(low, mid, min(t * 2.0, 1.0)), high, max(t * 2.0 - 1.0, 0.0));
+    textureStore(display_tex, cell, vec4<f32>(color, 1.0));
+}
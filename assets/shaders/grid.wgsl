@@ -0,0 +1,111 @@
+#define_import_path compute_playground::grid
+
+// A counting sort of items by grid cell in the `clear`, `count`, `scan`
+// and `scatter` passes, so every item only has to look at the items of
+// the 3x3 cells around it. The world wraps around at the edges.
+//
+// Needs `compute_playground::common`. Group 2 bindings 2 and 3 are the
+// cell buffers, see `cell_buffers` in grid.rs. The importing shader
+// defines
+//
+//     fn grid_cell_size() -> f32
+//     fn grid_item_count() -> u32
+//     fn grid_item_position(index: u32) -> vec2<f32>
+//     // copies the item at `index` to `slot` of the sorted items
+//     fn grid_sort(index: u32, slot: u32)
+
+// items per grid cell
+@group(2) @binding(2)
+var<storage, read_write> cell_counts: array<atomic<u32>>;
+
+// index of the first item of every cell in the sorted items
+@group(2) @binding(3)
+var<storage, read_write> cell_starts: array<u32>;
+
+// cells are at least `grid_cell_size` wide and fill the world exactly
+fn grid_size() -> vec2<u32> {
+    return max(vec2<u32>(frame.size / grid_cell_size()), vec2<u32>(1u));
+}
+
+fn cell_of(position: vec2<f32>) -> vec2<u32> {
+    let grid = grid_size();
+    let cell = vec2<u32>(max(position, vec2<f32>(0.0)) * vec2<f32>(grid) / frame.size);
+    return min(cell, grid - 1u);
+}
+
+fn cell_index(cell: vec2<u32>) -> u32 {
+    return cell.y * grid_size().x + cell.x;
+}
+
+fn wrap(position: vec2<f32>) -> vec2<f32> {
+    return position - floor(position / frame.size) * frame.size;
+}
+
+// shortest offset between two points of the wrapped world
+fn offset(a: vec2<f32>, b: vec2<f32>) -> vec2<f32> {
+    let d = b - a;
+    return d - round(d / frame.size) * frame.size;
+}
+
+// one thread per pixel, there are never more cells than pixels
+@compute @workgroup_size(8, 8, 1)
+fn clear(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let index = invocation_id.y * u32(frame.size.x) + invocation_id.x;
+    if invocation_id.x < u32(frame.size.x) && index < arrayLength(&cell_counts) {
+        atomicStore(&cell_counts[index], 0u);
+    }
+}
+
+@compute @workgroup_size(64, 1, 1)
+fn count(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    if invocation_id.x >= grid_item_count() {
+        return;
+    }
+    atomicAdd(&cell_counts[cell_index(cell_of(grid_item_position(invocation_id.x)))], 1u);
+}
+
+var<workgroup> partial_sum: array<u32, 256>;
+
+// exclusive prefix sum of the counts into the starts in a single
+// workgroup, the counts are zeroed again for `scatter`
+@compute @workgroup_size(256, 1, 1)
+fn scan(@builtin(local_invocation_index) index: u32) {
+    let grid = grid_size();
+    let n = grid.x * grid.y;
+    let chunk = (n + 255u) / 256u;
+    let start = min(index * chunk, n);
+    let end = min(start + chunk, n);
+    var sum = 0u;
+    for (var i = start; i < end; i++) {
+        sum += atomicLoad(&cell_counts[i]);
+    }
+    partial_sum[index] = sum;
+    workgroupBarrier();
+
+    if index == 0u {
+        var offset = 0u;
+        for (var i = 0u; i < 256u; i++) {
+            let c = partial_sum[i];
+            partial_sum[i] = offset;
+            offset += c;
+        }
+    }
+    workgroupBarrier();
+
+    var offset = partial_sum[index];
+    for (var i = start; i < end; i++) {
+        cell_starts[i] = offset;
+        offset += atomicExchange(&cell_counts[i], 0u);
+    }
+}
+
+// items of the same cell end up next to each other in any order, the
+// counts are back to the number of items per cell afterwards
+@compute @workgroup_size(64, 1, 1)
+fn scatter(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    if invocation_id.x >= grid_item_count() {
+        return;
+    }
+    let cell = cell_index(cell_of(grid_item_position(invocation_id.x)));
+    grid_sort(invocation_id.x, cell_starts[cell] + atomicAdd(&cell_counts[cell], 1u));
+}
@@ -0,0 +1,141 @@
+#import compute_playground::common
+
+struct LeniaParams {
+    // (2 radius + 1)² weights row by row, four per vector
+    kernel: array<vec4<f32>, 601>,
+    radius: u32,
+    mu: f32,
+    sigma: f32,
+    time_step: f32,
+    random_init: u32,
+    seeds: u32,
+    brush_radius: f32,
+}
+
+@group(0) @binding(1)
+var<uniform> params: LeniaParams;
+
+// the state in red
+@group(1) @binding(0)
+var output_tex: texture_storage_2d<rgba16float, write>;
+
+@group(1) @binding(1)
+var input_tex: texture_2d<f32>;
+
+@group(1) @binding(2)
+var display_tex: texture_storage_2d<rgba8unorm, write>;
+
+// width and height followed by the cells as f32 bits
+@group(2) @binding(0)
+var<storage, read_write> creature: array<u32>;
+
+// see `MAX_RADIUS` in lenia.rs
+const MAX_RADIUS: u32 = 24u;
+const TILE: u32 = 8u;
+// a tile with the kernel radius around it
+const TILE_SIDE: u32 = 56u;
+
+fn inside(cell: vec2<u32>) -> bool {
+    return all(vec2<f32>(cell) < frame.size);
+}
+
+// the state wraps around at the edges
+fn load(cell: vec2<i32>) -> f32 {
+    let size = vec2<i32>(frame.size);
+    return textureLoad(input_tex, (cell % size + size) % size, 0).r;
+}
+
+fn kernel_weight(index: u32) -> f32 {
+    return params.kernel[index / 4u][index % 4u];
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn init(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    if !inside(invocation_id.xy) {
+        return;
+    }
+    let cell = vec2<i32>(invocation_id.xy);
+    let position = vec2<f32>(invocation_id.xy);
+    var state = 0.0;
+    if params.random_init != 0u {
+        let extent = f32(params.radius) * 1.5;
+        for (var i = 0u; i < params.seeds; i++) {
+            let seed = hash(i ^ hash(frame.seed));
+            let center = vec2<f32>(randomFloat(seed), randomFloat(seed + 1u)) * frame.size;
+            if all(abs(position - center) < vec2<f32>(extent)) {
+                state = randomFloat(hash(invocation_id.x ^ hash(invocation_id.y ^ seed)));
+            }
+        }
+    } else {
+        let size = vec2<u32>(creature[0], creature[1]);
+        let origin = (vec2<i32>(frame.size) - vec2<i32>(size)) / 2;
+        let local = cell - origin;
+        if all(local >= vec2<i32>(0)) && all(local < vec2<i32>(size)) {
+            state = bitcast<f32>(creature[2u + u32(local.y) * size.x + u32(local.x)]);
+        }
+    }
+    textureStore(output_tex, cell, vec4<f32>(state, 0.0, 0.0, 1.0));
+}
+
+var<workgroup> tile: array<f32, 3136>;
+
+// convolves with the kernel from a tile of the state in workgroup memory,
+// every state pixel is read once per workgroup instead of once per pixel
+@compute @workgroup_size(8, 8, 1)
+fn update(
+    @builtin(global_invocation_id) invocation_id: vec3<u32>,
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+    @builtin(local_invocation_id) local_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+) {
+    let radius = min(params.radius, MAX_RADIUS);
+    let side = TILE + 2u * radius;
+    let origin = vec2<i32>(workgroup_id.xy * TILE) - i32(radius);
+    for (var i = local_index; i < side * side; i += TILE * TILE) {
+        let offset = vec2<u32>(i % side, i / side);
+        tile[offset.y * TILE_SIDE + offset.x] = load(origin + vec2<i32>(offset));
+    }
+    workgroupBarrier();
+
+    if !inside(invocation_id.xy) {
+        return;
+    }
+    let kernel_side = 2u * radius + 1u;
+    var potential = 0.0;
+    for (var y = 0u; y < kernel_side; y++) {
+        let row = (local_id.y + y) * TILE_SIDE + local_id.x;
+        for (var x = 0u; x < kernel_side; x++) {
+            potential += kernel_weight(y * kernel_side + x) * tile[row + x];
+        }
+    }
+
+    let cell = vec2<i32>(invocation_id.xy);
+    let state = tile[(local_id.y + radius) * TILE_SIDE + local_id.x + radius];
+    let difference = (potential - params.mu) / params.sigma;
+    let growth = 2.0 * exp(-0.5 * difference * difference) - 1.0;
+    var next = clamp(state + params.time_step * growth, 0.0, 1.0);
+
+    // left adds noise, right erases
+    if distance(vec2<f32>(cell) + 0.5, frame.pointer) < params.brush_radius {
+        if (frame.buttons & 1u) != 0u {
+            next = randomFloat(hash(invocation_id.x ^ hash(invocation_id.y ^ hash(frame.step))));
+        } else if (frame.buttons & 2u) != 0u {
+            next = 0.0;
+        }
+    }
+    textureStore(output_tex, cell, vec4<f32>(next, 0.0, 0.0, 1.0));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn display(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    if !inside(invocation_id.xy) {
+        return;
+    }
+    let cell = vec2<i32>(invocation_id.xy);
+    let t = load(cell);
+    let low = vec3<f32>(0.02, 0.01, 0.08);
+    let mid = vec3<f32>(0.55, 0.1, 0.45);
+    let high = vec3<f32>(1.0, 0.85, 0.3);
+    let color = mix(mix(low, mid, min(t * 2.0, 1.0)), high, max(t * 2.0 - 1.0, 0.0));
+    textureStore(display_tex, cell, vec4<f32>(color, 1.0));
+}
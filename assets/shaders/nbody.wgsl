@@ -0,0 +1,255 @@
+#import compute_playground::common
+#import compute_playground::trail
+
+struct NBodyParams {
+    scene: u32,
+    gravity: f32,
+    softening: f32,
+    time_step: f32,
+    mass: f32,
+    core_mass: f32,
+    // rows of the view rotation
+    view_x: vec3<f32>,
+    view_y: vec3<f32>,
+    zoom: f32,
+    brightness: f32,
+    trail: f32,
+}
+
+@group(0) @binding(1)
+var<uniform> params: NBodyParams;
+
+// the accumulated light in rgb
+@group(1) @binding(0)
+var output_tex: texture_storage_2d<rgba16float, write>;
+
+@group(1) @binding(1)
+var input_tex: texture_2d<f32>;
+
+@group(1) @binding(2)
+var display_tex: texture_storage_2d<rgba8unorm, write>;
+
+struct Body {
+    // mass in w
+    position: vec4<f32>,
+    // the group the body started in, for its color, in w
+    velocity: vec4<f32>,
+    acceleration: vec4<f32>,
+}
+
+@group(2) @binding(0)
+var<storage, read_write> bodies: array<Body>;
+
+// see `TILE` in nbody.rs
+const TILE: u32 = 256u;
+const TAU: f32 = 6.283185307;
+
+// the k-th random number of a body, different on every reset
+fn random(id: u32, k: u32) -> f32 {
+    return randomFloat(id ^ hash(k ^ hash(frame.seed)));
+}
+
+fn gaussian(id: u32, k: u32) -> f32 {
+    let u = max(random(id, k), 1e-7);
+    return sqrt(-2.0 * log(u)) * cos(TAU * random(id, k + 1u));
+}
+
+fn on_sphere(id: u32, k: u32) -> vec3<f32> {
+    let z = random(id, k) * 2.0 - 1.0;
+    let angle = random(id, k + 1u) * TAU;
+    let r = sqrt(1.0 - z * z);
+    return vec3<f32>(r * cos(angle), z, r * sin(angle));
+}
+
+// pulled in by a central mass and an exponential disk around it
+fn galaxy(id: u32, count: u32) -> Body {
+    var body: Body;
+    if id == 0u {
+        body.position = vec4<f32>(0.0, 0.0, 0.0, params.core_mass);
+        return body;
+    }
+    let scale_length = 0.3;
+    let disk_mass = params.mass;
+    // a gamma distribution of shape 2 matches the mass of an exponential disk
+    let r = min(-scale_length * log(max(random(id, 0u) * random(id, 1u), 1e-7)), 6.0 * scale_length);
+    let angle = random(id, 2u) * TAU;
+    let height = gaussian(id, 3u) * 0.02;
+    let x = r / scale_length;
+    let enclosed = params.core_mass + disk_mass * (1.0 - (1.0 + x) * exp(-x));
+    let softened = r * r + params.softening * params.softening;
+    let speed = sqrt(params.gravity * enclosed * r * r / (softened * sqrt(softened)));
+    body.position = vec4<f32>(r * cos(angle), height, r * sin(angle), disk_mass / f32(count - 1u));
+    body.velocity = vec4<f32>(-sin(angle) * speed, 0.0, cos(angle) * speed, 0.0);
+    return body;
+}
+
+// two Plummer spheres on a course to a glancing collision
+fn clusters(id: u32, count: u32) -> Body {
+    var body: Body;
+    let group = id % 2u;
+    let side = f32(group) * 2.0 - 1.0;
+    let scale = 0.15;
+    let cluster_mass = params.mass / 2.0;
+    let u = max(random(id, 0u), 1e-4);
+    let r = min(scale / sqrt(pow(u, -2.0 / 3.0) - 1.0), 10.0 * scale);
+    let center = vec3<f32>(side * 0.8, 0.0, side * 0.2);
+    body.position = vec4<f32>(center + on_sphere(id, 1u) * r, params.mass / f32(count));
+    // the velocity dispersion that keeps a Plummer sphere in equilibrium
+    let dispersion = sqrt(params.gravity * cluster_mass / (6.0 * sqrt(r * r + scale * scale)));
+    let thermal = vec3<f32>(gaussian(id, 3u), gaussian(id, 5u), gaussian(id, 7u)) * dispersion;
+    body.velocity = vec4<f32>(thermal + vec3<f32>(-side * 0.35, 0.0, 0.0), f32(group));
+    return body;
+}
+
+// at rest, collapsing under its own weight
+fn cube(id: u32, count: u32) -> Body {
+    var body: Body;
+    let position = vec3<f32>(random(id, 0u), random(id, 1u), random(id, 2u)) * 2.0 - 1.0;
+    body.position = vec4<f32>(position, params.mass / f32(count));
+    return body;
+}
+
+@compute @workgroup_size(64, 1, 1)
+fn init(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let id = invocation_id.x;
+    let count = arrayLength(&bodies);
+    if id >= count {
+        return;
+    }
+    // see `NBodyScene` in nbody.rs
+    switch params.scene {
+        case 0u: { bodies[id] = galaxy(id, count); }
+        case 1u: { bodies[id] = clusters(id, count); }
+        default: { bodies[id] = cube(id, count); }
+    }
+}
+
+// the image starts out dark
+@compute @workgroup_size(8, 8, 1)
+fn clear(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let cell = vec2<i32>(invocation_id.xy);
+    if all(vec2<f32>(invocation_id.xy) < frame.size) {
+        textureStore(output_tex, cell, vec4<f32>(0.0, 0.0, 0.0, 1.0));
+    }
+}
+
+var<workgroup> tile: array<vec4<f32>, 256>;
+
+// sums up the pull of all bodies, a tile of them at a time: every thread
+// of the workgroup loads one body into workgroup memory, then all threads
+// read the whole tile. Must be called from uniform control flow.
+fn gravity(id: u32, local_index: u32) -> vec3<f32> {
+    let count = arrayLength(&bodies);
+    var position = vec3<f32>(0.0);
+    if id < count {
+        position = bodies[id].position.xyz;
+    }
+    let softening = params.softening * params.softening;
+    var acceleration = vec3<f32>(0.0);
+    for (var start = 0u; start < count; start += TILE) {
+        let other = start + local_index;
+        // bodies past the end have no mass
+        var loaded = vec4<f32>(0.0);
+        if other < count {
+            loaded = bodies[other].position;
+        }
+        tile[local_index] = loaded;
+        workgroupBarrier();
+        for (var i = 0u; i < TILE; i++) {
+            let body = tile[i];
+            let offset = body.xyz - position;
+            let distance_squared = dot(offset, offset) + softening;
+            acceleration += offset * (body.w * inverseSqrt(distance_squared * distance_squared * distance_squared));
+        }
+        workgroupBarrier();
+    }
+    return acceleration * params.gravity;
+}
+
+// the first acceleration after a reset, so the first step can kick
+@compute @workgroup_size(256, 1, 1)
+fn accelerate(
+    @builtin(global_invocation_id) invocation_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+) {
+    let acceleration = gravity(invocation_id.x, local_index);
+    if invocation_id.x < arrayLength(&bodies) {
+        bodies[invocation_id.x].acceleration = vec4<f32>(acceleration, 0.0);
+    }
+}
+
+// leapfrog, kick-drift-kick: half a kick with the last acceleration and a
+// full drift here, the second half kick in `forces`
+@compute @workgroup_size(64, 1, 1)
+fn drift(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let id = invocation_id.x;
+    if id >= arrayLength(&bodies) {
+        return;
+    }
+    var body = bodies[id];
+    let velocity = body.velocity.xyz + body.acceleration.xyz * params.time_step * 0.5;
+    body.position = vec4<f32>(body.position.xyz + velocity * params.time_step, body.position.w);
+    body.velocity = vec4<f32>(velocity, body.velocity.w);
+    bodies[id] = body;
+}
+
+@compute @workgroup_size(256, 1, 1)
+fn forces(
+    @builtin(global_invocation_id) invocation_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+) {
+    let acceleration = gravity(invocation_id.x, local_index);
+    let id = invocation_id.x;
+    if id < arrayLength(&bodies) {
+        let velocity = bodies[id].velocity;
+        bodies[id].velocity = vec4<f32>(velocity.xyz + acceleration * params.time_step * 0.5, velocity.w);
+        bodies[id].acceleration = vec4<f32>(acceleration, 0.0);
+    }
+}
+
+fn group_color(group: f32) -> vec3<f32> {
+    if group > 0.5 {
+        return vec3<f32>(1.0, 0.65, 0.35);
+    }
+    return vec3<f32>(0.55, 0.7, 1.0);
+}
+
+// adds every body as a point of light, seen from the orbiting view
+@compute @workgroup_size(64, 1, 1)
+fn splat(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let id = invocation_id.x;
+    if id >= arrayLength(&bodies) {
+        return;
+    }
+    let body = bodies[id];
+    let view = vec2<f32>(dot(params.view_x, body.position.xyz), dot(params.view_y, body.position.xyz));
+    let pixel = vec2<i32>(floor(frame.size / 2.0 + view * vec2<f32>(1.0, -1.0) * params.zoom));
+    if any(pixel < vec2<i32>(0)) || any(vec2<f32>(pixel) >= frame.size) {
+        return;
+    }
+    deposit(vec2<u32>(pixel), vec4<f32>(group_color(body.velocity.w) * params.brightness, 0.0));
+}
+
+// moves the light of this step into the image, leaving a fading trail
+@compute @workgroup_size(8, 8, 1)
+fn draw(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    if any(vec2<f32>(invocation_id.xy) >= frame.size) {
+        return;
+    }
+    let cell = vec2<i32>(invocation_id.xy);
+    let added = take_deposits(invocation_id.xy).rgb;
+    let previous = textureLoad(input_tex, cell, 0).rgb;
+    // stays below the largest half float
+    let light = min(previous * params.trail + added, vec3<f32>(60000.0));
+    textureStore(output_tex, cell, vec4<f32>(light, 1.0));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn display(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    if any(vec2<f32>(invocation_id.xy) >= frame.size) {
+        return;
+    }
+    let cell = vec2<i32>(invocation_id.xy);
+    let value = textureLoad(input_tex, cell, 0).rgb;
+    textureStore(display_tex, cell, vec4<f32>(1.0 - exp(-value), 1.0));
+}
@@ -0,0 +1,99 @@
+#import compute_playground::common
+
+struct NewtonParams {
+    // roots in xy
+    roots: array<vec4<f32>, 8>,
+    root_count: u32,
+    iterations: u32,
+    damping: f32,
+    shading: f32,
+    // min x, min y, max x, max y
+    extents: vec4<f32>,
+    // 8 for none
+    highlighted: u32,
+    handle_radius: f32,
+}
+
+@group(0) @binding(1)
+var<uniform> params: NewtonParams;
+
+// the fractal does not keep a state, only the display image is used
+@group(1) @binding(2)
+var display_tex: texture_storage_2d<rgba8unorm, write>;
+
+const TOLERANCE: f32 = 0.0001;
+
+fn complex_inverse(z: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(z.x, -z.y) / dot(z, z);
+}
+
+fn hue(h: f32) -> vec3<f32> {
+    let k = (vec3<f32>(5.0, 3.0, 1.0) + h * 6.0) % 6.0;
+    return 1.0 - clamp(min(k, 4.0 - k), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+fn root_color(root: u32) -> vec3<f32> {
+    return mix(hue(f32(root) / f32(params.root_count)), vec3<f32>(1.0), 0.15);
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn display(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let pixel = vec2<f32>(invocation_id.xy);
+    if any(pixel >= frame.size) {
+        return;
+    }
+    // y points up in the plane and down in the image
+    let t = (pixel + 0.5) / frame.size;
+    var z = vec2<f32>(
+        mix(params.extents.x, params.extents.z, t.x),
+        mix(params.extents.w, params.extents.y, t.y),
+    );
+    let start = z;
+
+    // f / f' of a polynomial given by its roots is 1 / sum(1 / (z - root))
+    var root = params.root_count;
+    var smooth_iterations = f32(params.iterations);
+    var previous_distance = 1.0;
+    for (var i = 0u; i < params.iterations && root == params.root_count; i++) {
+        var sum = vec2<f32>(0.0);
+        var closest = 1e30;
+        for (var r = 0u; r < params.root_count; r++) {
+            let offset = z - params.roots[r].xy;
+            let distance = length(offset);
+            if distance < TOLERANCE {
+                root = r;
+                closest = distance;
+                break;
+            }
+            sum += complex_inverse(offset);
+            closest = min(closest, distance);
+        }
+        if root != params.root_count {
+            // interpolates between the iterations by how far the last step went
+            let fraction = log(previous_distance / TOLERANCE) / log(previous_distance / max(closest, 1e-30));
+            smooth_iterations = f32(i) - 1.0 + clamp(fraction, 0.0, 1.0);
+            break;
+        }
+        previous_distance = closest;
+        z -= params.damping * complex_inverse(sum);
+    }
+
+    var color = vec3<f32>(0.0);
+    if root != params.root_count {
+        let slowness = clamp(max(smooth_iterations, 0.0) / f32(params.iterations), 0.0, 1.0);
+        color = root_color(root) * (1.0 - params.shading * sqrt(slowness));
+    }
+
+    // handles of the roots, the highlighted one filled
+    let pixels_per_unit = frame.size.y / (params.extents.w - params.extents.y);
+    for (var r = 0u; r < params.root_count; r++) {
+        let distance = length(start - params.roots[r].xy) * pixels_per_unit;
+        let ring = abs(distance - params.handle_radius) < 1.5;
+        if ring || (r == params.highlighted && distance < params.handle_radius) {
+            color = select(root_color(r), vec3<f32>(1.0), ring);
+        } else if distance < params.handle_radius {
+            color = mix(color, vec3<f32>(0.0), 0.5);
+        }
+    }
+    textureStore(display_tex, vec2<i32>(invocation_id.xy), vec4<f32>(color, 1.0));
+}
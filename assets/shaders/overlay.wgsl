@@ -0,0 +1,52 @@
+struct Agent {
+    position: vec2<f32>,
+    angle: f32,
+    species: u32,
+}
+
+struct OverlayParams {
+    size: vec2<f32>,
+    stride: u32,
+    line_length: f32,
+    color: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<storage, read> agents: array<Agent>;
+
+@group(0) @binding(1)
+var<uniform> overlay: OverlayParams;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+}
+
+fn to_clip(position: vec2<f32>) -> vec4<f32> {
+    // texture y points down, clip space y points up
+    let uv = position / overlay.size;
+    return vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+}
+
+@vertex
+fn points(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let agent = agents[vertex_index * overlay.stride];
+    var out: VertexOutput;
+    out.position = to_clip(agent.position);
+    return out;
+}
+
+// two vertices per agent, from its position along its heading
+@vertex
+fn headings(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    let agent = agents[(vertex_index / 2u) * overlay.stride];
+    let direction = vec2<f32>(cos(agent.angle), sin(agent.angle));
+    let end = f32(vertex_index % 2u) * overlay.line_length;
+    var out: VertexOutput;
+    out.position = to_clip(agent.position + direction * end);
+    return out;
+}
+
+@fragment
+fn fragment() -> @location(0) vec4<f32> {
+    return overlay.color;
+}
@@ -0,0 +1,157 @@
+#import compute_playground::common
+#import compute_playground::grid
+#import compute_playground::trail
+
+struct ParticleLifeParams {
+    // row i in matrix[2i] and matrix[2i + 1]
+    matrix: array<vec4<f32>, 16>,
+    count: u32,
+    types: u32,
+    radius: f32,
+    repulsion: f32,
+    force: f32,
+    friction: f32,
+    cell_size: f32,
+}
+
+@group(0) @binding(1)
+var<uniform> params: ParticleLifeParams;
+
+// particle colors of the last step
+@group(1) @binding(0)
+var output_tex: texture_storage_2d<rgba16float, write>;
+
+@group(1) @binding(1)
+var input_tex: texture_2d<f32>;
+
+@group(1) @binding(2)
+var display_tex: texture_storage_2d<rgba8unorm, write>;
+
+struct Particle {
+    position: vec2<f32>,
+    velocity: vec2<f32>,
+    kind: u32,
+}
+
+@group(2) @binding(0)
+var<storage, read_write> particles: array<Particle>;
+
+// ordered by grid cell, written by `scatter`
+@group(2) @binding(4)
+var<storage, read_write> sorted: array<Particle>;
+
+fn hue(h: f32) -> vec3<f32> {
+    let k = vec3<f32>(0.0, 2.0 / 3.0, 1.0 / 3.0);
+    return clamp(abs(fract(h + k) * 6.0 - 3.0) - 1.0, vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+fn particle_count() -> u32 {
+    return min(params.count, arrayLength(&particles));
+}
+
+fn attraction(kind: u32, other: u32) -> f32 {
+    return params.matrix[kind * 2u + other / 4u][other % 4u];
+}
+
+fn grid_cell_size() -> f32 {
+    return params.cell_size;
+}
+
+fn grid_item_count() -> u32 {
+    return particle_count();
+}
+
+fn grid_item_position(index: u32) -> vec2<f32> {
+    return particles[index].position;
+}
+
+fn grid_sort(index: u32, slot: u32) {
+    sorted[slot] = particles[index];
+}
+
+@compute @workgroup_size(64, 1, 1)
+fn init(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let index = invocation_id.x;
+    if index >= particle_count() {
+        return;
+    }
+    let seed = hash(index ^ hash(frame.seed));
+    particles[index] = Particle(
+        vec2<f32>(randomFloat(seed), randomFloat(seed + 1u)) * frame.size,
+        vec2<f32>(0.0),
+        hash(seed + 2u) % params.types,
+    );
+}
+
+// repels below `repulsion`, then rises and falls back to zero at the
+// radius, peaking at the attraction
+fn force(distance: f32, attraction: f32) -> f32 {
+    if distance < params.repulsion {
+        return distance / params.repulsion - 1.0;
+    }
+    return attraction * (1.0 - abs(2.0 * distance - 1.0 - params.repulsion) / (1.0 - params.repulsion));
+}
+
+@compute @workgroup_size(64, 1, 1)
+fn update(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let index = invocation_id.x;
+    if index >= particle_count() {
+        return;
+    }
+    var particle = sorted[index];
+
+    var acceleration = vec2<f32>(0.0);
+    // 3x3 cells around the particle, fewer if the grid is smaller than that
+    let grid = grid_size();
+    let cell = cell_of(particle.position);
+    let span = min(grid, vec2<u32>(3u));
+    for (var y = 0u; y < span.y; y++) {
+        for (var x = 0u; x < span.x; x++) {
+            let other_cell = (cell + grid - 1u + vec2<u32>(x, y)) % grid;
+            let c = cell_index(other_cell);
+            let start = cell_starts[c];
+            let end = start + atomicLoad(&cell_counts[c]);
+            for (var j = start; j < end; j++) {
+                let other = sorted[j];
+                let d = offset(particle.position, other.position);
+                let distance = length(d);
+                if j == index || distance >= params.radius || distance == 0.0 {
+                    continue;
+                }
+                let f = force(distance / params.radius, attraction(particle.kind, other.kind));
+                acceleration += d / distance * f;
+            }
+        }
+    }
+
+    // large frame times would let particles jump through each other
+    let delta_time = min(frame.delta_time, 0.05);
+    let velocity = particle.velocity * pow(1.0 - params.friction, delta_time)
+        + acceleration * params.radius * params.force * delta_time;
+    particle.velocity = velocity;
+    particle.position = wrap(particle.position + velocity * delta_time);
+    particles[index] = particle;
+
+    let pixel = min(vec2<u32>(particle.position), vec2<u32>(frame.size) - 1u);
+    deposit(pixel, vec4<f32>(hue(f32(particle.kind) / f32(params.types)), 1.0));
+}
+
+// the state holds the particles drawn by the last step, clearing the deposits
+@compute @workgroup_size(8, 8, 1)
+fn draw(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    if any(vec2<f32>(invocation_id.xy) >= frame.size) {
+        return;
+    }
+    let color = take_deposits(invocation_id.xy);
+    textureStore(output_tex, vec2<i32>(invocation_id.xy), min(color, vec4<f32>(1.0)));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn display(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    if any(vec2<f32>(invocation_id.xy) >= frame.size) {
+        return;
+    }
+    let cell = vec2<i32>(invocation_id.xy);
+    let color = textureLoad(input_tex, cell, 0).rgb;
+    textureStore(display_tex, cell, vec4<f32>(color, 1.0));
+}
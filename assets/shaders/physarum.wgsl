@@ -0,0 +1,149 @@
+#import compute_playground::common
+#import compute_playground::trail
+#import compute_playground::agents
+
+struct PhysarumParams {
+    diffusion: f32,
+    evaporation: f32,
+    sensor_size: i32,
+    sensor_distance: f32,
+    sensor_angle_between: f32,
+    turn_speed: f32,
+    move_speed: f32,
+    deposit_mode: u32,
+    deposit_strength: f32,
+}
+
+@group(0) @binding(1)
+var<uniform> params: PhysarumParams;
+
+@group(1) @binding(0)
+var output_tex: texture_storage_2d<rgba8unorm, write>;
+
+@group(1) @binding(1)
+var input_tex: texture_2d<f32>;
+
+@group(1) @binding(2)
+var display_tex: texture_storage_2d<rgba8unorm, write>;
+
+struct Agents {
+    agents: array<Agent>,
+}
+
+@group(2) @binding(0)
+var<storage, read_write> agents: Agents;
+
+fn randomFloat01(value: u32) -> f32 {
+    return max(0.0, min(1.0, randomFloat(value)));
+}
+
+fn hue(h: f32) -> vec3<f32> {
+    let k = vec3<f32>(0.0, 2.0 / 3.0, 1.0 / 3.0);
+    return clamp(abs(fract(h + k) * 6.0 - 3.0) - 1.0, vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+fn deposit_color(agent: Agent) -> vec4<f32> {
+    switch params.deposit_mode {
+        case 1u: {
+            return vec4<f32>(hue(agent.angle / 6.2831853), 1.0);
+        }
+        case 2u: {
+            // 3 species, see `SPECIES` in physarum.rs
+            return vec4<f32>(hue(f32(agent.species) / 3.0), 1.0);
+        }
+        default: {
+            return vec4<f32>(1.0);
+        }
+    }
+}
+
+fn inside(location: vec2<u32>) -> bool {
+    return all(vec2<f32>(location) < frame.size);
+}
+
+fn smell(agent: Agent, trail: vec4<f32>) -> f32 {
+    return max(trail.r, max(trail.g, trail.b));
+}
+
+// keyed on the agent's state rather than its index, as the sort reorders
+// agents within a tile in any order
+fn agent_hash(agent: Agent) -> u32 {
+    let position = bitcast<vec2<u32>>(agent.position);
+    return hash(position.x ^ hash(position.y ^ hash(bitcast<u32>(agent.angle) ^ hash(agent.species))));
+}
+
+@compute @workgroup_size(64, 1, 1)
+fn update(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let location = invocation_id.x;
+    if location >= arrayLength(&agents.agents) {
+        return;
+    }
+    var agent = agents.agents[location];
+    var random = randomFloat(u32(agent.position.x) * hash(u32(agent.position.y)) + hash(agent_hash(agent) ^ hash(frame.seed ^ frame.step)));
+    var direction = vec2<f32>(cos(agent.angle), sin(agent.angle));
+    var new_pos = agent.position + direction * params.move_speed * frame.delta_time;
+
+    if new_pos.x < 0.0 || new_pos.x >= frame.size.x {
+        new_pos = min(new_pos, max(vec2<f32>(0.0), new_pos));
+        direction = vec2<f32>(-direction.x, direction.y);
+        agents.agents[location].angle = atan2(direction.y, direction.x);
+    }
+    if new_pos.y < 0.0 || new_pos.y >= frame.size.y {
+        new_pos = min(new_pos, max(vec2<f32>(0.0), new_pos));
+        direction = vec2<f32>(direction.x, -direction.y);
+        agents.agents[location].angle = atan2(direction.y, direction.x);
+    }
+
+    agents.agents[location].position = new_pos;
+
+    var w_forward = sensor(agent, 0.0, params.sensor_distance, params.sensor_size);
+    var w_left = sensor(agent, params.sensor_angle_between, params.sensor_distance, params.sensor_size);
+    var w_right = sensor(agent, -params.sensor_angle_between, params.sensor_distance, params.sensor_size);
+
+    var random_steer = randomFloat01(u32(random));
+
+    let turn = params.turn_speed * frame.delta_time;
+    if w_forward > w_left && w_forward > w_right {
+    } else if w_forward < w_left && w_forward < w_right {
+        agents.agents[location].angle += (random_steer - 0.5) * 0.2 * turn;
+    } else if w_right > w_left {
+        agents.agents[location].angle -= random_steer * turn;
+    } else if w_left > w_right {
+        agents.agents[location].angle += random_steer * turn;
+    }
+    let deposit_location = vec2<u32>(max(agent.position, vec2<f32>(0.0)));
+    if inside(deposit_location) {
+        deposit(deposit_location, deposit_color(agent) * params.deposit_strength);
+    }
+}
+
+// adds the deposits of this step to the trail map and clears them
+@compute @workgroup_size(8, 8, 1)
+fn resolve(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    if !inside(invocation_id.xy) {
+        return;
+    }
+    let location = vec2<i32>(invocation_id.xy);
+    let added = take_deposits(invocation_id.xy);
+    let previous = textureLoad(input_tex, location, 0);
+    textureStore(output_tex, location, min(previous + added, vec4<f32>(1.0)));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn blur(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    if !inside(invocation_id.xy) {
+        return;
+    }
+    let location = vec2<i32>(invocation_id.xy);
+    let trail = diffuse(input_tex, location, params.diffusion, params.evaporation, frame.delta_time, false);
+    textureStore(output_tex, location, trail);
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn display(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    if !inside(invocation_id.xy) {
+        return;
+    }
+    let location = vec2<i32>(invocation_id.xy);
+    textureStore(display_tex, location, textureLoad(input_tex, location, 0));
+}
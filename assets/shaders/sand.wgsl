@@ -0,0 +1,228 @@
+#import compute_playground::common
+
+struct FallingSandParams {
+    material: u32,
+    brush_radius: f32,
+    brush_density: f32,
+    burn_rate: f32,
+}
+
+@group(0) @binding(1)
+var<uniform> params: FallingSandParams;
+
+// material in the lowest byte, remaining life of fire and smoke in the
+// second and a color variation in the third
+@group(1) @binding(0)
+var output_tex: texture_storage_2d<r32uint, write>;
+
+@group(1) @binding(1)
+var input_tex: texture_2d<u32>;
+
+@group(1) @binding(2)
+var display_tex: texture_storage_2d<rgba8unorm, write>;
+
+// see `Material` in sand.rs
+const EMPTY: u32 = 0u;
+const SAND: u32 = 1u;
+const WATER: u32 = 2u;
+const STONE: u32 = 3u;
+const FIRE: u32 = 4u;
+const SMOKE: u32 = 5u;
+
+fn material(cell: u32) -> u32 {
+    return cell & 255u;
+}
+
+fn life(cell: u32) -> u32 {
+    return (cell >> 8u) & 255u;
+}
+
+fn make_cell(kind: u32, remaining: u32, random: u32) -> u32 {
+    return kind | (min(remaining, 255u) << 8u) | ((random & 255u) << 16u);
+}
+
+fn with_life(cell: u32, remaining: u32) -> u32 {
+    return (cell & ~(255u << 8u)) | (min(remaining, 255u) << 8u);
+}
+
+// heavier cells sink below lighter ones, stone never moves
+fn density(cell: u32) -> u32 {
+    switch material(cell) {
+        case 1u: { return 4u; }
+        case 2u: { return 3u; }
+        case 3u: { return 255u; }
+        case 4u: { return 1u; }
+        case 5u: { return 0u; }
+        default: { return 2u; }
+    }
+}
+
+fn fluid(cell: u32) -> bool {
+    let m = material(cell);
+    return m == WATER || m == FIRE || m == SMOKE;
+}
+
+// `a` sinks into `b` if it is heavier and neither is stone
+fn sinks(a: u32, b: u32) -> bool {
+    return material(a) != STONE && material(b) != STONE && density(a) > density(b);
+}
+
+fn inside(cell: vec2<i32>) -> bool {
+    return all(cell >= vec2<i32>(0)) && all(cell < vec2<i32>(frame.size));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn clear(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let cell = vec2<i32>(invocation_id.xy);
+    if inside(cell) {
+        textureStore(output_tex, cell, vec4<u32>(EMPTY));
+    }
+}
+
+// left paints the picked material, right erases
+@compute @workgroup_size(8, 8, 1)
+fn paint(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let cell = vec2<i32>(invocation_id.xy);
+    if !inside(cell) {
+        return;
+    }
+    var value = textureLoad(input_tex, cell, 0).r;
+    let random = hash(invocation_id.x ^ hash(invocation_id.y ^ hash(frame.step ^ frame.seed)));
+    let brushed = distance(vec2<f32>(cell) + 0.5, frame.pointer) < params.brush_radius;
+    if brushed && (frame.buttons & 2u) != 0u {
+        value = EMPTY;
+    } else if brushed && (frame.buttons & 1u) != 0u && randomFloat(random) < params.brush_density {
+        // fire and smoke live for a random number of block passes
+        value = make_cell(params.material, 40u + (random >> 24u) % 80u, random >> 8u);
+    }
+    textureStore(output_tex, cell, vec4<u32>(value));
+}
+
+fn swap(cells: ptr<function, array<u32, 4>>, a: u32, b: u32) {
+    let t = (*cells)[a];
+    (*cells)[a] = (*cells)[b];
+    (*cells)[b] = t;
+}
+
+// fire burns down into smoke, smoke fades away
+fn age(cell: u32, random: u32) -> u32 {
+    let m = material(cell);
+    if (m != FIRE && m != SMOKE) || randomFloat(random) >= params.burn_rate {
+        return cell;
+    }
+    let remaining = life(cell);
+    if remaining > 1u {
+        return with_life(cell, remaining - 1u);
+    }
+    if m == FIRE {
+        return make_cell(SMOKE, 60u, random >> 8u);
+    }
+    return EMPTY;
+}
+
+// moves the cells of one 2x2 block: index 0 and 1 are the top row, 2 and 3
+// the bottom row. Cells outside of the image are stone and aren't written.
+fn update_block(id: vec2<u32>, offset: i32) {
+    let origin = vec2<i32>(id) * 2 - offset;
+    if any(origin >= vec2<i32>(frame.size)) {
+        return;
+    }
+    var positions = array<vec2<i32>, 4>(
+        origin,
+        origin + vec2<i32>(1, 0),
+        origin + vec2<i32>(0, 1),
+        origin + vec2<i32>(1, 1),
+    );
+    var random = hash(id.x ^ hash(id.y ^ hash(frame.step ^ hash(frame.seed + u32(offset)))));
+    var cells = array<u32, 4>(STONE, STONE, STONE, STONE);
+    var has_water = false;
+    var has_fire = false;
+    for (var i = 0u; i < 4u; i++) {
+        if inside(positions[i]) {
+            random = hash(random);
+            cells[i] = age(textureLoad(input_tex, positions[i], 0).r, random);
+        }
+        has_water = has_water || material(cells[i]) == WATER;
+        has_fire = has_fire || material(cells[i]) == FIRE;
+    }
+
+    // water puts out fire, sometimes evaporating into smoke itself
+    if has_water && has_fire {
+        for (var i = 0u; i < 4u; i++) {
+            random = hash(random);
+            let m = material(cells[i]);
+            if m == FIRE || (m == WATER && randomFloat(random) < 0.2) {
+                cells[i] = make_cell(SMOKE, 30u, random >> 8u);
+            }
+        }
+    }
+
+    // straight down, or up for anything lighter than empty
+    for (var x = 0u; x < 2u; x++) {
+        if sinks(cells[x], cells[x + 2u]) {
+            swap(&cells, x, x + 2u);
+        }
+    }
+
+    // diagonally past a cell that holds it up, in a random direction
+    random = hash(random);
+    let first = random & 1u;
+    for (var i = 0u; i < 2u; i++) {
+        let x = first ^ i;
+        let other = 1u - x;
+        if !sinks(cells[x], cells[x + 2u]) && sinks(cells[x], cells[other + 2u])
+            && !sinks(cells[other], cells[other + 2u]) {
+            swap(&cells, x, other + 2u);
+        }
+    }
+
+    // liquids and gases spread sideways into lighter cells
+    for (var row = 0u; row < 4u; row += 2u) {
+        random = hash(random);
+        let a = cells[row];
+        let b = cells[row + 1u];
+        let spreads = (fluid(a) && sinks(a, b)) || (fluid(b) && sinks(b, a))
+            || (fluid(a) && fluid(b) && material(a) != material(b));
+        if spreads && randomFloat(random) < 0.5 {
+            swap(&cells, row, row + 1u);
+        }
+    }
+
+    for (var i = 0u; i < 4u; i++) {
+        if inside(positions[i]) {
+            textureStore(output_tex, positions[i], vec4<u32>(cells[i]));
+        }
+    }
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn blocks_even(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    update_block(invocation_id.xy, 0);
+}
+
+// blocks shifted by one cell, the first ones hang over the edge
+@compute @workgroup_size(8, 8, 1)
+fn blocks_odd(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    update_block(invocation_id.xy, 1);
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn display(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let cell = vec2<i32>(invocation_id.xy);
+    if !inside(cell) {
+        return;
+    }
+    let value = textureLoad(input_tex, cell, 0).r;
+    let shade = 0.85 + 0.15 * f32((value >> 16u) & 255u) / 255.0;
+    let fade = f32(life(value)) / 80.0;
+    var color = vec3<f32>(0.08, 0.08, 0.1);
+    switch material(value) {
+        case 1u: { color = vec3<f32>(0.86, 0.72, 0.44) * shade; }
+        case 2u: { color = vec3<f32>(0.2, 0.42, 0.86) * shade; }
+        case 3u: { color = vec3<f32>(0.47, 0.47, 0.5) * shade; }
+        case 4u: { color = mix(vec3<f32>(0.8, 0.15, 0.05), vec3<f32>(1.0, 0.8, 0.2), clamp(fade, 0.0, 1.0)); }
+        case 5u: { color = mix(color, vec3<f32>(0.6) * shade, clamp(fade, 0.0, 1.0)); }
+        default: {}
+    }
+    textureStore(display_tex, cell, vec4<f32>(color, 1.0));
+}
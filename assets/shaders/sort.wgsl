@@ -0,0 +1,102 @@
+#import compute_playground::common
+
+struct Agent {
+    position: vec2<f32>,
+    angle: f32,
+    species: u32,
+}
+struct Agents {
+    agents: array<Agent>,
+}
+
+@group(2) @binding(0)
+var<storage, read_write> agents: Agents;
+
+struct SortParams {
+    // 0: by tile, 1: pseudo random shuffle
+    key: u32,
+    seed: u32,
+}
+
+// agent count per key, turned into write offsets by `scan`
+@group(3) @binding(0)
+var<storage, read_write> counts: array<atomic<u32>>;
+
+@group(3) @binding(1)
+var<storage, read_write> sorted: array<Agent>;
+
+@group(3) @binding(2)
+var<uniform> sort: SortParams;
+
+const TILE_SIZE: u32 = 16u;
+
+fn tile_count() -> vec2<u32> {
+    return (vec2<u32>(frame.size) + TILE_SIZE - 1u) / TILE_SIZE;
+}
+
+fn key_count() -> u32 {
+    let count = tile_count();
+    return count.x * count.y;
+}
+
+fn key(index: u32) -> u32 {
+    if sort.key == 1u {
+        return hash(index ^ sort.seed) % key_count();
+    }
+    let count = tile_count();
+    let position = agents.agents[index].position;
+    let tile = min(vec2<u32>(max(position, vec2<f32>(0.0))) / TILE_SIZE, count - 1u);
+    return tile.y * count.x + tile.x;
+}
+
+@compute @workgroup_size(64,1,1)
+fn count(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    if invocation_id.x >= arrayLength(&agents.agents) {
+        return;
+    }
+    atomicAdd(&counts[key(invocation_id.x)], 1u);
+}
+
+var<workgroup> partial_sum: array<u32, 256>;
+
+// exclusive prefix sum over all counts in a single workgroup
+@compute @workgroup_size(256,1,1)
+fn scan(@builtin(local_invocation_index) index: u32) {
+    let n = key_count();
+    let chunk = (n + 255u) / 256u;
+    let start = min(index * chunk, n);
+    let end = min(start + chunk, n);
+    var sum = 0u;
+    for (var i = start; i < end; i++) {
+        sum += atomicLoad(&counts[i]);
+    }
+    partial_sum[index] = sum;
+    workgroupBarrier();
+
+    if index == 0u {
+        var offset = 0u;
+        for (var i = 0u; i < 256u; i++) {
+            let c = partial_sum[i];
+            partial_sum[i] = offset;
+            offset += c;
+        }
+    }
+    workgroupBarrier();
+
+    var offset = partial_sum[index];
+    for (var i = start; i < end; i++) {
+        let c = atomicLoad(&counts[i]);
+        atomicStore(&counts[i], offset);
+        offset += c;
+    }
+}
+
+// agents with the same key end up next to each other in any order
+@compute @workgroup_size(64,1,1)
+fn scatter(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    if invocation_id.x >= arrayLength(&agents.agents) {
+        return;
+    }
+    let destination = atomicAdd(&counts[key(invocation_id.x)], 1u);
+    sorted[destination] = agents.agents[invocation_id.x];
+}
@@ -0,0 +1,120 @@
+#import compute_playground::common
+
+@group(1) @binding(1)
+var input_tex : texture_2d<f32>;
+
+struct Agent {
+    position: vec2<f32>,
+    angle: f32,
+    species: u32,
+}
+struct Agents {
+    agents: array<Agent>,
+}
+
+@group(2) @binding(0)
+var<storage, read_write> agents: Agents;
+
+struct Stats {
+    // the sum passes 2^32 above 4096² pixels, it carries into the high word
+    intensity_sum_low: atomic<u32>,
+    intensity_sum_high: atomic<u32>,
+    intensity_max: atomic<u32>,
+    covered: atomic<u32>,
+    heading_x: atomic<i32>,
+    heading_y: atomic<i32>,
+    density_mean: f32,
+    density_variance: f32,
+}
+
+@group(3) @binding(0)
+var<storage, read_write> stats: Stats;
+
+// agent count per CELL_SIZE x CELL_SIZE cell
+@group(3) @binding(1)
+var<storage, read_write> cells: array<atomic<u32>>;
+
+const CELL_SIZE: u32 = 16u;
+// fixed point scale for the heading sums, keeps 1M agents inside i32
+const HEADING_SCALE: f32 = 256.0;
+
+fn cell_count() -> vec2<u32> {
+    return (vec2<u32>(frame.size) + CELL_SIZE - 1u) / CELL_SIZE;
+}
+
+var<workgroup> workgroup_sum: atomic<u32>;
+
+@compute @workgroup_size(8,8,1)
+fn trail_stats(
+    @builtin(global_invocation_id) invocation_id: vec3<u32>,
+    @builtin(local_invocation_index) index: u32,
+) {
+    // every thread has to reach the barrier, pixels outside count as empty
+    var intensity = 0u;
+    if all(invocation_id.xy < vec2<u32>(frame.size)) {
+        let color = textureLoad(input_tex, vec2<i32>(invocation_id.xy), 0);
+        intensity = u32(round(max(color.r, max(color.g, color.b)) * 255.0));
+    }
+    atomicAdd(&workgroup_sum, intensity);
+    atomicMax(&stats.intensity_max, intensity);
+    if intensity > 0u {
+        atomicAdd(&stats.covered, 1u);
+    }
+    workgroupBarrier();
+
+    if index == 0u {
+        let sum = atomicLoad(&workgroup_sum);
+        let low = atomicAdd(&stats.intensity_sum_low, sum);
+        if low + sum < low {
+            atomicAdd(&stats.intensity_sum_high, 1u);
+        }
+    }
+}
+
+@compute @workgroup_size(64,1,1)
+fn agent_stats(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    if invocation_id.x >= arrayLength(&agents.agents) {
+        return;
+    }
+    let agent = agents.agents[invocation_id.x];
+    atomicAdd(&stats.heading_x, i32(cos(agent.angle) * HEADING_SCALE));
+    atomicAdd(&stats.heading_y, i32(sin(agent.angle) * HEADING_SCALE));
+
+    let count = cell_count();
+    let cell = min(vec2<u32>(max(agent.position, vec2<f32>(0.0))) / CELL_SIZE, count - 1u);
+    atomicAdd(&cells[cell.y * count.x + cell.x], 1u);
+}
+
+var<workgroup> partial_sum: array<f32, 256>;
+var<workgroup> partial_sum_sq: array<f32, 256>;
+
+// a single workgroup reducing all cells
+@compute @workgroup_size(256,1,1)
+fn density_stats(@builtin(local_invocation_index) index: u32) {
+    let count = cell_count();
+    let n = count.x * count.y;
+    var sum = 0.0;
+    var sum_sq = 0.0;
+    for (var i = index; i < n; i += 256u) {
+        let c = f32(atomicLoad(&cells[i]));
+        sum += c;
+        sum_sq += c * c;
+    }
+    partial_sum[index] = sum;
+    partial_sum_sq[index] = sum_sq;
+    workgroupBarrier();
+
+    for (var stride = 128u; stride > 0u; stride >>= 1u) {
+        if index < stride {
+            partial_sum[index] += partial_sum[index + stride];
+            partial_sum_sq[index] += partial_sum_sq[index + stride];
+        }
+        workgroupBarrier();
+    }
+
+    if index == 0u {
+        let mean = partial_sum[0] / f32(n);
+        stats.density_mean = mean;
+        stats.density_variance = partial_sum_sq[0] / f32(n) - mean * mean;
+    }
+}
@@ -0,0 +1,76 @@
+#define_import_path compute_playground::trail
+
+// The trail map of the agent simulations: what moves deposits into
+// `deposits` during a step, `take_deposits` adds them to the state and
+// `diffuse` blurs and evaporates it.
+//
+// Needs `compute_playground::common`. Group 2 binding 1 is the deposits
+// buffer, see `deposit_buffer` in trail.rs.
+
+// rgba deposits of the current step in DEPOSIT_SCALE fixed point. Integer
+// atomics add up in any order, so deposits don't depend on thread scheduling.
+@group(2) @binding(1)
+var<storage, read_write> deposits: array<atomic<u32>>;
+
+const DEPOSIT_SCALE: f32 = 1024.0;
+
+fn deposit_index(pixel: vec2<u32>) -> u32 {
+    return 4u * (pixel.y * u32(frame.size.x) + pixel.x);
+}
+
+// adds to one channel of a pixel
+fn deposit_channel(pixel: vec2<u32>, channel: u32, amount: f32) {
+    atomicAdd(&deposits[deposit_index(pixel) + channel], u32(amount * DEPOSIT_SCALE));
+}
+
+fn deposit(pixel: vec2<u32>, amount: vec4<f32>) {
+    let index = deposit_index(pixel);
+    let scaled = vec4<u32>(amount * DEPOSIT_SCALE);
+    atomicAdd(&deposits[index], scaled.r);
+    atomicAdd(&deposits[index + 1u], scaled.g);
+    atomicAdd(&deposits[index + 2u], scaled.b);
+    atomicAdd(&deposits[index + 3u], scaled.a);
+}
+
+// the deposits of this step on a pixel, which are cleared for the next one
+fn take_deposits(pixel: vec2<u32>) -> vec4<f32> {
+    let index = deposit_index(pixel);
+    return vec4<f32>(
+        f32(atomicExchange(&deposits[index], 0u)),
+        f32(atomicExchange(&deposits[index + 1u], 0u)),
+        f32(atomicExchange(&deposits[index + 2u], 0u)),
+        f32(atomicExchange(&deposits[index + 3u], 0u)),
+    ) / DEPOSIT_SCALE;
+}
+
+// Pixels outside of the trail map read as empty, or from the other side
+// in a world that wraps around at the edges.
+fn trail_load(input: texture_2d<f32>, location: vec2<i32>, wrap: bool) -> vec4<f32> {
+    let size = vec2<i32>(textureDimensions(input));
+    if wrap {
+        return textureLoad(input, (location % size + size) % size, 0);
+    }
+    if any(location < vec2<i32>(0)) || any(location >= size) {
+        return vec4<f32>(0.0);
+    }
+    return textureLoad(input, location, 0);
+}
+
+// Blurs a pixel of the trail map with its 3x3 neighbourhood, then
+// evaporates a constant amount, both scaled by the time step.
+fn diffuse(input: texture_2d<f32>, location: vec2<i32>, diffusion: f32, evaporation: f32, delta_time: f32, wrap: bool) -> vec4<f32> {
+    let original = trail_load(input, location, wrap);
+
+    var sum = vec4<f32>(0.0);
+    for (var r = -1; r <= 1; r++) {
+        for (var c = -1; c <= 1; c++) {
+            if r != 0 || c != 0 {
+                sum += trail_load(input, location + vec2<i32>(r, c), wrap);
+            }
+        }
+    }
+    let avg = sum / 9.0;
+
+    let diffused = mix(original, avg, diffusion * delta_time);
+    return max(vec4<f32>(0.0), diffused - evaporation * delta_time);
+}
@@ -0,0 +1,246 @@
+#import compute_playground::common
+
+struct VolumeParams {
+    diffusion: f32,
+    evaporation: f32,
+    sensor_size: i32,
+    sensor_distance: f32,
+    sensor_angle_between: f32,
+    turn_speed: f32,
+    move_speed: f32,
+    deposit_strength: f32,
+    size: u32,
+    agents: u32,
+    // 0: raymarched volume, 1: slice
+    view: u32,
+    axis: u32,
+    slice: f32,
+    density: f32,
+    samples: u32,
+    // yaw and pitch of the orbiting camera
+    camera: vec2<f32>,
+}
+
+@group(0) @binding(1)
+var<uniform> params: VolumeParams;
+
+@group(1) @binding(2)
+var display_tex: texture_storage_2d<rgba8unorm, write>;
+
+struct VolumeAgent {
+    position: vec3<f32>,
+    yaw: f32,
+    pitch: f32,
+}
+
+@group(2) @binding(0)
+var<storage, read_write> agents: array<VolumeAgent>;
+
+// deposits of the current step in DEPOSIT_SCALE fixed point, one per voxel
+@group(2) @binding(1)
+var<storage, read_write> deposits: array<atomic<u32>>;
+
+// the trail after `diffuse`
+@group(3) @binding(0)
+var output_volume: texture_storage_3d<r32float, write>;
+
+// the trail volume the agents sense and the views show
+@group(3) @binding(1)
+var input_volume: texture_3d<f32>;
+
+const DEPOSIT_SCALE: f32 = 1024.0;
+
+fn direction(yaw: f32, pitch: f32) -> vec3<f32> {
+    return vec3<f32>(cos(pitch) * cos(yaw), sin(pitch), cos(pitch) * sin(yaw));
+}
+
+// the volume wraps around in all directions
+fn wrap(voxel: vec3<i32>) -> vec3<i32> {
+    let size = i32(params.size);
+    return (voxel % size + size) % size;
+}
+
+fn voxel_index(voxel: vec3<u32>) -> u32 {
+    return (voxel.z * params.size + voxel.y) * params.size + voxel.x;
+}
+
+fn load(voxel: vec3<i32>) -> f32 {
+    return textureLoad(input_volume, wrap(voxel), 0).r;
+}
+
+@compute @workgroup_size(64,1,1)
+fn init_agents(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let index = invocation_id.x;
+    if index >= params.agents {
+        return;
+    }
+    let seed = hash(index ^ hash(frame.seed));
+    // uniform inside a sphere around the center
+    let dir = direction(randomFloat(seed) * 6.2831853, asin(randomFloat(seed + 1u) * 2.0 - 1.0));
+    let radius = pow(randomFloat(seed + 2u), 1.0 / 3.0) * 0.3 * f32(params.size);
+    var agent: VolumeAgent;
+    agent.position = vec3<f32>(f32(params.size) * 0.5) + dir * radius;
+    agent.yaw = randomFloat(seed + 3u) * 6.2831853;
+    agent.pitch = asin(randomFloat(seed + 4u) * 2.0 - 1.0);
+    agents[index] = agent;
+}
+
+fn sensor(agent: VolumeAgent, yaw: f32, pitch: f32) -> f32 {
+    let sensor_dir = direction(agent.yaw + yaw, agent.pitch + pitch);
+    let sensor_mid = vec3<i32>(agent.position + sensor_dir * params.sensor_distance);
+
+    // a full cube grows quickly, at most 3x3x3 voxels are sensed
+    let r = min(params.sensor_size, 1);
+    var sum = 0.0;
+    for (var x = -r; x <= r; x++) {
+        for (var y = -r; y <= r; y++) {
+            for (var z = -r; z <= r; z++) {
+                sum += load(sensor_mid + vec3<i32>(x, y, z));
+            }
+        }
+    }
+    return sum;
+}
+
+// steering towards the stronger side, like the 2D agents do per angle
+fn steer(forward: f32, positive: f32, negative: f32, random: f32) -> f32 {
+    let turn = params.turn_speed * frame.delta_time;
+    if forward > positive && forward > negative {
+        return 0.0;
+    } else if forward < positive && forward < negative {
+        return (random - 0.5) * 0.2 * turn;
+    } else if negative > positive {
+        return -random * turn;
+    } else if positive > negative {
+        return random * turn;
+    }
+    return 0.0;
+}
+
+@compute @workgroup_size(64,1,1)
+fn update(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let index = invocation_id.x;
+    if index >= params.agents {
+        return;
+    }
+    var agent = agents[index];
+    let random = hash(index ^ hash(frame.seed ^ frame.step));
+
+    let angle = params.sensor_angle_between;
+    let forward = sensor(agent, 0.0, 0.0);
+    let yaw = steer(forward, sensor(agent, angle, 0.0), sensor(agent, -angle, 0.0), randomFloat(random));
+    let pitch = steer(forward, sensor(agent, 0.0, angle), sensor(agent, 0.0, -angle), randomFloat(random + 1u));
+    agent.yaw += yaw;
+    agent.pitch += pitch;
+
+    let size = f32(params.size);
+    let moved = agent.position + direction(agent.yaw, agent.pitch) * params.move_speed * frame.delta_time;
+    agent.position = moved - floor(moved / size) * size;
+    agents[index] = agent;
+
+    let voxel = min(vec3<u32>(agent.position), vec3<u32>(params.size - 1u));
+    atomicAdd(&deposits[voxel_index(voxel)], u32(params.deposit_strength * DEPOSIT_SCALE));
+}
+
+// diffuses and evaporates the trail and adds this step's deposits
+@compute @workgroup_size(4,4,4)
+fn diffuse(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    if any(invocation_id >= vec3<u32>(params.size)) {
+        return;
+    }
+    let voxel = vec3<i32>(invocation_id);
+    let original = textureLoad(input_volume, voxel, 0).r;
+
+    var sum = 0.0;
+    for (var x = -1; x <= 1; x++) {
+        for (var y = -1; y <= 1; y++) {
+            for (var z = -1; z <= 1; z++) {
+                sum += load(voxel + vec3<i32>(x, y, z));
+            }
+        }
+    }
+    let avg = sum / 27.0;
+
+    let blurred = mix(original, avg, params.diffusion * frame.delta_time);
+    let evaporated = max(0.0, blurred - params.evaporation * frame.delta_time);
+    let added = f32(atomicExchange(&deposits[voxel_index(invocation_id)], 0u)) / DEPOSIT_SCALE;
+    textureStore(output_volume, voxel, vec4<f32>(min(evaporated + added, 1.0)));
+}
+
+fn sample(position: vec3<f32>) -> f32 {
+    let voxel = min(vec3<u32>(max(position, vec3<f32>(0.0)) * f32(params.size)), vec3<u32>(params.size - 1u));
+    return textureLoad(input_volume, vec3<i32>(voxel), 0).r;
+}
+
+fn slice(uv: vec2<f32>) -> vec3<f32> {
+    if any(uv < vec2<f32>(0.0)) || any(uv >= vec2<f32>(1.0)) {
+        return vec3<f32>(0.0);
+    }
+    var position: vec3<f32>;
+    switch params.axis {
+        case 0u: {
+            position = vec3<f32>(params.slice, uv.y, uv.x);
+        }
+        case 1u: {
+            position = vec3<f32>(uv.x, params.slice, uv.y);
+        }
+        default: {
+            position = vec3<f32>(uv.x, uv.y, params.slice);
+        }
+    }
+    return vec3<f32>(sample(position));
+}
+
+fn heat(value: f32) -> vec3<f32> {
+    return mix(vec3<f32>(0.2, 0.4, 1.0), vec3<f32>(1.0, 0.9, 0.6), value);
+}
+
+// emission-absorption raymarch through the unit cube
+fn raymarch(uv: vec2<f32>) -> vec3<f32> {
+    let center = vec3<f32>(0.5);
+    let back = direction(params.camera.x, params.camera.y);
+    let forward = -back;
+    let right = normalize(cross(forward, vec3<f32>(0.0, 1.0, 0.0)));
+    let up = cross(right, forward);
+    let origin = center + back * 2.0;
+    let ray = normalize(forward + ((uv.x - 0.5) * right + (0.5 - uv.y) * up) * 0.8);
+
+    let t_a = (vec3<f32>(0.0) - origin) / ray;
+    let t_b = (vec3<f32>(1.0) - origin) / ray;
+    let t_near = max(max(min(t_a.x, t_b.x), min(t_a.y, t_b.y)), min(t_a.z, t_b.z));
+    let t_far = min(min(max(t_a.x, t_b.x), max(t_a.y, t_b.y)), max(t_a.z, t_b.z));
+    if t_far <= max(t_near, 0.0) {
+        return vec3<f32>(0.0);
+    }
+
+    let step = 1.7320508 / f32(max(params.samples, 1u));
+    var color = vec3<f32>(0.0);
+    var transmittance = 1.0;
+    for (var t = max(t_near, 0.0); t < t_far; t += step) {
+        let value = sample(origin + ray * t);
+        let alpha = 1.0 - exp(-value * params.density * step);
+        color += transmittance * alpha * heat(value);
+        transmittance *= 1.0 - alpha;
+        if transmittance < 0.01 {
+            break;
+        }
+    }
+    return color;
+}
+
+@compute @workgroup_size(8,8,1)
+fn display(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    if any(vec2<f32>(invocation_id.xy) >= frame.size) {
+        return;
+    }
+    // the square volume is fit into the view
+    let side = min(frame.size.x, frame.size.y);
+    let uv = (vec2<f32>(invocation_id.xy) + 0.5 - (frame.size - side) * 0.5) / side;
+    var color: vec3<f32>;
+    if params.view == 0u {
+        color = raymarch(uv);
+    } else {
+        color = slice(uv);
+    }
+    textureStore(display_tex, vec2<i32>(invocation_id.xy), vec4<f32>(color, 1.0));
+}
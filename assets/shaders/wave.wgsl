@@ -0,0 +1,178 @@
+#import compute_playground::common
+
+struct WaveParams {
+    // start in xy and end in zw as fractions of the window
+    sources: array<vec4<f32>, 16>,
+    source_count: u32,
+    speed: f32,
+    damping: f32,
+    frequency: f32,
+    amplitude: f32,
+    absorbing_border: f32,
+    tool: u32,
+    brush_radius: f32,
+    scene: u32,
+}
+
+@group(0) @binding(1)
+var<uniform> params: WaveParams;
+
+// height in red, velocity in green, walls in blue
+@group(1) @binding(0)
+var output_tex: texture_storage_2d<rgba32float, write>;
+
+@group(1) @binding(1)
+var input_tex: texture_2d<f32>;
+
+@group(1) @binding(2)
+var display_tex: texture_storage_2d<rgba8unorm, write>;
+
+// width and height followed by 1 for a wall and 0 for open space
+@group(2) @binding(0)
+var<storage, read_write> mask: array<u32>;
+
+// see `WaveTool` and `WaveScene` in wave.rs
+const TOOL_WALL: u32 = 2u;
+const SCENE_DOUBLE_SLIT: u32 = 1u;
+const SCENE_MASK: u32 = 2u;
+
+// pixels around a source that oscillate
+const SOURCE_WIDTH: f32 = 1.5;
+const TAU: f32 = 6.283185307;
+
+fn inside(cell: vec2<i32>) -> bool {
+    return all(cell >= vec2<i32>(0)) && all(cell < vec2<i32>(frame.size));
+}
+
+fn segment_distance(point: vec2<f32>, start: vec2<f32>, end: vec2<f32>) -> f32 {
+    let line = end - start;
+    let length_squared = dot(line, line);
+    var t = 0.0;
+    if length_squared > 0.0 {
+        t = clamp(dot(point - start, line) / length_squared, 0.0, 1.0);
+    }
+    return distance(point, start + line * t);
+}
+
+fn near_source(position: vec2<f32>) -> bool {
+    for (var i = 0u; i < params.source_count; i++) {
+        let source = params.sources[i];
+        if segment_distance(position, source.xy * frame.size, source.zw * frame.size) < SOURCE_WIDTH {
+            return true;
+        }
+    }
+    return false;
+}
+
+fn double_slit(position: vec2<f32>) -> bool {
+    let center = frame.size * vec2<f32>(0.33, 0.5);
+    if abs(position.x - center.x) > 2.0 {
+        return false;
+    }
+    let from_slit = abs(abs(position.y - center.y) - frame.size.y * 0.06);
+    return from_slit > frame.size.y * 0.015;
+}
+
+// the mask is stretched over the whole image
+fn mask_wall(position: vec2<f32>) -> bool {
+    let size = vec2<u32>(mask[0], mask[1]);
+    if any(size == vec2<u32>(0u)) {
+        return false;
+    }
+    let pixel = min(vec2<u32>(position / frame.size * vec2<f32>(size)), size - 1u);
+    return mask[2u + pixel.y * size.x + pixel.x] != 0u;
+}
+
+// flat water, only the walls of the scene
+@compute @workgroup_size(8, 8, 1)
+fn init(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let cell = vec2<i32>(invocation_id.xy);
+    if !inside(cell) {
+        return;
+    }
+    let position = vec2<f32>(cell) + 0.5;
+    var wall = false;
+    if params.scene == SCENE_DOUBLE_SLIT {
+        wall = double_slit(position);
+    } else if params.scene == SCENE_MASK {
+        wall = mask_wall(position);
+    }
+    textureStore(output_tex, cell, vec4<f32>(0.0, 0.0, f32(wall), 1.0));
+}
+
+// walls and the edges of the image hold the height at zero
+fn height(cell: vec2<i32>) -> f32 {
+    if !inside(cell) {
+        return 0.0;
+    }
+    let state = textureLoad(input_tex, cell, 0);
+    return state.r * f32(state.b < 0.5);
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn update(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let cell = vec2<i32>(invocation_id.xy);
+    if !inside(cell) {
+        return;
+    }
+    let position = vec2<f32>(cell) + 0.5;
+    let state = textureLoad(input_tex, cell, 0);
+    var wall = state.b > 0.5;
+
+    // left paints walls with the wall tool, right erases them with any tool
+    if distance(position, frame.pointer) < params.brush_radius {
+        if params.tool == TOOL_WALL && (frame.buttons & 1u) != 0u {
+            wall = true;
+        } else if (frame.buttons & 2u) != 0u {
+            wall = false;
+        }
+    }
+    if wall {
+        textureStore(output_tex, cell, vec4<f32>(0.0, 0.0, 1.0, 1.0));
+        return;
+    }
+
+    let laplacian = height(cell + vec2<i32>(1, 0)) + height(cell - vec2<i32>(1, 0))
+        + height(cell + vec2<i32>(0, 1)) + height(cell - vec2<i32>(0, 1)) - 4.0 * state.r;
+
+    // the damping rises smoothly towards the edges inside the absorbing border
+    var damping = params.damping;
+    if params.absorbing_border > 0.0 {
+        let edge = min(min(position.x, position.y), min(frame.size.x - position.x, frame.size.y - position.y));
+        let t = 1.0 - clamp(edge / params.absorbing_border, 0.0, 1.0);
+        damping += 0.2 * t * t;
+    }
+
+    var velocity = (state.g + params.speed * params.speed * laplacian) * (1.0 - damping);
+    var next = state.r + velocity;
+    if near_source(position) {
+        next = params.amplitude * sin(TAU * params.frequency * f32(frame.step));
+        velocity = 0.0;
+    }
+    textureStore(output_tex, cell, vec4<f32>(next, velocity, 0.0, 1.0));
+}
+
+// crests warm, troughs cold and calm water dark
+@compute @workgroup_size(8, 8, 1)
+fn display(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
+    let cell = vec2<i32>(invocation_id.xy);
+    if !inside(cell) {
+        return;
+    }
+    let state = textureLoad(input_tex, cell, 0);
+    let t = clamp(state.r / max(params.amplitude, 1e-6), -1.0, 1.0);
+    let calm = vec3<f32>(0.02, 0.02, 0.04);
+    var color = mix(calm, vec3<f32>(0.15, 0.45, 1.0), -t);
+    if t > 0.0 {
+        color = mix(calm, vec3<f32>(1.0, 0.55, 0.15), t);
+    }
+    // the colors saturate towards white at full amplitude
+    color = mix(color, vec3<f32>(1.0), max(abs(t) - 0.8, 0.0) * 2.5);
+    if state.b > 0.5 {
+        color = vec3<f32>(0.55, 0.55, 0.6);
+    }
+    if near_source(vec2<f32>(cell) + 0.5) {
+        color = vec3<f32>(0.9, 1.0, 0.9);
+    }
+    textureStore(display_tex, cell, vec4<f32>(color, 1.0));
+}
@@ -0,0 +1,189 @@
+use std::hash::Hash;
+
+use bevy::{
+    prelude::*,
+    render::{extract_resource::ExtractResource, render_resource::ShaderType},
+};
+use bevy_inspector_egui::prelude::*;
+
+use crate::{
+    simulation::{BufferInit, ComputeSimulationPlugin, Pass, Simulation},
+    trail,
+};
+
+/// Piles beyond this are ignored, the shader has a fixed size array.
+const MAX_FOOD_PILES: usize = 8;
+
+/// Must match the size of `Ant` in `ants.wgsl`.
+const ANT_SIZE: u64 = 24;
+
+/// Foraging ants, a relative of the slime agents with two pheromones.
+///
+/// Ants sense and deposit like the slime agents, but what they follow and
+/// lay down depends on whether they carry food: searching ants follow the
+/// to-food pheromone towards the food and mark the way home, ants carrying
+/// food follow the to-home pheromone back to the nest and mark the way to
+/// the food. Both fade with the time since an ant left the nest or the
+/// food. Every bite uses up a bit of a food pile. The left mouse button
+/// adds food, the right one removes it.
+pub(crate) struct AntsPlugin;
+impl Plugin for AntsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(ComputeSimulationPlugin::<AntColony>::default());
+    }
+}
+
+#[derive(Resource, ExtractResource, Clone, Reflect, InspectorOptions)]
+#[reflect(Resource, InspectorOptions)]
+pub(crate) struct AntColony {
+    #[inspector(min = 1, max = 1_000_000)]
+    count: u32,
+    /// Center as a fraction of the window.
+    nest: Vec2,
+    #[inspector(min = 1.0, max = 200.0)]
+    nest_radius: f32,
+    /// Center in `xy` as a fraction of the window, radius in pixels in `z`
+    /// and food per pixel in `w`. Placed on every reset, at most eight.
+    food_piles: Vec<Vec4>,
+    #[inspector(min = 0, max = 5)]
+    sensor_size: i32,
+    #[inspector(min = 0.0, max = 100.0)]
+    sensor_distance: f32,
+    #[inspector(min = 0.0, max = 3.0, speed = 0.01)]
+    sensor_angle_between: f32,
+    /// Radians per step.
+    #[inspector(min = 0.0, max = 3.0, speed = 0.01)]
+    turn_speed: f32,
+    /// Pixels per step.
+    #[inspector(min = 0.0, max = 10.0, speed = 0.01)]
+    move_speed: f32,
+    /// Pheromone laid down per step right after leaving the nest or food.
+    #[inspector(min = 0.0, max = 1.0, speed = 0.001)]
+    deposit: f32,
+    /// How fast the deposit fades per step away from the nest or food.
+    #[inspector(min = 0.0, max = 0.1, speed = 0.0001)]
+    deposit_decay: f32,
+    /// Fraction of the pheromones spread to the neighbours per step.
+    #[inspector(min = 0.0, max = 1.0, speed = 0.01)]
+    diffusion: f32,
+    /// Pheromone lost per step.
+    #[inspector(min = 0.0, max = 0.1, speed = 0.0001)]
+    evaporation: f32,
+    /// How strongly searching ants smell food compared to the pheromone.
+    #[inspector(min = 0.0, max = 100.0, speed = 0.1)]
+    food_attraction: f32,
+    /// Food a single ant carries away.
+    #[inspector(min = 0.0, max = 1.0, speed = 0.001)]
+    bite: f32,
+    #[inspector(min = 1.0, max = 100.0)]
+    brush_radius: f32,
+}
+
+impl Default for AntColony {
+    fn default() -> Self {
+        Self {
+            count: 1 << 14,
+            nest: Vec2::new(0.5, 0.5),
+            nest_radius: 15.0,
+            food_piles: vec![
+                Vec4::new(0.15, 0.2, 30.0, 1.0),
+                Vec4::new(0.85, 0.3, 25.0, 1.0),
+                Vec4::new(0.3, 0.85, 40.0, 1.0),
+            ],
+            sensor_size: 1,
+            sensor_distance: 12.0,
+            sensor_angle_between: 0.6,
+            turn_speed: 0.4,
+            move_speed: 1.0,
+            deposit: 0.1,
+            deposit_decay: 0.002,
+            diffusion: 0.05,
+            evaporation: 0.001,
+            food_attraction: 10.0,
+            bite: 0.02,
+            brush_radius: 15.0,
+        }
+    }
+}
+
+#[derive(ShaderType)]
+pub(crate) struct AntParams {
+    food_piles: [Vec4; MAX_FOOD_PILES],
+    pile_count: u32,
+    nest: Vec2,
+    nest_radius: f32,
+    sensor_size: i32,
+    sensor_distance: f32,
+    sensor_angle_between: f32,
+    turn_speed: f32,
+    move_speed: f32,
+    deposit: f32,
+    deposit_decay: f32,
+    diffusion: f32,
+    evaporation: f32,
+    food_attraction: f32,
+    bite: f32,
+    brush_radius: f32,
+}
+
+impl Simulation for AntColony {
+    const NAME: &'static str = "ants";
+    const SHADER: &'static str = "shaders/ants.wgsl";
+    const ENTRY_POINTS: &'static [&'static str] = &["spawn", "setup", "update", "resolve"];
+    const STEPS_PER_FRAME: u32 = 2;
+    const BUFFERS: &'static [&'static str] = &["ants", "ant_deposits"];
+
+    type Params = AntParams;
+
+    fn params(&self) -> AntParams {
+        let mut food_piles = [Vec4::ZERO; MAX_FOOD_PILES];
+        for (slot, pile) in food_piles.iter_mut().zip(&self.food_piles) {
+            *slot = *pile;
+        }
+        AntParams {
+            food_piles,
+            pile_count: self.food_piles.len().min(MAX_FOOD_PILES) as u32,
+            nest: self.nest,
+            nest_radius: self.nest_radius,
+            sensor_size: self.sensor_size,
+            sensor_distance: self.sensor_distance,
+            sensor_angle_between: self.sensor_angle_between,
+            turn_speed: self.turn_speed,
+            move_speed: self.move_speed,
+            deposit: self.deposit,
+            deposit_decay: self.deposit_decay,
+            diffusion: self.diffusion,
+            evaporation: self.evaporation,
+            food_attraction: self.food_attraction,
+            bite: self.bite,
+            brush_radius: self.brush_radius,
+        }
+    }
+
+    /// The ants buffer is sized for the count.
+    fn restart_key(&self) -> impl Hash {
+        self.count
+    }
+
+    /// The ants and both pheromones plus the bites per pixel.
+    fn buffers(&self, size: UVec2) -> Vec<BufferInit> {
+        vec![
+            BufferInit::Zeroed(self.count.max(1) as u64 * ANT_SIZE),
+            trail::deposit_buffer(size),
+        ]
+    }
+
+    fn init_passes(&self) -> Vec<Pass> {
+        vec![
+            Pass::items("spawn", self.count),
+            Pass::pixels("setup").swap(),
+        ]
+    }
+
+    fn passes(&self) -> Vec<Pass> {
+        vec![
+            Pass::items("update", self.count),
+            Pass::pixels("resolve").swap(),
+        ]
+    }
+}
@@ -0,0 +1,170 @@
+use std::hash::Hash;
+
+use bevy::{
+    prelude::*,
+    render::{extract_resource::ExtractResource, render_resource::ShaderType},
+};
+use bevy_inspector_egui::prelude::*;
+
+use crate::{
+    grid,
+    simulation::{BufferInit, ComputeSimulationPlugin, Pass, Simulation},
+    trail,
+};
+
+/// Bytes of a boid, its position and velocity.
+const BOID_SIZE: u64 = 16;
+
+/// Boids flocking by separation, alignment and cohesion in a world that
+/// wraps around at the edges.
+///
+/// Every step a counting sort by grid cell, with cells as large as the
+/// perception radius, orders the boids so each only looks at the 3x3
+/// cells around it. Boids deposit their heading color into the state,
+/// which optionally diffuses and evaporates into trails like the agents'
+/// trail map. The left mouse button attracts boids, the right one
+/// scatters them.
+pub(crate) struct BoidsPlugin;
+impl Plugin for BoidsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(ComputeSimulationPlugin::<Boids>::default());
+    }
+}
+
+#[derive(Resource, ExtractResource, Clone, Reflect, InspectorOptions)]
+#[reflect(Resource, InspectorOptions)]
+pub(crate) struct Boids {
+    #[inspector(min = 1, max = 2_000_000)]
+    count: u32,
+    /// Distance within which boids see each other, also the grid cell size.
+    #[inspector(min = 4.0, max = 100.0)]
+    perception_radius: f32,
+    /// Neighbours closer than this are avoided.
+    #[inspector(min = 0.0, max = 100.0)]
+    separation_distance: f32,
+    #[inspector(min = 0.0, max = 1000.0)]
+    separation: f32,
+    #[inspector(min = 0.0, max = 10.0, speed = 0.01)]
+    alignment: f32,
+    #[inspector(min = 0.0, max = 10.0, speed = 0.01)]
+    cohesion: f32,
+    #[inspector(min = 0.0, max = 500.0)]
+    min_speed: f32,
+    #[inspector(min = 0.0, max = 500.0)]
+    max_speed: f32,
+    /// Keep the deposits of earlier steps, diffusing and evaporating.
+    trails: bool,
+    #[inspector(min = 0.0, max = 1.0, speed = 0.01)]
+    diffusion: f32,
+    #[inspector(min = 0.0, max = 10.0, speed = 0.01)]
+    evaporation: f32,
+    #[inspector(min = 1.0, max = 500.0)]
+    pointer_radius: f32,
+}
+
+impl Default for Boids {
+    fn default() -> Self {
+        Self {
+            count: 200_000,
+            perception_radius: 12.0,
+            separation_distance: 4.0,
+            separation: 150.0,
+            alignment: 2.0,
+            cohesion: 1.0,
+            min_speed: 30.0,
+            max_speed: 90.0,
+            trails: true,
+            diffusion: 0.2,
+            evaporation: 2.4,
+            pointer_radius: 120.0,
+        }
+    }
+}
+
+#[derive(ShaderType)]
+pub(crate) struct BoidsParams {
+    count: u32,
+    perception_radius: f32,
+    /// Side of a grid cell, at least the perception radius.
+    cell_size: f32,
+    separation_distance: f32,
+    separation: f32,
+    alignment: f32,
+    cohesion: f32,
+    min_speed: f32,
+    max_speed: f32,
+    trails: u32,
+    diffusion: f32,
+    evaporation: f32,
+    pointer_radius: f32,
+}
+
+impl Simulation for Boids {
+    const NAME: &'static str = "boids";
+    const SHADER: &'static str = "shaders/boids.wgsl";
+    const ENTRY_POINTS: &'static [&'static str] = &[
+        "init", "clear", "count", "scan", "scatter", "update", "fade",
+    ];
+    const BUFFERS: &'static [&'static str] = &[
+        "boids",
+        "boid_deposits",
+        "boid_cell_counts",
+        "boid_cell_starts",
+        "boids_sorted",
+    ];
+
+    type Params = BoidsParams;
+
+    fn params(&self) -> BoidsParams {
+        BoidsParams {
+            count: self.count,
+            perception_radius: self.perception_radius,
+            cell_size: grid::cell_size(self.perception_radius),
+            separation_distance: self.separation_distance,
+            separation: self.separation,
+            alignment: self.alignment,
+            cohesion: self.cohesion,
+            min_speed: self.min_speed,
+            max_speed: self.max_speed.max(self.min_speed),
+            trails: self.trails as u32,
+            diffusion: self.diffusion,
+            evaporation: self.evaporation,
+            pointer_radius: self.pointer_radius,
+        }
+    }
+
+    /// The buffers are sized for the boid count.
+    fn restart_key(&self) -> impl Hash {
+        self.count
+    }
+
+    /// The boids, their deposits, two `u32` per grid cell and the boids
+    /// again, sorted by cell.
+    fn buffers(&self, size: UVec2) -> Vec<BufferInit> {
+        let boids = self.count as u64 * BOID_SIZE;
+        let [cell_counts, cell_starts] = grid::cell_buffers(size);
+        vec![
+            BufferInit::Zeroed(boids),
+            trail::deposit_buffer(size),
+            cell_counts,
+            cell_starts,
+            BufferInit::Zeroed(boids),
+        ]
+    }
+
+    fn init_passes(&self) -> Vec<Pass> {
+        vec![Pass::items("init", self.count)]
+    }
+
+    fn passes(&self) -> Vec<Pass> {
+        let [clear, count, scan, scatter] = grid::sort_passes(self.count);
+        vec![
+            clear,
+            count,
+            scan,
+            scatter,
+            Pass::items("update", self.count),
+            Pass::pixels("fade").swap(),
+        ]
+    }
+}
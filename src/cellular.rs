@@ -0,0 +1,537 @@
+use std::{hash::Hash, str::FromStr};
+
+use bevy::{
+    prelude::*,
+    render::{
+        extract_resource::ExtractResource,
+        render_resource::{ShaderType, TextureFormat},
+    },
+};
+use bevy_inspector_egui::prelude::*;
+
+use crate::simulation::{
+    restart_simulation, BufferInit, ComputeSimulationPlugin, Pass, Simulation,
+};
+
+/// Neighbourhood ranges above this are rejected, every cell reads
+/// `(2 * range + 1)²` cells per step.
+const MAX_RANGE: u32 = 16;
+
+/// Patterns with more cells are rejected, together with their width and
+/// height they must fit into one storage buffer.
+const MAX_PATTERN_CELLS: u64 = BufferInit::MAX_SIZE / 4 - 2;
+
+/// Cellular automata on an integer state image.
+///
+/// Supports Life-like rules (`B3/S23`), Generations rules with decaying
+/// states (`B2/S/C3`) and Larger than Life rules
+/// (`R5,C0,M1,S34..58,B34..45,NM`). The initial state is random or an
+/// RLE pattern file, whose rule replaces the current one.
+pub(crate) struct CellularPlugin;
+impl Plugin for CellularPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(ComputeSimulationPlugin::<CellularAutomaton>::default())
+            .add_system(parse_rule.after(restart_simulation::<CellularAutomaton>));
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Debug, Reflect, FromReflect)]
+enum CellularInit {
+    #[default]
+    Random,
+    /// The pattern file, centered.
+    Pattern,
+}
+
+#[derive(Resource, ExtractResource, Clone, Reflect, InspectorOptions)]
+#[reflect(Resource, InspectorOptions)]
+pub(crate) struct CellularAutomaton {
+    /// `B3/S23`, `B2/S/C3`, `23/3/8` or `R5,C0,M1,S34..58,B34..45,NM`.
+    rule: String,
+    init: CellularInit,
+    /// Fraction of live cells of a random start.
+    #[inspector(min = 0.0, max = 1.0, speed = 0.01)]
+    density: f32,
+    /// RLE file loaded on every reset while `init` is `Pattern`.
+    pattern: String,
+    #[reflect(ignore)]
+    parsed: Rule,
+    #[reflect(ignore)]
+    cells: Option<Pattern>,
+}
+
+impl Default for CellularAutomaton {
+    fn default() -> Self {
+        Self {
+            rule: "B3/S23".to_owned(),
+            init: CellularInit::Random,
+            density: 0.3,
+            pattern: "patterns/gosper-glider-gun.rle".to_owned(),
+            parsed: Rule::default(),
+            cells: None,
+        }
+    }
+}
+
+#[derive(ShaderType)]
+pub(crate) struct CellularParams {
+    birth_mask: u32,
+    survival_mask: u32,
+    birth_min: u32,
+    birth_max: u32,
+    survival_min: u32,
+    survival_max: u32,
+    range: u32,
+    states: u32,
+    /// 0: Moore, 1: von Neumann
+    neighborhood: u32,
+    middle: u32,
+    random_init: u32,
+    density: f32,
+}
+
+impl Simulation for CellularAutomaton {
+    const NAME: &'static str = "cellular";
+    const SHADER: &'static str = "shaders/cellular.wgsl";
+    const ENTRY_POINTS: &'static [&'static str] = &["init", "update"];
+    const STATE_FORMAT: TextureFormat = TextureFormat::R32Uint;
+    const BUFFERS: &'static [&'static str] = &["cellular_pattern"];
+
+    type Params = CellularParams;
+
+    fn params(&self) -> CellularParams {
+        let rule = &self.parsed;
+        CellularParams {
+            birth_mask: rule.birth.mask,
+            survival_mask: rule.survival.mask,
+            birth_min: rule.birth.min,
+            birth_max: rule.birth.max,
+            survival_min: rule.survival.min,
+            survival_max: rule.survival.max,
+            range: rule.range,
+            states: rule.states,
+            neighborhood: match rule.neighborhood {
+                Neighborhood::Moore => 0,
+                Neighborhood::VonNeumann => 1,
+            },
+            middle: rule.middle as u32,
+            random_init: (self.init == CellularInit::Random || self.cells.is_none()) as u32,
+            density: self.density,
+        }
+    }
+
+    fn restart_key(&self) -> impl Hash {
+        self.init
+    }
+
+    /// Reloads the pattern, so edits to the file show up. Its rule
+    /// replaces the current one.
+    fn reload(&mut self, _seed: u32) {
+        if self.init != CellularInit::Pattern {
+            return;
+        }
+        let pattern = std::fs::read_to_string(&self.pattern)
+            .map_err(|err| err.to_string())
+            .and_then(|text| Pattern::from_rle(&text));
+        match pattern {
+            Ok(pattern) => {
+                if let Some(rule) = &pattern.rule {
+                    self.rule = rule.clone();
+                }
+                self.cells = Some(pattern);
+            }
+            Err(err) => {
+                warn!("loading {} failed: {err}", self.pattern);
+                self.cells = None;
+            }
+        }
+    }
+
+    /// The pattern as its width and height followed by one state per cell.
+    fn buffers(&self, _size: UVec2) -> Vec<BufferInit> {
+        let (size, cells) = match &self.cells {
+            Some(pattern) => (pattern.size, pattern.cells.as_slice()),
+            None => (UVec2::ZERO, &[][..]),
+        };
+        let words: Vec<u32> = [size.x, size.y]
+            .into_iter()
+            .chain(cells.iter().copied())
+            .collect();
+        vec![BufferInit::Data(bytemuck::cast_slice(&words).to_vec())]
+    }
+
+    fn init_passes(&self) -> Vec<Pass> {
+        vec![Pass::pixels("init").swap()]
+    }
+
+    fn passes(&self) -> Vec<Pass> {
+        vec![Pass::pixels("update").swap()]
+    }
+}
+
+fn parse_rule(mut settings: ResMut<CellularAutomaton>, mut parsed: Local<Option<String>>) {
+    if parsed.as_deref() == Some(settings.rule.as_str()) {
+        return;
+    }
+    *parsed = Some(settings.rule.clone());
+    match settings.rule.parse() {
+        Ok(rule) => settings.parsed = rule,
+        Err(err) => warn!("invalid rule {}: {err}", settings.rule),
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+enum Neighborhood {
+    #[default]
+    Moore,
+    VonNeumann,
+}
+
+/// Neighbour counts that lead to birth or survival. Counts below 32 are
+/// looked up in the mask, larger ones in `min..=max`.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+struct Counts {
+    mask: u32,
+    min: u32,
+    max: u32,
+}
+
+impl Counts {
+    /// Single digit counts as in `B36`.
+    fn digits(digits: &str, max_count: u32) -> Result<Self, String> {
+        let mut counts = Counts {
+            mask: 0,
+            min: 1,
+            max: 0,
+        };
+        for digit in digits.chars() {
+            match digit.to_digit(10) {
+                Some(count) if count <= max_count => counts.mask |= 1 << count,
+                _ => return Err(format!("{digit} is not a neighbour count")),
+            }
+        }
+        Ok(counts)
+    }
+
+    fn interval(min: u32, max: u32) -> Self {
+        let mask = (min..=max.min(31)).fold(0, |mask, count| mask | 1 << count);
+        Counts { mask, min, max }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct Rule {
+    birth: Counts,
+    survival: Counts,
+    /// Live, dead and the decaying states in between.
+    states: u32,
+    range: u32,
+    neighborhood: Neighborhood,
+    /// Whether a cell counts itself.
+    middle: bool,
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        "B3/S23".parse().unwrap()
+    }
+}
+
+impl FromStr for Rule {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        let text: String = text
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<String>()
+            .to_ascii_uppercase();
+        if text.starts_with('R') {
+            Self::larger_than_life(&text)
+        } else {
+            Self::life_like(&text)
+        }
+    }
+}
+
+impl Rule {
+    /// `B3/S23` and Generations `B2/S/C3` in any order, or `S/B` and `S/B/C`.
+    fn life_like(text: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = text.split('/').collect();
+        let (mut birth, mut survival, mut states) = (None, None, 2);
+        if parts
+            .iter()
+            .any(|part| part.starts_with(['B', 'S', 'C', 'G']))
+        {
+            for part in parts {
+                let mut chars = part.chars();
+                match (chars.next(), chars.as_str()) {
+                    (Some('B'), digits) => birth = Some(Counts::digits(digits, 8)?),
+                    (Some('S'), digits) => survival = Some(Counts::digits(digits, 8)?),
+                    (Some('C' | 'G'), count) => {
+                        states = count.parse().map_err(|_| "invalid state count")?
+                    }
+                    _ => return Err(format!("unexpected {part}")),
+                }
+            }
+        } else {
+            match parts.as_slice() {
+                [s, b] => {
+                    (survival, birth) = (Some(Counts::digits(s, 8)?), Some(Counts::digits(b, 8)?))
+                }
+                [s, b, c] => {
+                    (survival, birth) = (Some(Counts::digits(s, 8)?), Some(Counts::digits(b, 8)?));
+                    states = c.parse().map_err(|_| "invalid state count")?;
+                }
+                _ => return Err("expected B../S.. or S/B".to_owned()),
+            }
+        }
+        Ok(Rule {
+            birth: birth.ok_or("missing births")?,
+            survival: survival.ok_or("missing survivals")?,
+            states: states.max(2),
+            range: 1,
+            neighborhood: Neighborhood::Moore,
+            middle: false,
+        })
+    }
+
+    /// Golly's `R5,C0,M1,S34..58,B34..45,NM`.
+    fn larger_than_life(text: &str) -> Result<Self, String> {
+        let mut rule = Rule {
+            birth: Counts::default(),
+            survival: Counts::default(),
+            states: 2,
+            range: 1,
+            neighborhood: Neighborhood::Moore,
+            middle: false,
+        };
+        let number = |value: &str| -> Result<u32, String> {
+            value
+                .parse()
+                .map_err(|_| format!("{value} is not a number"))
+        };
+        let interval = |value: &str| -> Result<Counts, String> {
+            let (min, max) = value.split_once("..").unwrap_or((value, value));
+            Ok(Counts::interval(number(min)?, number(max)?))
+        };
+        for part in text.split(',') {
+            let mut chars = part.chars();
+            let (key, value) = (chars.next(), chars.as_str());
+            match key {
+                Some('R') => rule.range = number(value)?,
+                Some('C') => rule.states = number(value)?.max(2),
+                Some('M') => rule.middle = number(value)? == 1,
+                Some('S') => rule.survival = interval(value)?,
+                Some('B') => rule.birth = interval(value)?,
+                Some('N') => {
+                    rule.neighborhood = match value {
+                        "M" => Neighborhood::Moore,
+                        "N" => Neighborhood::VonNeumann,
+                        _ => return Err(format!("unknown neighbourhood {value}")),
+                    }
+                }
+                _ => return Err(format!("unexpected {part}")),
+            }
+        }
+        if rule.range == 0 || rule.range > MAX_RANGE {
+            return Err(format!("the range must be in 1..={MAX_RANGE}"));
+        }
+        Ok(rule)
+    }
+}
+
+/// Cells of an RLE pattern file, row by row.
+#[derive(Clone, Debug)]
+struct Pattern {
+    size: UVec2,
+    cells: Vec<u32>,
+    rule: Option<String>,
+}
+
+impl Pattern {
+    /// Reads the `x = 3, y = 3, rule = B3/S23` header and the runs of
+    /// `b`/`.` (dead), `o` (alive) and `A`..`X` with `p`..`y` prefixes
+    /// (multiple states).
+    fn from_rle(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines().filter(|line| !line.starts_with('#'));
+        let header = lines.next().ok_or("empty pattern")?;
+        let (mut size, mut rule) = (UVec2::ZERO, None);
+        for entry in header.split(',') {
+            let Some((key, value)) = entry.split_once('=') else {continue;};
+            let value = value.trim();
+            match key.trim() {
+                "x" => size.x = value.parse().map_err(|_| "invalid width")?,
+                "y" => size.y = value.parse().map_err(|_| "invalid height")?,
+                "rule" => rule = Some(value.to_owned()),
+                _ => (),
+            }
+        }
+        if size.x == 0 || size.y == 0 {
+            return Err("missing pattern size".to_owned());
+        }
+        if size.x as u64 * size.y as u64 > MAX_PATTERN_CELLS {
+            return Err(format!(
+                "{}x{} has more than {MAX_PATTERN_CELLS} cells",
+                size.x, size.y
+            ));
+        }
+
+        let mut cells = vec![0; (size.x * size.y) as usize];
+        let (mut x, mut y, mut count, mut prefix) = (0u32, 0u32, None::<u32>, 0);
+        'runs: for c in lines.flat_map(str::chars) {
+            let state = match c {
+                '0'..='9' => {
+                    let digit = c.to_digit(10).unwrap();
+                    count = Some(count.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+                    continue;
+                }
+                'p'..='y' => {
+                    prefix = c as u32 - 'p' as u32 + 1;
+                    continue;
+                }
+                'b' | '.' => 0,
+                'o' => 1,
+                'A'..='X' => prefix * 24 + c as u32 - 'A' as u32 + 1,
+                '$' => {
+                    y = y.saturating_add(count.take().unwrap_or(1));
+                    x = 0;
+                    continue;
+                }
+                '!' => break 'runs,
+                _ => continue,
+            };
+            prefix = 0;
+            let run = count.take().unwrap_or(1);
+            if y < size.y {
+                for x in x..x.saturating_add(run).min(size.x) {
+                    cells[(y * size.x + x) as usize] = state;
+                }
+            }
+            x = x.saturating_add(run);
+        }
+        Ok(Pattern { size, cells, rule })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counts(digits: &str) -> Counts {
+        Counts::digits(digits, 8).unwrap()
+    }
+
+    #[test]
+    fn life_like_rules() {
+        let rule: Rule = "B3/S23".parse().unwrap();
+        assert_eq!(rule.birth, counts("3"));
+        assert_eq!(rule.survival, counts("23"));
+        assert_eq!(rule.states, 2);
+        assert_eq!(rule.range, 1);
+        assert_eq!(rule.neighborhood, Neighborhood::Moore);
+
+        assert_eq!("s23/b3".parse::<Rule>(), Ok(rule.clone()));
+        assert_eq!("23/3".parse::<Rule>(), Ok(rule));
+        let rule: Rule = "S/B".parse().unwrap();
+        assert_eq!((rule.birth.mask, rule.survival.mask), (0, 0));
+    }
+
+    #[test]
+    fn generations_rules() {
+        let rule: Rule = "B2/S/C3".parse().unwrap();
+        assert_eq!(rule.birth, counts("2"));
+        assert_eq!(rule.survival, counts(""));
+        assert_eq!(rule.states, 3);
+
+        let rule: Rule = "23/3/8".parse().unwrap();
+        assert_eq!(rule.birth, counts("3"));
+        assert_eq!(rule.survival, counts("23"));
+        assert_eq!(rule.states, 8);
+    }
+
+    #[test]
+    fn larger_than_life_rules() {
+        let rule: Rule = "R5,C0,M1,S34..58,B34..45,NM".parse().unwrap();
+        assert_eq!(rule.range, 5);
+        assert_eq!(rule.states, 2);
+        assert!(rule.middle);
+        assert_eq!((rule.survival.min, rule.survival.max), (34, 58));
+        assert_eq!((rule.birth.min, rule.birth.max), (34, 45));
+        assert_eq!(rule.neighborhood, Neighborhood::Moore);
+
+        let rule: Rule = "R2,C3,M0,S2,B3..4,NN".parse().unwrap();
+        assert_eq!(rule.survival, Counts::interval(2, 2));
+        assert_eq!(rule.birth.mask, 1 << 3 | 1 << 4);
+        assert_eq!(rule.neighborhood, Neighborhood::VonNeumann);
+    }
+
+    #[test]
+    fn invalid_rules() {
+        for text in [
+            "",
+            "B3",
+            "B9/S23",
+            "B3/S23/X",
+            "3",
+            "B3/S23/Cx",
+            "R0,C0,M0,S1,B1,NM",
+            "R17,C0,M0,S1,B1,NM",
+            "R5,C0,M1,S34..,B34..45,NM",
+            "R5,NX",
+            "R5,,B1",
+        ] {
+            assert!(text.parse::<Rule>().is_err(), "{text} parsed");
+        }
+    }
+
+    #[test]
+    fn non_ascii_rules() {
+        for text in ["é3/S23", "B3/Ś23", "B3/S2³", "R5,ÉM", "R5,C0,NÑ", "ß"] {
+            assert!(text.parse::<Rule>().is_err(), "{text} parsed");
+        }
+    }
+
+    #[test]
+    fn rle_runs() {
+        let pattern = Pattern::from_rle("#C comment\nx = 3, y = 2\n2ob$\n.A pB!").unwrap();
+        assert_eq!(pattern.size, UVec2::new(3, 2));
+        assert_eq!(pattern.cells, [1, 1, 0, 0, 1, 26]);
+        assert_eq!(pattern.rule, None);
+    }
+
+    #[test]
+    fn rle_clips_runs() {
+        let pattern = Pattern::from_rle("x = 2, y = 1\n99999999999999999999o$$é3o!").unwrap();
+        assert_eq!(pattern.cells, [1, 1]);
+    }
+
+    #[test]
+    fn invalid_rle() {
+        for text in [
+            "",
+            "#N only a comment",
+            "x = 3",
+            "x = 0, y = 3",
+            "x = -1, y = 3",
+            "x = 100000, y = 100000",
+            "x = 8192, y = 8192",
+            "x = 1, y = 33554431",
+            "x = 4294967295, y = 4294967295",
+        ] {
+            assert!(Pattern::from_rle(text).is_err(), "{text:?} parsed");
+        }
+    }
+
+    #[test]
+    fn gosper_glider_gun() {
+        let pattern = Pattern::from_rle(include_str!("../patterns/gosper-glider-gun.rle")).unwrap();
+        assert_eq!(pattern.size, UVec2::new(36, 9));
+        assert_eq!(pattern.rule.as_deref(), Some("B3/S23"));
+        let alive = pattern.cells.iter().filter(|&&state| state == 1).count();
+        assert_eq!(alive, 36);
+        assert_eq!(pattern.cells[24], 1);
+        assert_eq!(pattern.cells[8 * 36 + 12..8 * 36 + 14], [1, 1]);
+        assert_eq!(pattern.rule.unwrap().parse::<Rule>(), Ok(Rule::default()));
+    }
+}
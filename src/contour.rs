@@ -0,0 +1,278 @@
+use std::{collections::HashMap, fmt::Write as _, fs, io, path::PathBuf};
+
+use bevy::prelude::*;
+
+use crate::{
+    image::intensity,
+    readback::{Readback, ReadbackId, ReadbackSource},
+    simulation::{SimulationDisplay, SimulationFrame},
+};
+
+/// Traces iso-contours of the displayed image into an SVG.
+///
+/// `F8` reads the displayed image back and writes one path per threshold in
+/// [`ContourSettings::thresholds`] to [`ContourSettings::path`].
+pub(crate) struct ContourPlugin;
+impl Plugin for ContourPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ContourSettings>()
+            .init_resource::<PendingContours>()
+            .add_system(contour_hotkey)
+            .add_system(finish_contours);
+    }
+}
+
+#[derive(Resource, Clone)]
+pub struct ContourSettings {
+    /// Trail intensities in `0..=1` to trace.
+    pub thresholds: Vec<f32>,
+    pub path: PathBuf,
+}
+
+impl Default for ContourSettings {
+    fn default() -> Self {
+        Self {
+            thresholds: vec![0.25, 0.5, 0.75],
+            path: PathBuf::from("contours.svg"),
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+struct PendingContours(Option<(ReadbackId, UVec2)>);
+
+fn contour_hotkey(
+    keys: Res<Input<KeyCode>>,
+    mut pending: ResMut<PendingContours>,
+    readback: Res<Readback>,
+    display: Res<SimulationDisplay>,
+    frame: Res<SimulationFrame>,
+) {
+    if keys.just_pressed(KeyCode::F8) && pending.0.is_none() {
+        pending.0 = Some((
+            readback.request(ReadbackSource::Image(display.0.clone())),
+            frame.size,
+        ));
+    }
+}
+
+fn finish_contours(
+    mut pending: ResMut<PendingContours>,
+    readback: Res<Readback>,
+    settings: Res<ContourSettings>,
+) {
+    let Some((id, size)) = pending.0 else {return;};
+    let Some(result) = readback.take(id) else {return;};
+    pending.0 = None;
+    let Ok(bytes) = result else {return;};
+
+    let intensity = intensity(&bytes);
+    match write_svg(&intensity, size, &settings) {
+        Ok(()) => info!("contours saved to {}", settings.path.display()),
+        Err(err) => error!(
+            "saving contours to {} failed: {err}",
+            settings.path.display()
+        ),
+    }
+}
+
+fn write_svg(intensity: &[f32], size: UVec2, settings: &ContourSettings) -> io::Result<()> {
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">",
+        size.x, size.y
+    );
+    for (i, threshold) in settings.thresholds.iter().enumerate() {
+        let hue = 360.0 * i as f32 / settings.thresholds.len() as f32;
+        let _ = write!(
+            svg,
+            "  <path data-threshold=\"{threshold}\" fill=\"none\" stroke=\"hsl({hue:.0},80%,45%)\" stroke-width=\"1\" d=\""
+        );
+        for contour in contours(intensity, size, *threshold) {
+            for (j, p) in contour.iter().enumerate() {
+                let command = if j == 0 { 'M' } else { 'L' };
+                let _ = write!(svg, "{command}{:.2} {:.2} ", p.x, p.y);
+            }
+            svg.push_str("Z ");
+        }
+        svg.push_str("\"/>\n");
+    }
+    svg.push_str("</svg>\n");
+    fs::write(&settings.path, svg)
+}
+
+/// Closed iso-lines of `values` at `threshold`, using marching squares.
+///
+/// Values are sampled at pixel centers, everything outside the image counts
+/// as zero so every contour is closed.
+pub(crate) fn contours(values: &[f32], size: UVec2, threshold: f32) -> Vec<Vec<Vec2>> {
+    let (w, h) = (size.x as i32, size.y as i32);
+    let value = |x: i32, y: i32| {
+        if x < 0 || y < 0 || x >= w || y >= h {
+            0.0
+        } else {
+            values[(y * w + x) as usize]
+        }
+    };
+    // edges are keyed by their first corner and direction, so segments of
+    // neighbouring cells share the exact same key
+    let horizontal = |x: i32, y: i32| ((x, y), false);
+    let vertical = |x: i32, y: i32| ((x, y), true);
+    let point = |((x, y), vertical): ((i32, i32), bool)| {
+        let (x1, y1) = if vertical { (x, y + 1) } else { (x + 1, y) };
+        let (a, b) = (value(x, y), value(x1, y1));
+        let t = ((threshold - a) / (b - a)).clamp(0.0, 1.0);
+        // pixel centers sit at +0.5
+        Vec2::new(x as f32, y as f32).lerp(Vec2::new(x1 as f32, y1 as f32), t) + 0.5
+    };
+
+    let mut segments = Vec::new();
+    for y in -1..h {
+        for x in -1..w {
+            let corners = [
+                value(x, y),
+                value(x + 1, y),
+                value(x + 1, y + 1),
+                value(x, y + 1),
+            ];
+            let case = corners
+                .iter()
+                .enumerate()
+                .fold(0, |case, (i, v)| case | ((*v >= threshold) as u8) << i);
+            let top = horizontal(x, y);
+            let right = vertical(x + 1, y);
+            let bottom = horizontal(x, y + 1);
+            let left = vertical(x, y);
+            let center_inside = corners.iter().sum::<f32>() / 4.0 >= threshold;
+            match case {
+                0 | 15 => {}
+                1 | 14 => segments.push((left, top)),
+                2 | 13 => segments.push((top, right)),
+                3 | 12 => segments.push((left, right)),
+                4 | 11 => segments.push((right, bottom)),
+                6 | 9 => segments.push((top, bottom)),
+                7 | 8 => segments.push((left, bottom)),
+                // saddles, resolved by the cell center
+                5 if center_inside => {
+                    segments.push((left, bottom));
+                    segments.push((top, right));
+                }
+                5 => {
+                    segments.push((left, top));
+                    segments.push((right, bottom));
+                }
+                10 if center_inside => {
+                    segments.push((left, top));
+                    segments.push((right, bottom));
+                }
+                10 => {
+                    segments.push((left, bottom));
+                    segments.push((top, right));
+                }
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    // join segments sharing an edge into closed loops
+    let mut by_edge: HashMap<_, Vec<usize>> = HashMap::new();
+    for (i, (a, b)) in segments.iter().enumerate() {
+        by_edge.entry(*a).or_default().push(i);
+        by_edge.entry(*b).or_default().push(i);
+    }
+    let mut used = vec![false; segments.len()];
+    let mut contours = Vec::new();
+    for start in 0..segments.len() {
+        if used[start] {
+            continue;
+        }
+        used[start] = true;
+        let (first, mut edge) = segments[start];
+        let mut contour = vec![point(first)];
+        while edge != first {
+            contour.push(point(edge));
+            let Some(next) = by_edge[&edge].iter().copied().find(|s| !used[*s]) else {break;};
+            used[next] = true;
+            let (a, b) = segments[next];
+            edge = if a == edge { b } else { a };
+        }
+        contours.push(contour);
+    }
+    contours
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bounds(contour: &[Vec2]) -> (Vec2, Vec2) {
+        let min = contour.iter().copied().reduce(Vec2::min).unwrap();
+        let max = contour.iter().copied().reduce(Vec2::max).unwrap();
+        (min, max)
+    }
+
+    #[test]
+    fn single_cell() {
+        let contours = contours(&[1.0], UVec2::ONE, 0.5);
+        assert_eq!(contours.len(), 1);
+        let mut points = contours[0].clone();
+        points.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+        assert_eq!(
+            points,
+            [
+                Vec2::new(0.0, 0.5),
+                Vec2::new(0.5, 0.0),
+                Vec2::new(0.5, 1.0),
+                Vec2::new(1.0, 0.5),
+            ]
+        );
+    }
+
+    #[test]
+    fn below_threshold() {
+        assert!(contours(&[0.2; 4], UVec2::splat(2), 0.5).is_empty());
+    }
+
+    #[test]
+    fn saddle() {
+        let values = [1.0, 0.0, 0.0, 1.0];
+        // the mean of the saddle cell is inside, the diagonal is connected
+        assert_eq!(contours(&values, UVec2::splat(2), 0.5).len(), 1);
+        // and outside, each pixel gets its own contour
+        let separate = contours(&values, UVec2::splat(2), 0.6);
+        assert_eq!(separate.len(), 2);
+        for contour in separate {
+            assert_eq!(contour.len(), 4);
+        }
+    }
+
+    #[test]
+    fn closed_ring() {
+        #[rustfmt::skip]
+        let values = [
+            0.0, 0.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 1.0, 1.0, 0.0,
+            0.0, 1.0, 0.0, 1.0, 0.0,
+            0.0, 1.0, 1.0, 1.0, 0.0,
+            0.0, 0.0, 0.0, 0.0, 0.0,
+        ];
+        let mut contours = contours(&values, UVec2::splat(5), 0.5);
+        assert_eq!(contours.len(), 2);
+        contours.sort_by_key(|contour| contour.len());
+        assert_eq!(
+            bounds(&contours[0]),
+            (Vec2::new(2.0, 2.0), Vec2::new(3.0, 3.0))
+        );
+        assert_eq!(
+            bounds(&contours[1]),
+            (Vec2::new(1.0, 1.0), Vec2::new(4.0, 4.0))
+        );
+        // every point is the crossing of one edge, none is repeated
+        for contour in contours {
+            for (i, point) in contour.iter().enumerate() {
+                assert!(!contour[i + 1..].contains(point), "{point} repeated");
+            }
+        }
+    }
+}
@@ -0,0 +1,270 @@
+use std::f32::consts::TAU;
+
+use bevy::{prelude::*, tasks::ComputeTaskPool};
+
+use crate::{
+    physarum::{Agent, DepositMode, Physarum, SPECIES},
+    simulation::{register_simulation, SimulationDisplay, SimulationFrame},
+};
+
+/// Upper bound of agents simulated on the CPU, every nth agent is kept.
+const MAX_CPU_AGENTS: usize = 100_000;
+
+/// Runs the simulation on the CPU, for adapters without compute shaders.
+///
+/// Every step runs the `update`, `resolve` and `blur` passes of
+/// `physarum.wgsl` on the compute task pool, as many per frame as the GPU
+/// would, then the trail map is written into the display image the GPU
+/// backend renders to. Deposits are added up as floats rather than in
+/// fixed point, so the two backends drift apart over time. Only the
+/// agents run here, the other simulations and the GPU based tools like
+/// metrics or the overlay are not added.
+pub(crate) struct CpuPlugin;
+impl Plugin for CpuPlugin {
+    fn build(&self, app: &mut App) {
+        register_simulation::<Physarum>(app);
+        app.init_resource::<CpuTrail>()
+            .add_system(step.in_base_set(CoreSet::PostUpdate));
+    }
+}
+
+#[derive(Resource, Default)]
+struct CpuTrail {
+    size: UVec2,
+    agents: Vec<Agent>,
+    trail: Vec<Vec4>,
+    /// Deposits of the current step, then the diffused trail.
+    next: Vec<Vec4>,
+}
+
+/// Every nth of the spawned agents, at most [`MAX_CPU_AGENTS`].
+fn thin_agents(agents: &[Agent]) -> Vec<Agent> {
+    let every = agents.len().div_ceil(MAX_CPU_AGENTS).max(1);
+    let agents: Vec<_> = agents.iter().step_by(every).copied().collect();
+    info!("simulating {} agents on the CPU", agents.len());
+    agents
+}
+
+fn step(
+    data: Res<Physarum>,
+    frame: Res<SimulationFrame>,
+    mut cpu: ResMut<CpuTrail>,
+    display: Res<SimulationDisplay>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let size = frame.size;
+    if size.x == 0 || size.y == 0 {
+        return;
+    }
+    let pixels = (size.x * size.y) as usize;
+    // like the GPU buffers, the agents start over after a resize
+    if cpu.size != size || frame.reset {
+        *cpu = CpuTrail {
+            size,
+            agents: thin_agents(&data.agents),
+            trail: vec![Vec4::ZERO; pixels],
+            next: vec![Vec4::ZERO; pixels],
+        };
+    }
+    if frame.steps == 0 {
+        return;
+    }
+    for i in 0..frame.steps {
+        run_step(&mut cpu, &data, &frame, frame.step.wrapping_add(i));
+    }
+
+    let Some(image) = images.get_mut(&display.0) else {return;};
+    if image.data.len() != pixels * 4 {
+        return;
+    }
+    for (pixel, value) in image.data.chunks_exact_mut(4).zip(cpu.trail.iter()) {
+        let value = (value.clamp(Vec4::ZERO, Vec4::ONE) * 255.0).round();
+        pixel.copy_from_slice(&[value.x as u8, value.y as u8, value.z as u8, value.w as u8]);
+    }
+}
+
+/// One step of the agents, like the passes of `Physarum::passes`.
+fn run_step(cpu: &mut CpuTrail, data: &Physarum, frame: &SimulationFrame, step: u32) {
+    let pool = ComputeTaskPool::get();
+    let threads = pool.thread_num().max(1);
+    let size = cpu.size;
+    let CpuTrail {
+        agents,
+        trail,
+        next,
+        ..
+    } = cpu;
+
+    // deposits of the agents before they move, like the GPU update pass
+    next.fill(Vec4::ZERO);
+    for agent in agents.iter() {
+        let location = agent.positon.max(Vec2::ZERO).as_uvec2();
+        if location.x < size.x && location.y < size.y {
+            next[(location.y * size.x + location.x) as usize] +=
+                deposit_color(agent, data) * data.deposit.strength;
+        }
+    }
+
+    let chunk = agents.len().div_ceil(threads).max(1);
+    let sensed: &[Vec4] = trail;
+    pool.scope(|scope| {
+        for agents in agents.chunks_mut(chunk) {
+            scope.spawn(async move {
+                for agent in agents {
+                    update_agent(agent, data, frame, step, sensed);
+                }
+            });
+        }
+    });
+
+    for (value, deposit) in trail.iter_mut().zip(next.iter()) {
+        *value = (*value + *deposit).min(Vec4::ONE);
+    }
+
+    let rows = (size.y as usize).div_ceil(threads).max(1);
+    let source: &[Vec4] = trail;
+    pool.scope(|scope| {
+        for (i, target) in next.chunks_mut(rows * size.x as usize).enumerate() {
+            scope.spawn(async move {
+                for (j, value) in target.iter_mut().enumerate() {
+                    let index = i * rows * size.x as usize + j;
+                    let location = IVec2::new(
+                        (index % size.x as usize) as i32,
+                        (index / size.x as usize) as i32,
+                    );
+                    *value = diffuse(source, size, location, data, frame.delta_time);
+                }
+            });
+        }
+    });
+    std::mem::swap(trail, next);
+}
+
+/// Same as `hash` in `common.wgsl`.
+fn hash(value: u32) -> u32 {
+    let mut state = value;
+    state ^= 2747636419;
+    state = state.wrapping_mul(2654435769);
+    state ^= state >> 16;
+    state = state.wrapping_mul(2654435769);
+    state ^= state >> 16;
+    state = state.wrapping_mul(2654435769);
+    state
+}
+
+fn random_float(value: u32) -> f32 {
+    hash(value) as f32 / 4294967295.0
+}
+
+fn hue(h: f32) -> Vec3 {
+    let k = Vec3::new(0.0, 2.0 / 3.0, 1.0 / 3.0);
+    (((Vec3::splat(h) + k).fract() * 6.0 - 3.0).abs() - 1.0).clamp(Vec3::ZERO, Vec3::ONE)
+}
+
+fn deposit_color(agent: &Agent, data: &Physarum) -> Vec4 {
+    match data.deposit.mode {
+        DepositMode::White => Vec4::ONE,
+        DepositMode::Heading => hue(agent.angle / TAU).extend(1.0),
+        DepositMode::Species => hue(agent.species as f32 / SPECIES as f32).extend(1.0),
+    }
+}
+
+/// Same as `trail_load` in `trail.wgsl` without wrapping, the agents
+/// read pixels outside of the trail map as empty.
+fn trail_load(trail: &[Vec4], size: UVec2, location: IVec2) -> Vec4 {
+    if location.x < 0
+        || location.y < 0
+        || location.x >= size.x as i32
+        || location.y >= size.y as i32
+    {
+        return Vec4::ZERO;
+    }
+    trail[(location.y as u32 * size.x + location.x as u32) as usize]
+}
+
+/// Same as `sensor` in `agents.wgsl`, with `smell` of `physarum.wgsl`.
+fn sensor(agent: &Agent, sensor_angle: f32, data: &Physarum, trail: &[Vec4], size: UVec2) -> f32 {
+    let sensor_angle = agent.angle + sensor_angle;
+    let sensor_dir = Vec2::new(sensor_angle.cos(), sensor_angle.sin());
+    let sensor_mid = (agent.positon + sensor_dir * data.sensor.sensor_distance).as_ivec2();
+
+    let mut sum = 0.0;
+    let sensor_size = data.sensor.sensor_size;
+    for r in -sensor_size..=sensor_size {
+        for c in -sensor_size..=sensor_size {
+            let color = trail_load(trail, size, sensor_mid + IVec2::new(r, c));
+            sum += color.x.max(color.y).max(color.z);
+        }
+    }
+    sum
+}
+
+/// Same as `agent_hash` in `physarum.wgsl`.
+fn agent_hash(agent: &Agent) -> u32 {
+    hash(
+        agent.positon.x.to_bits()
+            ^ hash(agent.positon.y.to_bits() ^ hash(agent.angle.to_bits() ^ hash(agent.species))),
+    )
+}
+
+fn update_agent(
+    agent: &mut Agent,
+    data: &Physarum,
+    frame: &SimulationFrame,
+    step: u32,
+    trail: &[Vec4],
+) {
+    let size = frame.size;
+    let old = *agent;
+    let random = random_float(
+        (old.positon.x as u32)
+            .wrapping_mul(hash(old.positon.y as u32))
+            .wrapping_add(hash(agent_hash(&old) ^ hash(frame.seed ^ step))),
+    );
+    let mut direction = Vec2::new(old.angle.cos(), old.angle.sin());
+    let new_pos = old.positon + direction * data.agent.move_speed * frame.delta_time;
+
+    if new_pos.x < 0.0 || new_pos.x >= size.x as f32 {
+        direction.x = -direction.x;
+        agent.angle = direction.y.atan2(direction.x);
+    }
+    if new_pos.y < 0.0 || new_pos.y >= size.y as f32 {
+        direction.y = -direction.y;
+        agent.angle = direction.y.atan2(direction.x);
+    }
+    agent.positon = new_pos;
+
+    let angle = data.sensor.sensor_angle_between;
+    let w_forward = sensor(&old, 0.0, data, trail, size);
+    let w_left = sensor(&old, angle, data, trail, size);
+    let w_right = sensor(&old, -angle, data, trail, size);
+
+    let random_steer = random_float(random as u32).clamp(0.0, 1.0);
+    let turn = data.agent.turn_speed * frame.delta_time;
+    if w_forward > w_left && w_forward > w_right {
+    } else if w_forward < w_left && w_forward < w_right {
+        agent.angle += (random_steer - 0.5) * 0.2 * turn;
+    } else if w_right > w_left {
+        agent.angle -= random_steer * turn;
+    } else if w_left > w_right {
+        agent.angle += random_steer * turn;
+    }
+}
+
+/// Same as `diffuse` in `trail.wgsl` without wrapping, like `blur` calls it.
+fn diffuse(trail: &[Vec4], size: UVec2, location: IVec2, data: &Physarum, delta_time: f32) -> Vec4 {
+    let original = trail_load(trail, size, location);
+    let mut sum = Vec4::ZERO;
+    for r in -1..=1 {
+        for c in -1..=1 {
+            if r != 0 || c != 0 {
+                sum += trail_load(trail, size, location + IVec2::new(r, c));
+            }
+        }
+    }
+    let avg = sum / 9.0;
+
+    let trail = &data.trail;
+    let diffused = original.lerp(avg, trail.diffusion * delta_time);
+    (diffused - trail.evaporation * delta_time).max(Vec4::ZERO)
+}
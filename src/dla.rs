@@ -0,0 +1,116 @@
+use std::hash::Hash;
+
+use bevy::{
+    prelude::*,
+    render::{
+        extract_resource::ExtractResource,
+        render_resource::{ShaderType, TextureFormat},
+    },
+};
+use bevy_inspector_egui::prelude::*;
+
+use crate::simulation::{BufferInit, ComputeSimulationPlugin, Pass, Simulation};
+
+/// Diffusion-limited aggregation.
+///
+/// Random walkers in a storage buffer wander around until they end up
+/// next to the cluster in the state image, where they freeze with the
+/// stick probability and start over somewhere else. Every pixel of the
+/// cluster stores the step it joined at, which colors it. The left mouse
+/// button adds to the cluster, the right one erases.
+pub(crate) struct DlaPlugin;
+impl Plugin for DlaPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(ComputeSimulationPlugin::<Dla>::default());
+    }
+}
+
+/// Values match `seeded` in `dla.wgsl`.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Debug, Reflect, FromReflect)]
+enum SeedShape {
+    /// A single pixel in the center.
+    #[default]
+    Point = 0,
+    /// The bottom row.
+    Line = 1,
+    /// A ring around the center, growing inwards and outwards.
+    Circle = 2,
+}
+
+#[derive(Resource, ExtractResource, Clone, Reflect, InspectorOptions)]
+#[reflect(Resource, InspectorOptions)]
+pub(crate) struct Dla {
+    seed: SeedShape,
+    #[inspector(min = 1, max = 2_000_000)]
+    walkers: u32,
+    /// Chance of a walker next to the cluster to freeze per step, lower
+    /// values grow denser clusters.
+    #[inspector(min = 0.0, max = 1.0, speed = 0.01)]
+    stickiness: f32,
+    #[inspector(min = 1.0, max = 100.0)]
+    brush_radius: f32,
+}
+
+impl Default for Dla {
+    fn default() -> Self {
+        Self {
+            seed: SeedShape::Point,
+            walkers: 1 << 17,
+            stickiness: 1.0,
+            brush_radius: 5.0,
+        }
+    }
+}
+
+#[derive(ShaderType)]
+pub(crate) struct DlaParams {
+    seed_shape: u32,
+    stickiness: f32,
+    brush_radius: f32,
+}
+
+impl Simulation for Dla {
+    const NAME: &'static str = "dla";
+    const SHADER: &'static str = "shaders/dla.wgsl";
+    const ENTRY_POINTS: &'static [&'static str] = &["seed", "spawn", "walk", "grow"];
+    const STATE_FORMAT: TextureFormat = TextureFormat::R32Uint;
+    const STEPS_PER_FRAME: u32 = 16;
+    const BUFFERS: &'static [&'static str] = &["dla_walkers", "dla_occupied"];
+
+    type Params = DlaParams;
+
+    fn params(&self) -> DlaParams {
+        DlaParams {
+            seed_shape: self.seed as u32,
+            stickiness: self.stickiness,
+            brush_radius: self.brush_radius,
+        }
+    }
+
+    /// The seed and the walkers are only placed on a reset.
+    fn restart_key(&self) -> impl Hash {
+        (self.seed, self.walkers)
+    }
+
+    /// A pixel position per walker and a flag per pixel.
+    fn buffers(&self, size: UVec2) -> Vec<BufferInit> {
+        vec![
+            BufferInit::Zeroed(self.walkers.max(1) as u64 * 8),
+            BufferInit::Zeroed(size.x as u64 * size.y as u64 * 4),
+        ]
+    }
+
+    fn init_passes(&self) -> Vec<Pass> {
+        vec![
+            Pass::pixels("seed").swap(),
+            Pass::items("spawn", self.walkers),
+        ]
+    }
+
+    fn passes(&self) -> Vec<Pass> {
+        vec![
+            Pass::items("walk", self.walkers),
+            Pass::pixels("grow").swap(),
+        ]
+    }
+}
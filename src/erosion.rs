@@ -0,0 +1,535 @@
+use std::{hash::Hash, path::PathBuf};
+
+use bevy::{
+    core_pipeline::clear_color::ClearColorConfig,
+    prelude::*,
+    render::{
+        camera::Viewport,
+        extract_resource::ExtractResource,
+        mesh::{Indices, PrimitiveTopology},
+        render_resource::ShaderType,
+    },
+    window::PrimaryWindow,
+};
+use bevy_inspector_egui::{
+    bevy_egui::{egui, EguiContexts},
+    prelude::*,
+};
+
+use crate::{
+    image::fit_into_buffer,
+    readback::{Readback, ReadbackId, ReadbackSource},
+    simulation::{
+        simulation_active, ActiveSimulation, BufferInit, ComputeSimulationPlugin, Pass, Simulation,
+        SimulationFrame,
+    },
+};
+
+/// Must match `HEIGHT_SCALE` in `erosion.wgsl`, heights are stored as
+/// fixed point so droplets can change them with atomics.
+const HEIGHT_SCALE: f32 = 16777216.0;
+
+/// Must match `MAX_BRUSH_RADIUS` in `erosion.wgsl`.
+const MAX_BRUSH_RADIUS: u32 = 8;
+
+/// Must match the size of `Droplet` in `erosion.wgsl`.
+const DROPLET_SIZE: u64 = 32;
+
+/// Vertices along the longer side of the preview mesh.
+const PREVIEW_RESOLUTION: u32 = 256;
+
+/// Hydraulic erosion of a heightmap by water droplets, after Hans Theobald
+/// Beyer's "Implementation of a method for hydraulic erosion".
+///
+/// Every droplet is an agent in a storage buffer that moves a pixel
+/// downhill per step, picking up sediment while it speeds up and dropping
+/// it where it slows down or evaporates. The terrain starts out as fractal
+/// noise or a heightmap file, is shown as shaded relief and optionally as
+/// a 3D mesh in a corner of the window, and can be exported as a 16-bit
+/// grayscale png.
+pub(crate) struct ErosionPlugin;
+impl Plugin for ErosionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(ComputeSimulationPlugin::<Erosion>::default())
+            .init_resource::<PendingExport>()
+            .init_resource::<Preview>()
+            .add_systems((finish_export, update_preview))
+            .add_system(terrain_window.run_if(simulation_active(Erosion::NAME)));
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Debug, Reflect, FromReflect)]
+enum Terrain {
+    /// Fractal value noise, different on every reset.
+    #[default]
+    Noise,
+    /// The heightmap file, stretched to the window.
+    File,
+}
+
+#[derive(Resource, ExtractResource, Clone, Reflect, InspectorOptions)]
+#[reflect(Resource, InspectorOptions)]
+pub(crate) struct Erosion {
+    terrain: Terrain,
+    /// Grayscale image loaded on every reset while `terrain` is `File`,
+    /// black is the lowest and white the highest point.
+    heightmap: String,
+    /// Size in pixels of the largest noise features.
+    #[inspector(min = 1.0, max = 2000.0)]
+    noise_scale: f32,
+    #[inspector(min = 1, max = 12)]
+    octaves: u32,
+    /// Amplitude of each octave relative to the previous one.
+    #[inspector(min = 0.0, max = 1.0, speed = 0.01)]
+    persistence: f32,
+    /// Droplets moving at the same time.
+    #[inspector(min = 64, max = 1_000_000)]
+    droplets: u32,
+    /// Steps until a droplet dries up and starts over.
+    #[inspector(min = 1, max = 200)]
+    lifetime: u32,
+    /// How much a droplet keeps its direction instead of following the slope.
+    #[inspector(min = 0.0, max = 1.0, speed = 0.01)]
+    inertia: f32,
+    /// Sediment a droplet can carry per slope, speed and water.
+    #[inspector(min = 0.0, max = 32.0, speed = 0.1)]
+    capacity: f32,
+    /// Capacity on flat ground.
+    #[inspector(min = 0.0, max = 1.0, speed = 0.001)]
+    min_capacity: f32,
+    /// Fraction of the free capacity picked up per step.
+    #[inspector(min = 0.0, max = 1.0, speed = 0.01)]
+    erosion: f32,
+    /// Fraction of the excess sediment dropped per step.
+    #[inspector(min = 0.0, max = 1.0, speed = 0.01)]
+    deposition: f32,
+    /// Fraction of the water lost per step.
+    #[inspector(min = 0.0, max = 1.0, speed = 0.001)]
+    evaporation: f32,
+    #[inspector(min = 0.0, max = 32.0, speed = 0.1)]
+    gravity: f32,
+    /// Pixels around a droplet it erodes from.
+    #[inspector(min = 1, max = 8)]
+    brush_radius: u32,
+    /// Height exaggeration of the shading.
+    #[inspector(min = 1.0, max = 5000.0)]
+    relief: f32,
+    /// Shows the terrain as a mesh in the bottom right corner.
+    preview: bool,
+    /// Height of the mesh relative to its longer side.
+    #[inspector(min = 0.0, max = 2.0, speed = 0.01)]
+    preview_height: f32,
+    /// Mesh turns in radians per second.
+    preview_orbit_speed: f32,
+    /// Written by the export button of the "Terrain" window.
+    export_path: PathBuf,
+    #[reflect(ignore)]
+    loaded: Option<Heightmap>,
+}
+
+impl Default for Erosion {
+    fn default() -> Self {
+        Self {
+            terrain: Terrain::Noise,
+            heightmap: "heightmap.png".to_owned(),
+            noise_scale: 300.0,
+            octaves: 7,
+            persistence: 0.5,
+            droplets: 1 << 14,
+            lifetime: 30,
+            inertia: 0.05,
+            capacity: 4.0,
+            min_capacity: 0.01,
+            erosion: 0.3,
+            deposition: 0.3,
+            evaporation: 0.01,
+            gravity: 4.0,
+            brush_radius: 3,
+            relief: 400.0,
+            preview: false,
+            preview_height: 0.3,
+            preview_orbit_speed: 0.2,
+            export_path: PathBuf::from("heightmap-eroded.png"),
+            loaded: None,
+        }
+    }
+}
+
+#[derive(ShaderType)]
+pub(crate) struct ErosionParams {
+    from_file: u32,
+    noise_scale: f32,
+    octaves: u32,
+    persistence: f32,
+    lifetime: u32,
+    inertia: f32,
+    capacity: f32,
+    min_capacity: f32,
+    erosion: f32,
+    deposition: f32,
+    evaporation: f32,
+    gravity: f32,
+    brush_radius: u32,
+    relief: f32,
+}
+
+impl Simulation for Erosion {
+    const NAME: &'static str = "erosion";
+    const SHADER: &'static str = "shaders/erosion.wgsl";
+    const ENTRY_POINTS: &'static [&'static str] = &["generate", "update"];
+    const STEPS_PER_FRAME: u32 = 4;
+    const BUFFERS: &'static [&'static str] =
+        &["erosion_heights", "erosion_source", "erosion_droplets"];
+
+    type Params = ErosionParams;
+
+    fn params(&self) -> ErosionParams {
+        ErosionParams {
+            from_file: (self.terrain == Terrain::File && self.loaded.is_some()) as u32,
+            noise_scale: self.noise_scale,
+            octaves: self.octaves,
+            persistence: self.persistence,
+            lifetime: self.lifetime,
+            inertia: self.inertia,
+            capacity: self.capacity,
+            min_capacity: self.min_capacity,
+            erosion: self.erosion,
+            deposition: self.deposition,
+            evaporation: self.evaporation,
+            gravity: self.gravity,
+            brush_radius: self.brush_radius.clamp(1, MAX_BRUSH_RADIUS),
+            relief: self.relief,
+        }
+    }
+
+    /// The droplets buffer is sized for the count.
+    fn restart_key(&self) -> impl Hash {
+        (self.droplets, self.terrain)
+    }
+
+    /// Reloads the heightmap, so edits to the file show up.
+    fn reload(&mut self, _seed: u32) {
+        if self.terrain != Terrain::File {
+            return;
+        }
+        match Heightmap::load(&self.heightmap) {
+            Ok(heightmap) => self.loaded = Some(heightmap),
+            Err(err) => {
+                warn!("loading {} failed: {err}", self.heightmap);
+                self.loaded = None;
+            }
+        }
+    }
+
+    /// The heights as fixed point `i32`s, the loaded file as its width and
+    /// height followed by one `f32` per pixel, and the droplets, which
+    /// start dried up so the first step places them.
+    fn buffers(&self, size: UVec2) -> Vec<BufferInit> {
+        let (source_size, heights) = match &self.loaded {
+            Some(heightmap) => (heightmap.size, heightmap.heights.as_slice()),
+            None => (UVec2::ZERO, &[][..]),
+        };
+        let source: Vec<u32> = [source_size.x, source_size.y]
+            .into_iter()
+            .chain(heights.iter().map(|height| height.to_bits()))
+            .collect();
+        vec![
+            BufferInit::Zeroed(size.x as u64 * size.y as u64 * 4),
+            BufferInit::Data(bytemuck::cast_slice(&source).to_vec()),
+            BufferInit::Zeroed(self.droplets.max(1) as u64 * DROPLET_SIZE),
+        ]
+    }
+
+    fn init_passes(&self) -> Vec<Pass> {
+        vec![Pass::pixels("generate")]
+    }
+
+    fn passes(&self) -> Vec<Pass> {
+        vec![Pass::items("update", self.droplets.max(1))]
+    }
+}
+
+/// Heights between 0 and 1, row by row from the top.
+#[derive(Clone, Debug, PartialEq)]
+struct Heightmap {
+    size: UVec2,
+    heights: Vec<f32>,
+}
+
+impl Heightmap {
+    fn load(path: &str) -> Result<Self, String> {
+        let image = image::open(path).map_err(|err| err.to_string())?;
+        let image = fit_into_buffer(image, path).to_luma16();
+        Ok(Heightmap {
+            size: UVec2::new(image.width(), image.height()),
+            heights: image
+                .pixels()
+                .map(|pixel| pixel.0[0] as f32 / u16::MAX as f32)
+                .collect(),
+        })
+    }
+
+    /// Reads the fixed point heights of the `erosion_heights` buffer,
+    /// `None` if it was recreated at a smaller size in the meantime.
+    fn from_fixed_point(size: UVec2, bytes: &[u8]) -> Option<Self> {
+        let count = (size.x * size.y) as usize;
+        if count == 0 || bytes.len() < count * 4 {
+            return None;
+        }
+        let heights = bytes
+            .chunks_exact(4)
+            .take(count)
+            .map(|chunk| i32::from_le_bytes(chunk.try_into().unwrap()) as f32 / HEIGHT_SCALE)
+            .collect();
+        Some(Heightmap { size, heights })
+    }
+
+    fn get(&self, x: u32, y: u32) -> f32 {
+        let x = x.min(self.size.x - 1);
+        let y = y.min(self.size.y - 1);
+        self.heights[(y * self.size.x + x) as usize]
+    }
+
+    fn save_png(&self, path: &std::path::Path) -> Result<(), String> {
+        let pixels: Vec<u16> = self
+            .heights
+            .iter()
+            .map(|height| (height.clamp(0.0, 1.0) * u16::MAX as f32).round() as u16)
+            .collect();
+        image::ImageBuffer::<image::Luma<u16>, _>::from_raw(self.size.x, self.size.y, pixels)
+            .ok_or("the heightmap is incomplete")?
+            .save(path)
+            .map_err(|err| err.to_string())
+    }
+
+    /// A grid of at most [`PREVIEW_RESOLUTION`] vertices along the longer
+    /// side, which spans two units around the origin.
+    fn mesh(&self, height: f32) -> Mesh {
+        let stride = (self.size.max_element() / PREVIEW_RESOLUTION).max(1);
+        let columns = (self.size.x - 1) / stride + 1;
+        let rows = (self.size.y - 1) / stride + 1;
+        let scale = 2.0 / self.size.max_element() as f32;
+        let center = self.size.as_vec2() / 2.0;
+        let elevation = |x: u32, y: u32| self.get(x, y) * height * 2.0;
+
+        let mut positions = Vec::with_capacity((columns * rows) as usize);
+        let mut normals = Vec::with_capacity(positions.capacity());
+        let mut colors = Vec::with_capacity(positions.capacity());
+        for row in 0..rows {
+            for column in 0..columns {
+                let (x, y) = (column * stride, row * stride);
+                let position = (Vec2::new(x as f32, y as f32) - center) * scale;
+                positions.push([position.x, elevation(x, y), position.y]);
+                let dx = elevation(x + stride, y) - elevation(x.saturating_sub(stride), y);
+                let dz = elevation(x, y + stride) - elevation(x, y.saturating_sub(stride));
+                let span = 2.0 * stride as f32 * scale;
+                normals.push(Vec3::new(-dx, span, -dz).normalize().to_array());
+                colors.push(terrain_color(self.get(x, y)));
+            }
+        }
+        let mut indices = Vec::with_capacity(((columns - 1) * (rows - 1) * 6) as usize);
+        for row in 0..rows - 1 {
+            for column in 0..columns - 1 {
+                let i = row * columns + column;
+                indices.extend([i, i + columns, i + 1, i + 1, i + columns, i + columns + 1]);
+            }
+        }
+
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+        mesh.set_indices(Some(Indices::U32(indices)));
+        mesh
+    }
+}
+
+/// Matches `terrain_color` in `erosion.wgsl`.
+fn terrain_color(height: f32) -> [f32; 4] {
+    let smoothstep = |low: f32, high: f32| {
+        let t = ((height - low) / (high - low)).clamp(0.0, 1.0);
+        t * t * (3.0 - 2.0 * t)
+    };
+    let low = Vec3::new(0.2, 0.36, 0.2);
+    let mid = Vec3::new(0.48, 0.4, 0.29);
+    let rock = Vec3::new(0.58, 0.56, 0.54);
+    let snow = Vec3::new(0.95, 0.95, 0.97);
+    let color = low.lerp(mid, smoothstep(0.3, 0.55));
+    let color = color.lerp(rock, smoothstep(0.55, 0.75));
+    color
+        .lerp(snow, smoothstep(0.8, 0.9))
+        .extend(1.0)
+        .to_array()
+}
+
+#[derive(Resource, Default)]
+struct PendingExport(Option<(ReadbackId, UVec2, PathBuf)>);
+
+fn terrain_window(
+    mut contexts: EguiContexts,
+    settings: Res<Erosion>,
+    frame: Res<SimulationFrame>,
+    readback: Res<Readback>,
+    mut pending: ResMut<PendingExport>,
+) {
+    egui::Window::new("Terrain").show(contexts.ctx_mut(), |ui| {
+        let export = ui.add_enabled(
+            pending.0.is_none(),
+            egui::Button::new(format!("export to {}", settings.export_path.display())),
+        );
+        if export.clicked() {
+            pending.0 = Some((
+                readback.request(ReadbackSource::Buffer("erosion_heights")),
+                frame.size,
+                settings.export_path.clone(),
+            ));
+        }
+    });
+}
+
+fn finish_export(mut pending: ResMut<PendingExport>, readback: Res<Readback>) {
+    let Some((id, size, path)) = pending.0.as_ref() else {
+        return;
+    };
+    let Some(result) = readback.take(*id) else {
+        return;
+    };
+    let Ok(bytes) = result else {
+        pending.0 = None;
+        return;
+    };
+    let saved = Heightmap::from_fixed_point(*size, &bytes)
+        .ok_or_else(|| "the simulation was resized".to_owned())
+        .and_then(|heightmap| heightmap.save_png(path));
+    match saved {
+        Ok(()) => info!("heightmap saved to {}", path.display()),
+        Err(err) => error!("saving the heightmap to {} failed: {err}", path.display()),
+    }
+    pending.0 = None;
+}
+
+#[derive(Resource, Default)]
+struct Preview {
+    entities: Option<PreviewEntities>,
+    request: Option<(ReadbackId, UVec2)>,
+    timer: Timer,
+}
+
+struct PreviewEntities {
+    camera: Entity,
+    light: Entity,
+    terrain: Entity,
+    mesh: Handle<Mesh>,
+}
+
+/// Keeps the preview mesh in sync with the heights, read back twice a second.
+#[allow(clippy::too_many_arguments)]
+fn update_preview(
+    mut commands: Commands,
+    settings: Res<Erosion>,
+    active: Res<ActiveSimulation>,
+    frame: Res<SimulationFrame>,
+    time: Res<Time>,
+    readback: Res<Readback>,
+    mut preview: ResMut<Preview>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut cameras: Query<&mut Camera>,
+    mut transforms: Query<&mut Transform>,
+) {
+    let preview = &mut *preview;
+    if !settings.preview || active.0 != Erosion::NAME {
+        if let Some(entities) = preview.entities.take() {
+            for entity in [entities.camera, entities.light, entities.terrain] {
+                commands.entity(entity).despawn();
+            }
+            meshes.remove(entities.mesh);
+        }
+        if let Some((id, _)) = preview.request.take() {
+            readback.cancel(id);
+        }
+        return;
+    }
+
+    let entities = preview.entities.get_or_insert_with(|| {
+        let mesh = meshes.add(Mesh::new(PrimitiveTopology::TriangleList));
+        let camera = commands
+            .spawn(Camera3dBundle {
+                camera: Camera {
+                    // drawn over the simulation image
+                    order: 1,
+                    ..default()
+                },
+                camera_3d: Camera3d {
+                    clear_color: ClearColorConfig::None,
+                    ..default()
+                },
+                transform: Transform::from_xyz(0.0, 1.8, 2.4).looking_at(Vec3::ZERO, Vec3::Y),
+                ..default()
+            })
+            .id();
+        let light = commands
+            .spawn(DirectionalLightBundle {
+                transform: Transform::from_xyz(-1.0, 1.0, -1.0).looking_at(Vec3::ZERO, Vec3::Y),
+                ..default()
+            })
+            .id();
+        let terrain = commands
+            .spawn(PbrBundle {
+                mesh: mesh.clone(),
+                material: materials.add(StandardMaterial {
+                    perceptual_roughness: 0.9,
+                    ..default()
+                }),
+                ..default()
+            })
+            .id();
+        PreviewEntities {
+            camera,
+            light,
+            terrain,
+            mesh,
+        }
+    });
+
+    if let (Ok(window), Ok(mut camera)) = (windows.get_single(), cameras.get_mut(entities.camera)) {
+        let size = UVec2::new(window.physical_width(), window.physical_height());
+        let viewport_size = size / 3;
+        if viewport_size.min_element() > 0 {
+            camera.viewport = Some(Viewport {
+                physical_position: size - viewport_size,
+                physical_size: viewport_size,
+                ..default()
+            });
+        }
+    }
+    if let Ok(mut transform) = transforms.get_mut(entities.terrain) {
+        transform.rotate_y(settings.preview_orbit_speed * time.delta_seconds());
+    }
+
+    if let Some((id, size)) = preview.request {
+        let Some(result) = readback.take(id) else {
+            return;
+        };
+        preview.request = None;
+        if let Some(heightmap) = result
+            .ok()
+            .and_then(|bytes| Heightmap::from_fixed_point(size, &bytes))
+        {
+            if size.min_element() > 1 {
+                meshes.set_untracked(&entities.mesh, heightmap.mesh(settings.preview_height));
+            }
+        }
+    }
+    if preview.timer.duration().is_zero() {
+        preview.timer = Timer::from_seconds(0.5, TimerMode::Repeating);
+    }
+    let request_due = preview.timer.tick(time.delta()).just_finished();
+    if preview.request.is_none() && (request_due || frame.reset || settings.is_changed()) {
+        preview.request = Some((
+            readback.request(ReadbackSource::Buffer("erosion_heights")),
+            frame.size,
+        ));
+    }
+}
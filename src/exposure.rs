@@ -0,0 +1,413 @@
+use std::{borrow::Cow, path::PathBuf};
+
+use bevy::{
+    prelude::*,
+    render::{
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+        render_asset::RenderAssets,
+        render_graph::{Node, NodeRunError, RenderGraph, RenderGraphContext},
+        render_resource::{
+            encase::UniformBuffer, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+            BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType,
+            BufferBindingType, BufferInitDescriptor, BufferUsages, CachedComputePipelineId,
+            ComputePassDescriptor, ComputePipelineDescriptor, Extent3d, PipelineCache,
+            ShaderStages, ShaderType, StorageTextureAccess, TextureDimension, TextureFormat,
+            TextureUsages, TextureViewDimension,
+        },
+        renderer::{RenderContext, RenderDevice},
+        RenderApp, RenderSet,
+    },
+};
+use bevy_inspector_egui::{prelude::*, quick::ResourceInspectorPlugin};
+
+use crate::{
+    image::{create_image, save_png, MainImageMarker},
+    physarum::Physarum,
+    readback::{Readback, ReadbackId, ReadbackSource},
+    simulation::{
+        show_simulation, simulation_active, ActiveSimulation, Simulation, SimulationBindGroups,
+        SimulationDisplay, SimulationFrame, SimulationPipeline,
+    },
+};
+
+/// Long-exposure display of where agents have been.
+///
+/// An accumulation buffer next to the trail map integrates it over time
+/// with its own decay. The simulation itself is unaffected, only the main
+/// sprite switches to show the accumulation. `F3` toggles it, `F4` resets
+/// it and `F6` exports it as png.
+pub(crate) struct ExposurePlugin;
+impl Plugin for ExposurePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LongExposure>()
+            .init_resource::<ExposureImages>()
+            .init_resource::<PendingExport>()
+            .add_plugin(
+                ResourceInspectorPlugin::<LongExposure>::default()
+                    .run_if(simulation_active(Physarum::NAME)),
+            )
+            .add_plugin(ExtractResourcePlugin::<LongExposure>::default())
+            .add_plugin(ExtractResourcePlugin::<ExposureImages>::default())
+            .add_system(clear_reset.in_base_set(CoreSet::First))
+            .add_system(exposure_hotkeys.run_if(simulation_active(Physarum::NAME)))
+            .add_systems((
+                update_exposure_images,
+                switch_display.after(show_simulation),
+                finish_export,
+            ));
+
+        let render_app = app.sub_app_mut(RenderApp);
+        render_app
+            .init_resource::<ExposurePipeline>()
+            .add_system(queue_exposure_bind_group.in_set(RenderSet::Queue));
+        let mut render_graph = render_app.world.resource_mut::<RenderGraph>();
+        render_graph.add_node("long_exposure", ExposureNode);
+        render_graph.add_node_edge(Physarum::NAME, "long_exposure");
+        render_graph.add_node_edge(
+            "long_exposure",
+            bevy::render::main_graph::node::CAMERA_DRIVER,
+        );
+    }
+}
+
+#[derive(Resource, ExtractResource, Clone, Reflect, InspectorOptions)]
+#[reflect(Resource, InspectorOptions)]
+struct LongExposure {
+    enabled: bool,
+    /// Integrate only fresh deposits instead of the whole trail map.
+    deposits_only: bool,
+    /// Fraction of the accumulation kept per second. It is integrated
+    /// once per frame, scaled by the frame time.
+    #[inspector(min = 0.0, max = 1.0, speed = 0.001)]
+    decay: f32,
+    /// Share of the trail map added per second.
+    #[inspector(min = 0.0, max = 10.0, speed = 0.01)]
+    gain: f32,
+    /// Scale of the accumulation before it is mapped to the display.
+    #[inspector(min = 0.0, speed = 0.01)]
+    exposure: f32,
+    /// Clears the accumulation, unset again after one frame.
+    reset: bool,
+    #[reflect(ignore)]
+    export_path: PathBuf,
+}
+
+impl Default for LongExposure {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            deposits_only: false,
+            decay: 0.9,
+            gain: 0.6,
+            exposure: 1.0,
+            reset: false,
+            export_path: PathBuf::from("exposure.png"),
+        }
+    }
+}
+
+#[derive(ShaderType)]
+struct ExposureParams {
+    decay: f32,
+    gain: f32,
+    exposure: f32,
+    reset: u32,
+}
+
+#[derive(Resource, ExtractResource, Clone)]
+struct ExposureImages {
+    /// Rgba32Float sum of everything integrated so far.
+    accumulation: Handle<Image>,
+    /// The accumulation mapped to a displayable image.
+    display: Handle<Image>,
+}
+
+/// Resized to the trail map by [`update_exposure_images`].
+impl FromWorld for ExposureImages {
+    fn from_world(world: &mut World) -> Self {
+        let mut images = world.resource_mut::<Assets<Image>>();
+        ExposureImages {
+            accumulation: images.add(create_accumulation_image(UVec2::ONE)),
+            display: images.add(create_image(1, 1)),
+        }
+    }
+}
+
+fn create_accumulation_image(size: UVec2) -> Image {
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0; 16],
+        TextureFormat::Rgba32Float,
+    );
+    image.texture_descriptor.usage = TextureUsages::COPY_DST | TextureUsages::STORAGE_BINDING;
+    image
+}
+
+fn clear_reset(mut exposure: ResMut<LongExposure>) {
+    if exposure.reset {
+        exposure.reset = false;
+    }
+}
+
+#[derive(Resource, Default)]
+struct PendingExport(Option<(ReadbackId, UVec2)>);
+
+fn exposure_hotkeys(
+    keys: Res<Input<KeyCode>>,
+    mut exposure: ResMut<LongExposure>,
+    mut pending: ResMut<PendingExport>,
+    readback: Res<Readback>,
+    images: Res<ExposureImages>,
+    frame: Res<SimulationFrame>,
+) {
+    if keys.just_pressed(KeyCode::F3) {
+        exposure.enabled = !exposure.enabled;
+    }
+    if keys.just_pressed(KeyCode::F4) {
+        exposure.reset = true;
+    }
+    if keys.just_pressed(KeyCode::F6) && pending.0.is_none() {
+        pending.0 = Some((
+            readback.request(ReadbackSource::Image(images.display.clone())),
+            frame.size,
+        ));
+    }
+}
+
+fn finish_export(
+    mut pending: ResMut<PendingExport>,
+    readback: Res<Readback>,
+    exposure: Res<LongExposure>,
+) {
+    let Some((id, size)) = pending.0 else {return;};
+    let Some(result) = readback.take(id) else {return;};
+    pending.0 = None;
+    let Ok(bytes) = result else {return;};
+    match save_png(&exposure.export_path, size, bytes) {
+        Ok(()) => info!("exposure saved to {}", exposure.export_path.display()),
+        Err(err) => error!(
+            "saving exposure to {} failed: {err}",
+            exposure.export_path.display()
+        ),
+    }
+}
+
+/// Keeps the accumulation the size of the trail map, resizing clears it.
+fn update_exposure_images(
+    handles: Res<ExposureImages>,
+    frame: Res<SimulationFrame>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let size = frame.size;
+    let Some(image) = images.get(&handles.display) else {return;};
+    if size.x > 0 && size.y > 0 && image.size().as_uvec2() != size {
+        images.set_untracked(&handles.accumulation, create_accumulation_image(size));
+        images.set_untracked(&handles.display, create_image(size.x, size.y));
+    }
+}
+
+fn switch_display(
+    exposure: Res<LongExposure>,
+    active: Res<ActiveSimulation>,
+    handles: Res<ExposureImages>,
+    simulation_display: Res<SimulationDisplay>,
+    mut sprites: Query<&mut Handle<Image>, With<MainImageMarker>>,
+) {
+    if !(exposure.is_changed() || active.is_changed()) || active.0 != Physarum::NAME {
+        return;
+    }
+    for mut texture in sprites.iter_mut() {
+        *texture = if exposure.enabled {
+            handles.display.clone()
+        } else {
+            simulation_display.0.clone()
+        };
+    }
+}
+
+#[derive(Resource)]
+struct ExposurePipeline {
+    accumulate_frame_pipeline: CachedComputePipelineId,
+    decay_pipeline: CachedComputePipelineId,
+    accumulate_deposits_pipeline: CachedComputePipelineId,
+    display_pipeline: CachedComputePipelineId,
+    exposure_bind_group_layout: BindGroupLayout,
+}
+
+impl FromWorld for ExposurePipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+        let storage_texture = |binding, access, format| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::StorageTexture {
+                access,
+                format,
+                view_dimension: TextureViewDimension::D2,
+            },
+            count: None,
+        };
+        let exposure_bind_group_layout =
+            render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("LayoutExposureBindGroup"),
+                entries: &[
+                    storage_texture(
+                        0,
+                        StorageTextureAccess::ReadWrite,
+                        TextureFormat::Rgba32Float,
+                    ),
+                    storage_texture(
+                        1,
+                        StorageTextureAccess::WriteOnly,
+                        TextureFormat::Rgba8Unorm,
+                    ),
+                    BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: Some(ExposureParams::min_size()),
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let mut layout = world.resource::<SimulationPipeline<Physarum>>().layouts();
+        layout.push(exposure_bind_group_layout.clone());
+        let shader = world
+            .resource::<AssetServer>()
+            .load("shaders/exposure.wgsl");
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let queue = |entry_point: &'static str| {
+            pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+                label: Some(Cow::from(entry_point)),
+                layout: layout.clone(),
+                push_constant_ranges: vec![],
+                shader: shader.clone(),
+                shader_defs: vec![],
+                entry_point: Cow::from(entry_point),
+            })
+        };
+
+        Self {
+            accumulate_frame_pipeline: queue("accumulate_frame"),
+            decay_pipeline: queue("decay"),
+            accumulate_deposits_pipeline: queue("accumulate_deposits"),
+            display_pipeline: queue("display"),
+            exposure_bind_group_layout,
+        }
+    }
+}
+
+#[derive(Resource)]
+struct ExposureBindGroup(BindGroup);
+
+fn queue_exposure_bind_group(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    pipeline: Res<ExposurePipeline>,
+    exposure: Res<LongExposure>,
+    handles: Res<ExposureImages>,
+    gpu_images: Res<RenderAssets<Image>>,
+    frame: Res<SimulationFrame>,
+) {
+    if !exposure.enabled {
+        return;
+    }
+    let (Some(accumulation), Some(display)) = (
+        gpu_images.get(&handles.accumulation),
+        gpu_images.get(&handles.display),
+    ) else {return;};
+
+    let mut uniform = UniformBuffer::new(Vec::new());
+    uniform
+        .write(&ExposureParams {
+            decay: exposure.decay.powf(frame.delta_time),
+            gain: exposure.gain * frame.delta_time,
+            exposure: exposure.exposure,
+            reset: exposure.reset as u32,
+        })
+        .unwrap();
+    let params = render_device.create_buffer_with_data(&BufferInitDescriptor {
+        label: Some("exposure_params"),
+        contents: uniform.as_ref(),
+        usage: BufferUsages::UNIFORM,
+    });
+    commands.insert_resource(ExposureBindGroup(render_device.create_bind_group(
+        &BindGroupDescriptor {
+            label: Some("ExposureBindGroup"),
+            layout: &pipeline.exposure_bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&accumulation.texture_view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&display.texture_view),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: params.as_entire_binding(),
+                },
+            ],
+        },
+    )));
+}
+
+struct ExposureNode;
+
+impl Node for ExposureNode {
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let exposure = world.resource::<LongExposure>();
+        if !exposure.enabled {
+            return Ok(());
+        }
+        let (Some(bind_groups), Some(exposure_bind_group)) = (
+            world.get_resource::<SimulationBindGroups<Physarum>>(),
+            world.get_resource::<ExposureBindGroup>(),
+        ) else {return Ok(());};
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let pipeline = world.resource::<ExposurePipeline>();
+        let (Some(accumulate_frame), Some(decay), Some(accumulate_deposits), Some(display)) = (
+            pipeline_cache.get_compute_pipeline(pipeline.accumulate_frame_pipeline),
+            pipeline_cache.get_compute_pipeline(pipeline.decay_pipeline),
+            pipeline_cache.get_compute_pipeline(pipeline.accumulate_deposits_pipeline),
+            pipeline_cache.get_compute_pipeline(pipeline.display_pipeline),
+        ) else {return Ok(());};
+
+        let size = world.resource::<SimulationFrame>().size;
+        let (w, h) = (size.x, size.y);
+        let agents_len = world.resource::<Physarum>().agents.len() as u32;
+
+        let mut pass = render_context
+            .command_encoder()
+            .begin_compute_pass(&ComputePassDescriptor::default());
+        bind_groups.set_latest(&mut pass);
+        pass.set_bind_group(3, &exposure_bind_group.0, &[]);
+        if exposure.deposits_only {
+            pass.set_pipeline(decay);
+            pass.dispatch_workgroups(w.div_ceil(8), h.div_ceil(8), 1);
+            pass.set_pipeline(accumulate_deposits);
+            pass.dispatch_workgroups(agents_len.div_ceil(64), 1, 1);
+        } else {
+            pass.set_pipeline(accumulate_frame);
+            pass.dispatch_workgroups(w.div_ceil(8), h.div_ceil(8), 1);
+        }
+        pass.set_pipeline(display);
+        pass.dispatch_workgroups(w.div_ceil(8), h.div_ceil(8), 1);
+        Ok(())
+    }
+}
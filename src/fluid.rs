@@ -0,0 +1,118 @@
+use bevy::{
+    prelude::*,
+    render::{
+        extract_resource::ExtractResource,
+        render_resource::{ShaderType, TextureFormat},
+    },
+};
+use bevy_inspector_egui::prelude::*;
+
+use crate::simulation::{BufferInit, ComputeSimulationPlugin, Pass, Simulation};
+
+/// Incompressible 2D fluid after Stam's "Stable Fluids".
+///
+/// The state images hold velocity in red and green, pressure in blue and
+/// the divergence in alpha, every pass of a step writes the whole state.
+/// Colored dye is carried along in a storage buffer. Dragging with the
+/// left mouse button pushes the fluid and adds dye, the right button
+/// only pushes.
+///
+/// Unlike the usual implementation, velocity, pressure and divergence do
+/// not get a pair of images each: a [`Simulation`] has just the one
+/// swapped state pair, so they share its channels. The dye has no spare
+/// channels left and no second image pair either, `advect` writes it to
+/// the back half of its buffer and `copy_dye` copies that over the front
+/// half, which is what the other passes and `display` read.
+pub(crate) struct FluidPlugin;
+impl Plugin for FluidPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(ComputeSimulationPlugin::<Fluid>::default());
+    }
+}
+
+#[derive(Resource, ExtractResource, Clone, Reflect, InspectorOptions)]
+#[reflect(Resource, InspectorOptions)]
+pub(crate) struct Fluid {
+    /// Jacobi iterations of the pressure solve, more keep the fluid closer
+    /// to incompressible.
+    #[inspector(min = 1, max = 200)]
+    pressure_iterations: u32,
+    /// Fraction of the velocity lost per second.
+    #[inspector(min = 0.0, max = 1.0, speed = 0.01)]
+    velocity_dissipation: f32,
+    /// Fraction of the dye lost per second.
+    #[inspector(min = 0.0, max = 1.0, speed = 0.01)]
+    dye_dissipation: f32,
+    /// Velocity added per pixel the pointer moved.
+    #[inspector(min = 0.0, max = 100.0)]
+    force: f32,
+    #[inspector(min = 1.0, max = 200.0)]
+    brush_radius: f32,
+}
+
+impl Default for Fluid {
+    fn default() -> Self {
+        Self {
+            pressure_iterations: 40,
+            velocity_dissipation: 0.05,
+            dye_dissipation: 0.1,
+            force: 20.0,
+            brush_radius: 30.0,
+        }
+    }
+}
+
+#[derive(ShaderType)]
+pub(crate) struct FluidParams {
+    velocity_dissipation: f32,
+    dye_dissipation: f32,
+    force: f32,
+    brush_radius: f32,
+}
+
+impl Simulation for Fluid {
+    const NAME: &'static str = "fluid";
+    const SHADER: &'static str = "shaders/fluid.wgsl";
+    const ENTRY_POINTS: &'static [&'static str] = &[
+        "init",
+        "advect",
+        "copy_dye",
+        "divergence",
+        "jacobi",
+        "project",
+    ];
+    /// Pressure converges badly at half precision.
+    const STATE_FORMAT: TextureFormat = TextureFormat::Rgba32Float;
+    const BUFFERS: &'static [&'static str] = &["fluid_dye"];
+
+    type Params = FluidParams;
+
+    fn params(&self) -> FluidParams {
+        FluidParams {
+            velocity_dissipation: self.velocity_dissipation,
+            dye_dissipation: self.dye_dissipation,
+            force: self.force,
+            brush_radius: self.brush_radius,
+        }
+    }
+
+    /// The current and the advected dye, a `vec4<f32>` per pixel each.
+    fn buffers(&self, size: UVec2) -> Vec<BufferInit> {
+        vec![BufferInit::Zeroed(size.x as u64 * size.y as u64 * 16 * 2)]
+    }
+
+    fn init_passes(&self) -> Vec<Pass> {
+        vec![Pass::pixels("init").swap()]
+    }
+
+    fn passes(&self) -> Vec<Pass> {
+        let mut passes = vec![
+            Pass::pixels("advect").swap(),
+            Pass::pixels("copy_dye"),
+            Pass::pixels("divergence").swap(),
+        ];
+        passes.extend((0..self.pressure_iterations.max(1)).map(|_| Pass::pixels("jacobi").swap()));
+        passes.push(Pass::pixels("project").swap());
+        passes
+    }
+}
@@ -0,0 +1,134 @@
+use bevy::{
+    prelude::*,
+    render::{extract_resource::ExtractResource, render_resource::ShaderType},
+};
+use bevy_inspector_egui::prelude::*;
+
+use crate::simulation::{ComputeSimulationPlugin, Pass, Simulation};
+
+/// Gray-Scott reaction-diffusion on the ping-pong state images.
+///
+/// `U` and `V` are stored in the red and green channel. The left mouse
+/// button paints `V` into the state, the right button wipes it.
+pub(crate) struct GrayScottPlugin;
+impl Plugin for GrayScottPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(ComputeSimulationPlugin::<GrayScott>::default())
+            .add_system(apply_preset);
+    }
+}
+
+/// Classic regimes, see Pearson's "Complex patterns in a simple system".
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Reflect, FromReflect)]
+enum GrayScottPreset {
+    Mitosis,
+    #[default]
+    Coral,
+    Worms,
+    Spots,
+    /// Keeps feed and kill as set.
+    Custom,
+}
+
+impl GrayScottPreset {
+    /// Feed and kill rate.
+    fn rates(self) -> Option<(f32, f32)> {
+        match self {
+            GrayScottPreset::Mitosis => Some((0.0367, 0.0649)),
+            GrayScottPreset::Coral => Some((0.0545, 0.062)),
+            GrayScottPreset::Worms => Some((0.078, 0.061)),
+            GrayScottPreset::Spots => Some((0.03, 0.062)),
+            GrayScottPreset::Custom => None,
+        }
+    }
+}
+
+#[derive(Resource, ExtractResource, Clone, Reflect, InspectorOptions)]
+#[reflect(Resource, InspectorOptions)]
+pub(crate) struct GrayScott {
+    /// Picking a preset sets feed and kill.
+    preset: GrayScottPreset,
+    #[inspector(min = 0.0, max = 0.1, speed = 0.0001)]
+    feed: f32,
+    #[inspector(min = 0.0, max = 0.1, speed = 0.0001)]
+    kill: f32,
+    #[inspector(min = 0.0, max = 1.0, speed = 0.01)]
+    diffusion_u: f32,
+    #[inspector(min = 0.0, max = 1.0, speed = 0.01)]
+    diffusion_v: f32,
+    /// Time per step, unstable above 1.
+    #[inspector(min = 0.0, max = 1.5, speed = 0.01)]
+    delta_time: f32,
+    /// Squares of `V` placed at random on a reset.
+    #[inspector(min = 0, max = 1000)]
+    seeds: u32,
+    #[inspector(min = 1.0, max = 200.0)]
+    brush_radius: f32,
+}
+
+impl Default for GrayScott {
+    fn default() -> Self {
+        let (feed, kill) = GrayScottPreset::Coral.rates().unwrap();
+        Self {
+            preset: GrayScottPreset::Coral,
+            feed,
+            kill,
+            diffusion_u: 1.0,
+            diffusion_v: 0.5,
+            delta_time: 1.0,
+            seeds: 40,
+            brush_radius: 12.0,
+        }
+    }
+}
+
+#[derive(ShaderType)]
+pub(crate) struct GrayScottParams {
+    feed: f32,
+    kill: f32,
+    diffusion_u: f32,
+    diffusion_v: f32,
+    delta_time: f32,
+    seeds: u32,
+    brush_radius: f32,
+}
+
+impl Simulation for GrayScott {
+    const NAME: &'static str = "gray-scott";
+    const SHADER: &'static str = "shaders/gray_scott.wgsl";
+    const ENTRY_POINTS: &'static [&'static str] = &["init", "update"];
+    const STEPS_PER_FRAME: u32 = 12;
+
+    type Params = GrayScottParams;
+
+    fn params(&self) -> GrayScottParams {
+        GrayScottParams {
+            feed: self.feed,
+            kill: self.kill,
+            diffusion_u: self.diffusion_u,
+            diffusion_v: self.diffusion_v,
+            delta_time: self.delta_time,
+            seeds: self.seeds,
+            brush_radius: self.brush_radius,
+        }
+    }
+
+    fn init_passes(&self) -> Vec<Pass> {
+        vec![Pass::pixels("init").swap()]
+    }
+
+    fn passes(&self) -> Vec<Pass> {
+        vec![Pass::pixels("update").swap()]
+    }
+}
+
+fn apply_preset(mut settings: ResMut<GrayScott>, mut applied: Local<Option<GrayScottPreset>>) {
+    if *applied == Some(settings.preset) {
+        return;
+    }
+    *applied = Some(settings.preset);
+    if let Some((feed, kill)) = settings.preset.rates() {
+        settings.feed = feed;
+        settings.kill = kill;
+    }
+}
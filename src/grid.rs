@@ -0,0 +1,30 @@
+use bevy::prelude::*;
+
+use crate::simulation::{BufferInit, Pass};
+
+/// Smallest side of a grid cell, the cell buffers are sized for it.
+const MIN_CELL_SIZE: f32 = 4.0;
+
+/// Side of the grid cells for an interaction radius, the `cell_size`
+/// that `grid_cell_size` in grid.wgsl returns.
+pub(crate) fn cell_size(radius: f32) -> f32 {
+    radius.max(MIN_CELL_SIZE)
+}
+
+/// The `cell_counts` and `cell_starts` buffers of grid.wgsl, one `u32`
+/// per cell of the smallest cell size.
+pub(crate) fn cell_buffers(size: UVec2) -> [BufferInit; 2] {
+    let cells = (size.as_vec2() / MIN_CELL_SIZE).ceil();
+    let cells = cells.x as u64 * cells.y as u64 * 4;
+    [BufferInit::Zeroed(cells), BufferInit::Zeroed(cells)]
+}
+
+/// The passes that sort `count` items by grid cell.
+pub(crate) fn sort_passes(count: u32) -> [Pass; 4] {
+    [
+        Pass::pixels("clear"),
+        Pass::items("count", count),
+        Pass::workgroups("scan", UVec3::ONE),
+        Pass::items("scatter", count),
+    ]
+}
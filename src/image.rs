@@ -1,3 +1,5 @@
+use std::{io, path::Path};
+
 use bevy::{
     prelude::*,
     render::{
@@ -7,6 +9,8 @@ use bevy::{
     window::WindowResized,
 };
 
+use crate::{simulation::BufferInit, Backend};
+
 pub(super) struct ImagePlugin;
 impl Plugin for ImagePlugin {
     fn build(&self, app: &mut App) {
@@ -19,21 +23,41 @@ impl Plugin for ImagePlugin {
 #[derive(Resource, ExtractResource, Clone)]
 pub(crate) struct ComputePlaygroundImages {
     pub(crate) main_textures: (Handle<Image>, Handle<Image>),
+    /// State format of the active simulation, `Rgba8Unorm` for the agents.
+    pub(crate) format: TextureFormat,
+}
+
+impl ComputePlaygroundImages {
+    /// Replaces both textures with cleared ones of the given size and format.
+    pub(crate) fn recreate(
+        &mut self,
+        images: &mut Assets<Image>,
+        size: UVec2,
+        format: TextureFormat,
+        backend: Backend,
+    ) {
+        self.format = format;
+        let image = || create_trail_image(size.x, size.y, format, backend);
+        self.main_textures.0 = images.set(&self.main_textures.0, image());
+        self.main_textures.1 = images.set(&self.main_textures.1, image());
+    }
 }
 
 #[derive(Component, Default)]
-struct MainImageMarker;
+pub(crate) struct MainImageMarker;
 
 impl FromWorld for ComputePlaygroundImages {
     fn from_world(world: &mut World) -> Self {
         let mut win = world.query::<&Window>();
         let win = win.single(world);
         let (w, h) = (win.width() as u32, win.height() as u32);
+        let backend = *world.resource::<Backend>();
         let mut image_assets = world.resource_mut::<Assets<Image>>();
 
-        let imagea = image_assets.add(create_image(w, h));
+        let format = TextureFormat::Rgba8Unorm;
+        let imagea = image_assets.add(create_trail_image(w, h, format, backend));
 
-        let imageb = image_assets.add(create_image(w, h));
+        let imageb = image_assets.add(create_trail_image(w, h, format, backend));
 
         world.spawn((
             SpriteBundle {
@@ -44,11 +68,72 @@ impl FromWorld for ComputePlaygroundImages {
         ));
         ComputePlaygroundImages {
             main_textures: (imagea, imageb),
+            format,
         }
     }
 }
 
+/// Trail intensity of every pixel of a `Rgba8Unorm` readback.
+pub(crate) fn intensity(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks(4)
+        .map(|px| px[0].max(px[1]).max(px[2]) as f32 / 255.0)
+        .collect()
+}
+
+/// Writes a `Rgba8Unorm` readback as png.
+pub(crate) fn save_png(path: &Path, size: UVec2, bytes: Vec<u8>) -> io::Result<()> {
+    let image = Image::new(
+        Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        bytes,
+        // the bytes are stored as is, only the conversion needs an srgb format
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    image
+        .try_into_dynamic()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?
+        .save(path)
+        .map_err(io::Error::other)
+}
+
+/// Downscales an image loaded from `path` whose pixels go into a storage
+/// buffer as 4 bytes each, after its width and height, until the buffer
+/// fits [`BufferInit::MAX_SIZE`]. The aspect ratio is kept.
+pub(crate) fn fit_into_buffer(image: ::image::DynamicImage, path: &str) -> ::image::DynamicImage {
+    let max_pixels = BufferInit::MAX_SIZE / 4 - 2;
+    let (width, height) = (image.width() as u64, image.height() as u64);
+    if width * height <= max_pixels {
+        return image;
+    }
+    let scale = (max_pixels as f64 / (width * height) as f64).sqrt();
+    let new_width = ((width as f64 * scale) as u64).clamp(1, max_pixels);
+    let new_height = ((height as f64 * scale) as u64).clamp(1, max_pixels / new_width);
+    warn!(
+        "{path} is {width}x{height}, downscaled to {new_width}x{new_height} to fit into a buffer"
+    );
+    image.resize_exact(
+        new_width as u32,
+        new_height as u32,
+        ::image::imageops::FilterType::Triangle,
+    )
+}
+
 pub fn create_image(width: u32, height: u32) -> Image {
+    create_image_with_format(width, height, &[0, 0, 0, 255], TextureFormat::Rgba8Unorm)
+}
+
+/// A storage image filled with `pixel`, which must match the format.
+pub(crate) fn create_image_with_format(
+    width: u32,
+    height: u32,
+    pixel: &[u8],
+    format: TextureFormat,
+) -> Image {
     let mut image = Image::new_fill(
         Extent3d {
             width,
@@ -56,11 +141,33 @@ pub fn create_image(width: u32, height: u32) -> Image {
             depth_or_array_layers: 1,
         },
         TextureDimension::D2,
-        &[0, 0, 0, 255],
-        TextureFormat::Rgba8Unorm,
+        pixel,
+        format,
     );
-    image.texture_descriptor.usage =
-        TextureUsages::COPY_DST | TextureUsages::STORAGE_BINDING | TextureUsages::TEXTURE_BINDING;
+    image.texture_descriptor.usage = TextureUsages::COPY_DST
+        | TextureUsages::COPY_SRC
+        | TextureUsages::STORAGE_BINDING
+        | TextureUsages::TEXTURE_BINDING;
+    image
+}
+
+/// The image a simulation is shown in, see [`create_trail_image`].
+pub(crate) fn create_display_image(size: UVec2, backend: Backend) -> Image {
+    create_trail_image(size.x, size.y, TextureFormat::Rgba8Unorm, backend)
+}
+
+/// The trail map, the CPU backend writes it without storage texture usage.
+/// Other formats than `Rgba8Unorm` start zeroed.
+fn create_trail_image(width: u32, height: u32, format: TextureFormat, backend: Backend) -> Image {
+    let mut image = if format == TextureFormat::Rgba8Unorm {
+        create_image(width, height)
+    } else {
+        let pixel = vec![0; format.describe().block_size as usize];
+        create_image_with_format(width, height, &pixel, format)
+    };
+    if backend == Backend::Cpu {
+        image.texture_descriptor.usage -= TextureUsages::STORAGE_BINDING;
+    }
     image
 }
 
@@ -68,14 +175,18 @@ fn update_image(
     mut resize: EventReader<WindowResized>,
     mut handles: ResMut<ComputePlaygroundImages>,
     mut images: ResMut<Assets<Image>>,
+    backend: Res<Backend>,
 ) {
     for res in resize.iter() {
         let (w, h) = (res.width, res.height);
         if w > 100.0 && h > 100.0 {
-            handles.main_textures.0 =
-                images.set(&handles.main_textures.0, create_image(w as u32, h as u32));
-            handles.main_textures.1 =
-                images.set(&handles.main_textures.1, create_image(w as u32, h as u32));
+            let format = handles.format;
+            handles.recreate(
+                &mut images,
+                UVec2::new(w as u32, h as u32),
+                format,
+                *backend,
+            );
         }
     }
 }
@@ -0,0 +1,521 @@
+use std::hash::Hash;
+
+use bevy::{
+    prelude::*,
+    render::{extract_resource::ExtractResource, render_resource::ShaderType},
+};
+use bevy_inspector_egui::prelude::*;
+use serde_json::Value;
+
+use crate::simulation::{BufferInit, ComputeSimulationPlugin, Pass, Simulation};
+
+/// Must match `MAX_RADIUS` in `lenia.wgsl`, the tiles of the convolution
+/// fill the 16 KiB of workgroup memory every adapter has.
+const MAX_RADIUS: u32 = 24;
+
+/// Kernel weights of the largest radius, packed into `vec4`s.
+const KERNEL_VECTORS: usize = ((2 * MAX_RADIUS + 1) * (2 * MAX_RADIUS + 1)).div_ceil(4) as usize;
+
+/// Rings beyond this are ignored.
+const MAX_PEAKS: usize = 4;
+
+/// RLE cells wider or higher than this are rejected, the size of the
+/// largest image most adapters support.
+const MAX_PATTERN_SIZE: usize = 8192;
+
+/// Creatures with more cells are rejected, together with their width and
+/// height they must fit into one storage buffer.
+const MAX_CELLS: usize = (BufferInit::MAX_SIZE / 4 - 2) as usize;
+
+/// Lenia, a cellular automaton with continuous states, a ring-shaped
+/// kernel and a smooth growth function, after Bert Chan's "Lenia:
+/// Biology of Artificial Life".
+///
+/// The convolution loads a tile of the state into workgroup memory. The
+/// initial state is random noise or a creature file, whose parameters
+/// replace the current ones. The left mouse button adds noise, the right
+/// one erases.
+pub(crate) struct LeniaPlugin;
+impl Plugin for LeniaPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(ComputeSimulationPlugin::<Lenia>::default());
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Debug, Reflect, FromReflect)]
+enum LeniaInit {
+    /// Squares of noise.
+    Random,
+    /// The creature file, centered.
+    #[default]
+    Creature,
+}
+
+#[derive(Resource, ExtractResource, Clone, Reflect, InspectorOptions)]
+#[reflect(Resource, InspectorOptions)]
+pub(crate) struct Lenia {
+    /// Kernel radius in pixels.
+    #[inspector(min = 2, max = 24)]
+    radius: u32,
+    /// Heights of the kernel rings from the center out, at most four.
+    peaks: Vec<f32>,
+    /// Growth center.
+    #[inspector(min = 0.0, max = 1.0, speed = 0.001)]
+    mu: f32,
+    /// Growth width.
+    #[inspector(min = 0.001, max = 1.0, speed = 0.0001)]
+    sigma: f32,
+    /// Fraction of the growth applied per step, `1 / T`.
+    #[inspector(min = 0.01, max = 1.0, speed = 0.01)]
+    time_step: f32,
+    init: LeniaInit,
+    /// Squares of noise placed at random on a random start.
+    #[inspector(min = 0, max = 100)]
+    seeds: u32,
+    /// Creature loaded on every reset while `init` is `Creature`.
+    creature: String,
+    #[inspector(min = 1.0, max = 200.0)]
+    brush_radius: f32,
+    #[reflect(ignore)]
+    cells: Option<Creature>,
+}
+
+impl Default for Lenia {
+    fn default() -> Self {
+        // Orbium, the parameters of `patterns/orbium.json`
+        Self {
+            radius: 13,
+            peaks: vec![1.0],
+            mu: 0.15,
+            sigma: 0.015,
+            time_step: 0.1,
+            init: LeniaInit::Creature,
+            seeds: 12,
+            creature: "patterns/orbium.json".to_owned(),
+            brush_radius: 20.0,
+            cells: None,
+        }
+    }
+}
+
+impl Lenia {
+    /// Weights of the `(2r + 1)²` kernel pixels, row by row, summing up to
+    /// one. Every ring is a bump `exp(4 - 1 / (x (1 - x)))` scaled by its peak.
+    fn kernel(&self) -> Vec<f32> {
+        let radius = self.radius.clamp(1, MAX_RADIUS) as i32;
+        let peaks = &self.peaks[..self.peaks.len().min(MAX_PEAKS)];
+        let mut weights = Vec::with_capacity(((2 * radius + 1) * (2 * radius + 1)) as usize);
+        for y in -radius..=radius {
+            for x in -radius..=radius {
+                let distance = Vec2::new(x as f32, y as f32).length() / radius as f32;
+                let rings = distance * peaks.len() as f32;
+                let ring = rings as usize;
+                let t = rings.fract();
+                let weight = match peaks.get(ring) {
+                    Some(peak) if distance < 1.0 && t > 0.0 => {
+                        peak * (4.0 - 1.0 / (t * (1.0 - t))).exp()
+                    }
+                    _ => 0.0,
+                };
+                weights.push(weight);
+            }
+        }
+        let sum: f32 = weights.iter().sum();
+        if sum > 0.0 {
+            weights.iter_mut().for_each(|weight| *weight /= sum);
+        }
+        weights
+    }
+}
+
+#[derive(ShaderType)]
+pub(crate) struct LeniaParams {
+    /// Weights of [`Lenia::kernel`], four per vector.
+    kernel: [Vec4; KERNEL_VECTORS],
+    radius: u32,
+    mu: f32,
+    sigma: f32,
+    time_step: f32,
+    random_init: u32,
+    seeds: u32,
+    brush_radius: f32,
+}
+
+impl Simulation for Lenia {
+    const NAME: &'static str = "lenia";
+    const SHADER: &'static str = "shaders/lenia.wgsl";
+    const ENTRY_POINTS: &'static [&'static str] = &["init", "update"];
+    const BUFFERS: &'static [&'static str] = &["lenia_creature"];
+
+    type Params = LeniaParams;
+
+    fn params(&self) -> LeniaParams {
+        let mut kernel = [Vec4::ZERO; KERNEL_VECTORS];
+        for (i, weights) in self.kernel().chunks(4).enumerate() {
+            let mut vector = [0.0; 4];
+            vector[..weights.len()].copy_from_slice(weights);
+            kernel[i] = Vec4::from_array(vector);
+        }
+        LeniaParams {
+            kernel,
+            radius: self.radius.clamp(1, MAX_RADIUS),
+            mu: self.mu,
+            sigma: self.sigma,
+            time_step: self.time_step,
+            random_init: (self.init == LeniaInit::Random || self.cells.is_none()) as u32,
+            seeds: self.seeds,
+            brush_radius: self.brush_radius,
+        }
+    }
+
+    fn restart_key(&self) -> impl Hash {
+        self.init
+    }
+
+    /// Reloads the creature, so edits to the file show up.
+    fn reload(&mut self, _seed: u32) {
+        if self.init != LeniaInit::Creature {
+            return;
+        }
+        let creature = std::fs::read_to_string(&self.creature)
+            .map_err(|err| err.to_string())
+            .and_then(|text| Creature::from_json(&text));
+        match creature {
+            Ok(creature) => {
+                let params = &creature.params;
+                self.radius = params.radius.min(MAX_RADIUS);
+                self.peaks = params.peaks.clone();
+                self.mu = params.mu;
+                self.sigma = params.sigma;
+                self.time_step = params.time_step;
+                if params.radius > MAX_RADIUS {
+                    warn!(
+                        "{} needs a radius of {}, at most {MAX_RADIUS} is supported",
+                        self.creature, params.radius
+                    );
+                }
+                self.cells = Some(creature);
+            }
+            Err(err) => {
+                warn!("loading {} failed: {err}", self.creature);
+                self.cells = None;
+            }
+        }
+    }
+
+    /// The creature as its width and height followed by one `f32` per cell.
+    fn buffers(&self, _size: UVec2) -> Vec<BufferInit> {
+        let (size, cells) = match &self.cells {
+            Some(creature) => (creature.size, creature.cells.as_slice()),
+            None => (UVec2::ZERO, &[][..]),
+        };
+        let words: Vec<u32> = [size.x, size.y]
+            .into_iter()
+            .chain(cells.iter().map(|cell| cell.to_bits()))
+            .collect();
+        vec![BufferInit::Data(bytemuck::cast_slice(&words).to_vec())]
+    }
+
+    fn init_passes(&self) -> Vec<Pass> {
+        vec![Pass::pixels("init").swap()]
+    }
+
+    fn passes(&self) -> Vec<Pass> {
+        vec![Pass::pixels("update").swap()]
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct CreatureParams {
+    radius: u32,
+    peaks: Vec<f32>,
+    mu: f32,
+    sigma: f32,
+    time_step: f32,
+}
+
+/// A Lenia pattern with the parameters it lives in.
+#[derive(Clone, Debug, PartialEq)]
+struct Creature {
+    size: UVec2,
+    /// Row by row from the top, states between 0 and 1.
+    cells: Vec<f32>,
+    params: CreatureParams,
+}
+
+impl Creature {
+    /// Reads the JSON of Lenia's creature library: `params` with `R`, `T`,
+    /// `m`, `s` and the peaks `b` as a list or a string like `"1,2/3"`,
+    /// and `cells` as rows of numbers or as an RLE string.
+    fn from_json(text: &str) -> Result<Self, String> {
+        let json: Value = serde_json::from_str(text).map_err(|err| err.to_string())?;
+        // the parameters are nested in `params` in the library and at the top level elsewhere
+        let params = json.get("params").unwrap_or(&json);
+        let number = |key: &str| {
+            params
+                .get(key)
+                .and_then(Value::as_f64)
+                .ok_or_else(|| format!("missing number {key}"))
+        };
+        let peaks = match params.get("b") {
+            Some(Value::Array(peaks)) => peaks
+                .iter()
+                .map(|peak| {
+                    peak.as_f64()
+                        .map(|peak| peak as f32)
+                        .ok_or("peaks must be numbers")
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            Some(Value::String(peaks)) => peaks
+                .split(',')
+                .map(parse_fraction)
+                .collect::<Result<Vec<_>, _>>()?,
+            Some(Value::Number(peak)) => vec![peak.as_f64().unwrap_or(1.0) as f32],
+            _ => vec![1.0],
+        };
+        let params = CreatureParams {
+            radius: number("R")?.round().max(1.0) as u32,
+            peaks,
+            mu: number("m")? as f32,
+            sigma: number("s")? as f32,
+            time_step: 1.0 / number("T")?.max(1.0) as f32,
+        };
+
+        let rows = match json.get("cells") {
+            Some(Value::String(rle)) => rle_rows(rle)?,
+            Some(Value::Array(rows)) => rows
+                .iter()
+                .map(|row| {
+                    row.as_array()
+                        .ok_or("cells must be rows of numbers")?
+                        .iter()
+                        .map(|cell| {
+                            cell.as_f64()
+                                .map(|cell| cell as f32)
+                                .ok_or("cells must be numbers")
+                        })
+                        .collect::<Result<Vec<_>, _>>()
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            _ => return Err("missing cells".to_owned()),
+        };
+        let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+        if width.saturating_mul(rows.len()) > MAX_CELLS {
+            return Err(format!(
+                "{width}x{} has more than {MAX_CELLS} cells",
+                rows.len()
+            ));
+        }
+        let mut cells = vec![0.0; width * rows.len()];
+        for (y, row) in rows.iter().enumerate() {
+            for (x, cell) in row.iter().enumerate() {
+                cells[y * width + x] = cell.clamp(0.0, 1.0);
+            }
+        }
+        Ok(Creature {
+            size: UVec2::new(width as u32, rows.len() as u32),
+            cells,
+            params,
+        })
+    }
+}
+
+fn parse_fraction(text: &str) -> Result<f32, String> {
+    let invalid = || format!("{text} is not a number or fraction");
+    match text.split_once('/') {
+        Some((numerator, denominator)) => {
+            let numerator: f32 = numerator.trim().parse().map_err(|_| invalid())?;
+            let denominator: f32 = denominator.trim().parse().map_err(|_| invalid())?;
+            Ok(numerator / denominator)
+        }
+        None => text.trim().parse().map_err(|_| invalid()),
+    }
+}
+
+/// Rows of Lenia's RLE: `.` or `b` is 0, `A` to `X` are 1 to 24 and a
+/// prefix `p` to `y` adds multiples of 24, out of 255. `o` is 255, runs
+/// have a count in front, `$` ends a row and `!` the pattern.
+fn rle_rows(rle: &str) -> Result<Vec<Vec<f32>>, String> {
+    let too_large = || format!("cells larger than {MAX_PATTERN_SIZE}x{MAX_PATTERN_SIZE}");
+    let mut rows = vec![Vec::new()];
+    let mut cells: usize = 0;
+    let mut count: usize = 0;
+    let mut prefix = 0;
+    for c in rle.chars() {
+        let value = match c {
+            '0'..='9' => {
+                let digit = c.to_digit(10).unwrap() as usize;
+                count = count.saturating_mul(10).saturating_add(digit);
+                continue;
+            }
+            'p'..='y' => {
+                prefix = (c as u32 - 'p' as u32 + 1) * 24;
+                continue;
+            }
+            '$' => {
+                if rows.len().saturating_add(count.max(1)) > MAX_PATTERN_SIZE {
+                    return Err(too_large());
+                }
+                rows.extend((0..count.max(1)).map(|_| Vec::new()));
+                count = 0;
+                continue;
+            }
+            '!' => break,
+            '.' | 'b' => 0,
+            'o' => 255,
+            'A'..='X' => prefix + c as u32 - 'A' as u32 + 1,
+            c if c.is_whitespace() => continue,
+            c => return Err(format!("unexpected {c} in the cells")),
+        };
+        let row = rows.last_mut().unwrap();
+        if row.len().saturating_add(count.max(1)) > MAX_PATTERN_SIZE {
+            return Err(too_large());
+        }
+        // the padding is checked by `Creature::from_json`, this keeps the rows themselves small
+        cells += count.max(1);
+        if cells > MAX_CELLS {
+            return Err(format!("more than {MAX_CELLS} cells"));
+        }
+        row.extend(std::iter::repeat_n(
+            value.min(255) as f32 / 255.0,
+            count.max(1),
+        ));
+        count = 0;
+        prefix = 0;
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fractions() {
+        assert_eq!(parse_fraction("0.5"), Ok(0.5));
+        assert_eq!(parse_fraction(" 1 "), Ok(1.0));
+        assert_eq!(parse_fraction("2/3"), Ok(2.0 / 3.0));
+        assert_eq!(parse_fraction(" 1 / 4 "), Ok(0.25));
+        for text in ["", "a", "1/", "/2", "1/2/3", "½"] {
+            assert!(parse_fraction(text).is_err(), "{text:?} parsed");
+        }
+    }
+
+    #[test]
+    fn peaks() {
+        let creature = |b: &str| {
+            let json = format!(
+                r#"{{"params": {{"R": 5, "T": 2, "b": {b}, "m": 0.1, "s": 0.01}}, "cells": [[1]]}}"#
+            );
+            Creature::from_json(&json).map(|creature| creature.params.peaks)
+        };
+        assert_eq!(creature("[1, 0.5]"), Ok(vec![1.0, 0.5]));
+        assert_eq!(creature(r#""1,2/3""#), Ok(vec![1.0, 2.0 / 3.0]));
+        assert_eq!(creature("0.75"), Ok(vec![0.75]));
+        assert_eq!(creature("null"), Ok(vec![1.0]));
+        assert!(creature(r#"[1, "a"]"#).is_err());
+        assert!(creature(r#""1,x""#).is_err());
+    }
+
+    #[test]
+    fn top_level_params() {
+        let creature =
+            Creature::from_json(r#"{"R": 12.6, "T": 0.5, "m": 0.2, "s": 0.03, "cells": "o!"}"#)
+                .unwrap();
+        assert_eq!(
+            creature.params,
+            CreatureParams {
+                radius: 13,
+                peaks: vec![1.0],
+                mu: 0.2,
+                sigma: 0.03,
+                time_step: 1.0,
+            }
+        );
+        assert_eq!((creature.size, creature.cells), (UVec2::ONE, vec![1.0]));
+    }
+
+    #[test]
+    fn invalid_creatures() {
+        for json in [
+            "",
+            "[]",
+            r#"{"R": 13, "T": 10, "m": 0.15, "cells": [[1]]}"#,
+            r#"{"R": 13, "T": 10, "m": 0.15, "s": 0.015}"#,
+            r#"{"R": 13, "T": 10, "m": 0.15, "s": 0.015, "cells": [1]}"#,
+            r#"{"R": 13, "T": 10, "m": 0.15, "s": 0.015, "cells": [["1"]]}"#,
+            r#"{"R": 13, "T": 10, "m": 0.15, "s": 0.015, "cells": "2o$Z!"}"#,
+        ] {
+            assert!(Creature::from_json(json).is_err(), "{json:?} parsed");
+        }
+    }
+
+    #[test]
+    fn rle_counts_and_prefixes() {
+        let rows = rle_rows("2.A$pA 3o$$qX!ignored").unwrap();
+        let value = |v: u32| v as f32 / 255.0;
+        assert_eq!(
+            rows,
+            vec![
+                vec![0.0, 0.0, value(1)],
+                vec![value(25), 1.0, 1.0, 1.0],
+                vec![],
+                vec![value(72)],
+            ]
+        );
+        // prefixes add up to more than 255 at `y`
+        assert_eq!(rle_rows("yX").unwrap(), vec![vec![1.0]]);
+        assert_eq!(rle_rows("").unwrap(), vec![Vec::<f32>::new()]);
+        assert!(rle_rows("o?").is_err());
+    }
+
+    #[test]
+    fn rle_rejects_huge_runs() {
+        assert_eq!(rle_rows("8192o!").unwrap()[0].len(), 8192);
+        assert_eq!(rle_rows("8191$o!").unwrap().len(), 8192);
+        for rle in ["8193o!", "8192$o!", "4096o4097o!", "99999999999999999999999o!"] {
+            assert!(rle_rows(rle).is_err(), "{rle:?} parsed");
+        }
+    }
+
+    #[test]
+    fn creatures_fit_into_a_buffer() {
+        let json =
+            |cells: &str| format!(r#"{{"R": 2, "T": 1, "m": 0.1, "s": 0.01, "cells": {cells}}}"#);
+        // 8192 by 4095 fits next to the size, 8192 by 4096 does not
+        let creature = Creature::from_json(&json(r#""8192o4094$o!""#)).unwrap();
+        assert_eq!(creature.size, UVec2::new(8192, 4095));
+        assert!(Creature::from_json(&json(r#""8192o4095$o!""#)).is_err());
+        let rows = format!("[[{}0]{}]", "0,".repeat(8191), ",[]".repeat(4095));
+        assert!(Creature::from_json(&json(&rows)).is_err());
+        assert!(rle_rows(&"8192o$".repeat(4097)).is_err());
+    }
+
+    #[test]
+    fn rle_cells_are_padded() {
+        let creature =
+            Creature::from_json(r#"{"R": 2, "T": 1, "m": 0.1, "s": 0.01, "cells": "o$2o$.o!"}"#)
+                .unwrap();
+        assert_eq!(creature.size, UVec2::new(2, 3));
+        assert_eq!(creature.cells, [1.0, 0.0, 1.0, 1.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn orbium() {
+        let creature = Creature::from_json(include_str!("../patterns/orbium.json")).unwrap();
+        assert_eq!(creature.size, UVec2::new(20, 20));
+        assert_eq!(creature.cells.len(), 400);
+        assert_eq!(creature.cells[6], 0.1);
+        assert!(creature.cells.iter().all(|cell| (0.0..=1.0).contains(cell)));
+        assert_eq!(
+            creature.params,
+            CreatureParams {
+                radius: 13,
+                peaks: vec![1.0],
+                mu: 0.15,
+                sigma: 0.015,
+                time_step: 0.1,
+            }
+        );
+    }
+}
@@ -27,8 +27,10 @@ fn main() {
         )
         .add_plugin(ScreenDiagnosticsPlugin::default())
         .add_plugin(ScreenFrameDiagnosticsPlugin)
-        .add_startup_system(spawn_camera)
-        .add_plugin(ComputePlaygroundPlugin);
+        .add_startup_system(spawn_camera);
+    // settings inserted before the plugin take precedence over its defaults
+    parse_args(&mut app);
+    app.add_plugin(ComputePlaygroundPlugin);
 
     app.run();
 }
@@ -36,3 +38,58 @@ fn main() {
 fn spawn_camera(mut commands: Commands) {
     commands.spawn(Camera2dBundle::default());
 }
+
+/// `--snapshot <path>` sets the file used by the save/load hotkeys,
+/// `--load-snapshot <path>` resumes from a snapshot on startup,
+/// `--metrics-csv <path>` appends the metrics of every frame to a csv file,
+/// `--contours <t1,t2,..>` sets the thresholds traced by the svg export,
+/// `--benchmark-sort` compares steps/sec with and without agent sorting, then exits,
+/// `--cpu` simulates on the CPU even if the GPU supports compute shaders,
+/// `--simulation <name>` starts with another simulation than the agents.
+fn parse_args(app: &mut App) {
+    let mut snapshot = SnapshotSettings::default();
+    let mut metrics = MetricsSettings::default();
+    let mut contours = ContourSettings::default();
+    let mut sort = SortSettings::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut path = || -> std::path::PathBuf {
+            args.next()
+                .unwrap_or_else(|| panic!("missing path after {arg}"))
+                .into()
+        };
+        match arg.as_str() {
+            "--snapshot" => snapshot.path = path(),
+            "--load-snapshot" => snapshot.load_on_startup = Some(path()),
+            "--metrics-csv" => metrics.csv = Some(path()),
+            "--contours" => {
+                let thresholds = args
+                    .next()
+                    .ok_or_else(|| "missing thresholds".to_owned())
+                    .and_then(|thresholds| {
+                        thresholds
+                            .split(',')
+                            .map(|t| t.trim().parse().map_err(|_| format!("{t} is not a number")))
+                            .collect()
+                    });
+                match thresholds {
+                    Ok(thresholds) => contours.thresholds = thresholds,
+                    Err(err) => error!("{err} after --contours, keeping the default thresholds"),
+                }
+            }
+            "--benchmark-sort" => sort.benchmark_on_startup = true,
+            "--cpu" => {
+                app.insert_resource(Backend::Cpu);
+            }
+            "--simulation" => {
+                let name = args.next().expect("missing name after --simulation");
+                app.insert_resource(ActiveSimulation(name));
+            }
+            _ => warn!("unknown argument {arg}"),
+        }
+    }
+    app.insert_resource(snapshot)
+        .insert_resource(metrics)
+        .insert_resource(contours)
+        .insert_resource(sort);
+}
@@ -0,0 +1,405 @@
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    fs::{File, OpenOptions},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use bevy::{
+    core::{Pod, Zeroable},
+    diagnostic::{Diagnostic, DiagnosticId, Diagnostics},
+    prelude::*,
+    render::{
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+        render_graph::{Node, NodeRunError, RenderGraph, RenderGraphContext},
+        render_resource::{
+            BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+            BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, Buffer,
+            BufferBindingType, BufferDescriptor, BufferUsages, CachedComputePipelineId,
+            ComputePassDescriptor, ComputePipelineDescriptor, PipelineCache, ShaderStages,
+        },
+        renderer::{RenderContext, RenderDevice},
+        RenderApp, RenderSet,
+    },
+};
+use bevy_screen_diagnostics::{Aggregate, ScreenDiagnostics};
+
+use crate::{
+    physarum::Physarum,
+    readback::{Readback, ReadbackBuffers, ReadbackId, ReadbackSource},
+    simulation::{
+        simulation_active, Simulation, SimulationBindGroups, SimulationFrame, SimulationPipeline,
+    },
+};
+
+/// Must match `CELL_SIZE` in `stats.wgsl`.
+const CELL_SIZE: u32 = 16;
+/// Must match `HEADING_SCALE` in `stats.wgsl`.
+const HEADING_SCALE: f64 = 256.0;
+
+pub const MEAN_INTENSITY: DiagnosticId =
+    DiagnosticId::from_u128(0x6d1c_5e8a_2a3f_4b7e_9c11_0f3a_7d52_e401);
+pub const MAX_INTENSITY: DiagnosticId =
+    DiagnosticId::from_u128(0x6d1c_5e8a_2a3f_4b7e_9c11_0f3a_7d52_e402);
+pub const COVERAGE: DiagnosticId =
+    DiagnosticId::from_u128(0x6d1c_5e8a_2a3f_4b7e_9c11_0f3a_7d52_e403);
+pub const HEADING_COHERENCE: DiagnosticId =
+    DiagnosticId::from_u128(0x6d1c_5e8a_2a3f_4b7e_9c11_0f3a_7d52_e404);
+pub const DENSITY_VARIANCE: DiagnosticId =
+    DiagnosticId::from_u128(0x6d1c_5e8a_2a3f_4b7e_9c11_0f3a_7d52_e405);
+
+/// Statistics computed on the GPU and reported as [`Diagnostics`].
+///
+/// They are taken once per frame after its last step, with several steps
+/// per frame the steps in between are not measured.
+pub(crate) struct MetricsPlugin;
+impl Plugin for MetricsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MetricsSettings>()
+            .init_resource::<PendingMetrics>()
+            .add_plugin(ExtractResourcePlugin::<MetricsSettings>::default())
+            .add_startup_system(setup_diagnostics)
+            .add_system(read_metrics.run_if(simulation_active(Physarum::NAME)));
+
+        let render_app = app.sub_app_mut(RenderApp);
+        render_app
+            .init_resource::<MetricsPipeline>()
+            .add_system(prepare_metrics_buffers.in_set(RenderSet::Prepare));
+        let mut render_graph = render_app.world.resource_mut::<RenderGraph>();
+        render_graph.add_node("metrics", MetricsNode);
+        render_graph.add_node_edge(Physarum::NAME, "metrics");
+        render_graph.add_node_edge("metrics", bevy::render::main_graph::node::CAMERA_DRIVER);
+    }
+}
+
+#[derive(Resource, ExtractResource, Clone)]
+pub struct MetricsSettings {
+    pub enabled: bool,
+    /// The last step of every frame is appended here, if set.
+    pub csv: Option<PathBuf>,
+}
+
+impl Default for MetricsSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            csv: None,
+        }
+    }
+}
+
+/// Layout of the `Stats` struct in `stats.wgsl`.
+#[derive(Pod, Zeroable, Copy, Clone)]
+#[repr(C)]
+struct GpuStats {
+    intensity_sum_low: u32,
+    intensity_sum_high: u32,
+    intensity_max: u32,
+    covered: u32,
+    heading_x: i32,
+    heading_y: i32,
+    density_mean: f32,
+    density_variance: f32,
+}
+
+struct Metrics {
+    step: u32,
+    mean_intensity: f64,
+    max_intensity: f64,
+    coverage: f64,
+    heading_coherence: f64,
+    density_variance: f64,
+}
+
+impl Metrics {
+    fn new(stats: &GpuStats, frame: &SimulationFrame, agents: usize) -> Self {
+        let pixels = (frame.size.x * frame.size.y).max(1) as f64;
+        let heading = Vec2::new(stats.heading_x as f32, stats.heading_y as f32);
+        let intensity_sum =
+            (stats.intensity_sum_high as u64) << 32 | stats.intensity_sum_low as u64;
+        Self {
+            // the stats are taken after all steps of the frame
+            step: frame.step.wrapping_add(frame.steps),
+            mean_intensity: intensity_sum as f64 / 255.0 / pixels,
+            max_intensity: stats.intensity_max as f64 / 255.0,
+            coverage: stats.covered as f64 / pixels,
+            heading_coherence: heading.length() as f64 / HEADING_SCALE / agents.max(1) as f64,
+            density_variance: stats.density_variance as f64,
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+struct PendingMetrics {
+    requests: VecDeque<(SimulationFrame, ReadbackId)>,
+    csv: Option<BufWriter<File>>,
+}
+
+fn setup_diagnostics(
+    mut diagnostics: ResMut<Diagnostics>,
+    screen: Option<ResMut<ScreenDiagnostics>>,
+) {
+    for (id, name) in [
+        (MEAN_INTENSITY, "mean intensity"),
+        (MAX_INTENSITY, "max intensity"),
+        (COVERAGE, "coverage"),
+        (HEADING_COHERENCE, "heading coherence"),
+        (DENSITY_VARIANCE, "density variance"),
+    ] {
+        diagnostics.add(Diagnostic::new(id, name, 20));
+    }
+
+    let Some(mut screen) = screen else {return;};
+    for (id, name) in [
+        (MEAN_INTENSITY, "mean"),
+        (MAX_INTENSITY, "max"),
+        (COVERAGE, "covered"),
+        (HEADING_COHERENCE, "coherence"),
+    ] {
+        screen
+            .add(name.to_string(), id)
+            .aggregate(Aggregate::MovingAverage(5))
+            .format(|v| format!("{v:.3}"));
+    }
+    screen
+        .add("density var".to_string(), DENSITY_VARIANCE)
+        .aggregate(Aggregate::MovingAverage(5))
+        .format(|v| format!("{v:.0}"));
+}
+
+fn read_metrics(
+    settings: Res<MetricsSettings>,
+    mut pending: ResMut<PendingMetrics>,
+    mut diagnostics: ResMut<Diagnostics>,
+    readback: Res<Readback>,
+    frame: Res<SimulationFrame>,
+    physarum: Res<Physarum>,
+) {
+    if settings.is_changed() {
+        pending.csv = settings.csv.as_deref().and_then(open_csv);
+    }
+    if !settings.enabled {
+        for (_, id) in pending.requests.drain(..) {
+            readback.cancel(id);
+        }
+        return;
+    }
+    pending.requests.push_back((
+        frame.clone(),
+        readback.request(ReadbackSource::Buffer("metrics")),
+    ));
+
+    // readbacks finish in order
+    while let Some((frame, id)) = pending.requests.front() {
+        let Some(result) = readback.take(*id) else {break;};
+        let Ok(bytes) = result else {
+            pending.requests.pop_front();
+            continue;
+        };
+        let stats: GpuStats =
+            bytemuck::pod_read_unaligned(&bytes[..std::mem::size_of::<GpuStats>()]);
+        let metrics = Metrics::new(&stats, frame, physarum.agents.len());
+        pending.requests.pop_front();
+
+        diagnostics.add_measurement(MEAN_INTENSITY, || metrics.mean_intensity);
+        diagnostics.add_measurement(MAX_INTENSITY, || metrics.max_intensity);
+        diagnostics.add_measurement(COVERAGE, || metrics.coverage);
+        diagnostics.add_measurement(HEADING_COHERENCE, || metrics.heading_coherence);
+        diagnostics.add_measurement(DENSITY_VARIANCE, || metrics.density_variance);
+
+        if let Some(csv) = pending.csv.as_mut() {
+            let result = writeln!(
+                csv,
+                "{},{},{},{},{},{}",
+                metrics.step,
+                metrics.mean_intensity,
+                metrics.max_intensity,
+                metrics.coverage,
+                metrics.heading_coherence,
+                metrics.density_variance
+            )
+            // flushed every row, the app exits without dropping resources
+            .and_then(|_| csv.flush());
+            if let Err(err) = result {
+                error!("writing metrics csv failed: {err}");
+                pending.csv = None;
+            }
+        }
+    }
+}
+
+fn open_csv(path: &Path) -> Option<BufWriter<File>> {
+    let exists = path.exists();
+    let file = match OpenOptions::new().create(true).append(true).open(path) {
+        Ok(file) => file,
+        Err(err) => {
+            error!("opening metrics csv {} failed: {err}", path.display());
+            return None;
+        }
+    };
+    let mut csv = BufWriter::new(file);
+    if !exists {
+        writeln!(
+            csv,
+            "step,mean_intensity,max_intensity,coverage,heading_coherence,density_variance"
+        )
+        .ok()?;
+    }
+    Some(csv)
+}
+
+#[derive(Resource)]
+struct MetricsPipeline {
+    trail_pipeline: CachedComputePipelineId,
+    agent_pipeline: CachedComputePipelineId,
+    density_pipeline: CachedComputePipelineId,
+    stats_bind_group_layout: BindGroupLayout,
+}
+
+impl FromWorld for MetricsPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+
+        let storage_entry = |binding| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let stats_bind_group_layout =
+            render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("LayoutStatsBindGroup"),
+                entries: &[storage_entry(0), storage_entry(1)],
+            });
+
+        let mut layout = world.resource::<SimulationPipeline<Physarum>>().layouts();
+        layout.push(stats_bind_group_layout.clone());
+
+        let stats_shader = world.resource::<AssetServer>().load("shaders/stats.wgsl");
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let queue = |entry_point: &'static str| {
+            pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+                label: Some(Cow::from(entry_point)),
+                layout: layout.clone(),
+                push_constant_ranges: vec![],
+                shader: stats_shader.clone(),
+                shader_defs: vec![],
+                entry_point: Cow::from(entry_point),
+            })
+        };
+
+        Self {
+            trail_pipeline: queue("trail_stats"),
+            agent_pipeline: queue("agent_stats"),
+            density_pipeline: queue("density_stats"),
+            stats_bind_group_layout,
+        }
+    }
+}
+
+#[derive(Resource)]
+struct MetricsBuffers {
+    stats: Buffer,
+    cells: Buffer,
+    cells_size: UVec2,
+    bind_group: BindGroup,
+}
+
+fn prepare_metrics_buffers(
+    mut commands: Commands,
+    buffers: Option<Res<MetricsBuffers>>,
+    mut readback_buffers: ResMut<ReadbackBuffers>,
+    pipeline: Res<MetricsPipeline>,
+    render_device: Res<RenderDevice>,
+    frame: Res<SimulationFrame>,
+) {
+    let cells_size = (frame.size + CELL_SIZE - 1) / CELL_SIZE;
+    if buffers.is_some_and(|b| b.cells_size == cells_size) {
+        return;
+    }
+
+    let stats = render_device.create_buffer(&BufferDescriptor {
+        label: Some("metrics_stats"),
+        size: std::mem::size_of::<GpuStats>() as u64,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let cells = render_device.create_buffer(&BufferDescriptor {
+        label: Some("metrics_cells"),
+        size: (cells_size.x * cells_size.y).max(1) as u64 * 4,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let bind_group = render_device.create_bind_group(&BindGroupDescriptor {
+        label: Some("StatsBindGroup"),
+        layout: &pipeline.stats_bind_group_layout,
+        entries: &[
+            BindGroupEntry {
+                binding: 0,
+                resource: stats.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: cells.as_entire_binding(),
+            },
+        ],
+    });
+    readback_buffers.0.insert("metrics", stats.clone());
+    commands.insert_resource(MetricsBuffers {
+        stats,
+        cells,
+        cells_size,
+        bind_group,
+    });
+}
+
+struct MetricsNode;
+
+impl Node for MetricsNode {
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        if !world.resource::<MetricsSettings>().enabled {
+            return Ok(());
+        }
+        let (Some(bind_groups), Some(buffers)) = (
+            world.get_resource::<SimulationBindGroups<Physarum>>(),
+            world.get_resource::<MetricsBuffers>(),
+        ) else {return Ok(());};
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let pipeline = world.resource::<MetricsPipeline>();
+        let (Some(trail_pipeline), Some(agent_pipeline), Some(density_pipeline)) = (
+            pipeline_cache.get_compute_pipeline(pipeline.trail_pipeline),
+            pipeline_cache.get_compute_pipeline(pipeline.agent_pipeline),
+            pipeline_cache.get_compute_pipeline(pipeline.density_pipeline),
+        ) else {return Ok(());};
+
+        let size = world.resource::<SimulationFrame>().size;
+        let (w, h) = (size.x, size.y);
+        let agents_len = world.resource::<Physarum>().agents.len() as u32;
+
+        let encoder = render_context.command_encoder();
+        encoder.clear_buffer(&buffers.stats, 0, None);
+        encoder.clear_buffer(&buffers.cells, 0, None);
+
+        let mut pass = encoder.begin_compute_pass(&ComputePassDescriptor::default());
+        bind_groups.set_latest(&mut pass);
+        pass.set_bind_group(3, &buffers.bind_group, &[]);
+
+        pass.set_pipeline(trail_pipeline);
+        pass.dispatch_workgroups(w.div_ceil(8), h.div_ceil(8), 1);
+        pass.set_pipeline(agent_pipeline);
+        pass.dispatch_workgroups(agents_len.div_ceil(64), 1, 1);
+        pass.set_pipeline(density_pipeline);
+        pass.dispatch_workgroups(1, 1, 1);
+        Ok(())
+    }
+}
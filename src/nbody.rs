@@ -0,0 +1,215 @@
+use std::hash::Hash;
+
+use bevy::{
+    input::mouse::{MouseScrollUnit, MouseWheel},
+    prelude::*,
+    render::{extract_resource::ExtractResource, render_resource::ShaderType},
+};
+use bevy_inspector_egui::{bevy_egui::EguiContexts, prelude::*};
+
+use crate::{
+    simulation::{
+        simulation_active, BufferInit, ComputeSimulationPlugin, Pass, Simulation, SimulationFrame,
+    },
+    trail,
+};
+
+/// Must match `TILE` and the workgroup size of the force passes in
+/// `nbody.wgsl`.
+const TILE: u32 = 256;
+
+/// Must match the size of `Body` in `nbody.wgsl`.
+const BODY_SIZE: u64 = 48;
+
+/// Gravitational N-body simulation with all-pairs forces.
+///
+/// Every workgroup loads the bodies a tile at a time into workgroup
+/// memory and sums up their softened pull on its own bodies, which move
+/// with a kick-drift-kick leapfrog step. Bodies are added up as points of
+/// light into the state image, seen from a view the left mouse button
+/// turns and the mouse wheel zooms.
+pub(crate) struct NBodyPlugin;
+impl Plugin for NBodyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(ComputeSimulationPlugin::<NBody>::default())
+            .add_system(orbit_view.run_if(simulation_active(NBody::NAME)));
+    }
+}
+
+/// Values match the scenes of `init` in `nbody.wgsl`.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Debug, Reflect, FromReflect)]
+enum NBodyScene {
+    /// A rotating exponential disk around a heavy core.
+    #[default]
+    Galaxy = 0,
+    /// Two Plummer spheres on a course to a glancing collision.
+    Clusters = 1,
+    /// A cold cube collapsing under its own weight.
+    Cube = 2,
+}
+
+#[derive(Resource, ExtractResource, Clone, Reflect, InspectorOptions)]
+#[reflect(Resource, InspectorOptions)]
+pub(crate) struct NBody {
+    scene: NBodyScene,
+    #[inspector(min = 256, max = 131072)]
+    count: u32,
+    /// Gravitational constant.
+    #[inspector(min = 0.0, max = 10.0, speed = 0.01)]
+    gravity: f32,
+    /// Added to every distance, keeps close encounters from flinging
+    /// bodies away.
+    #[inspector(min = 0.0001, max = 0.5, speed = 0.0001)]
+    softening: f32,
+    #[inspector(min = 0.0, max = 0.05, speed = 0.0001)]
+    time_step: f32,
+    /// Mass of all bodies together, of the disk for the galaxy.
+    #[inspector(min = 0.0, max = 10.0, speed = 0.01)]
+    mass: f32,
+    /// Mass of the core of the galaxy.
+    #[inspector(min = 0.0, max = 10.0, speed = 0.01)]
+    core_mass: f32,
+    /// Rotation of the view around the vertical axis, in radians.
+    yaw: f32,
+    #[inspector(min = -1.57, max = 1.57, speed = 0.01)]
+    pitch: f32,
+    /// Pixels per unit of length.
+    #[inspector(min = 1.0)]
+    zoom: f32,
+    /// Light of a single body.
+    #[inspector(min = 0.0, max = 10.0, speed = 0.01)]
+    brightness: f32,
+    /// Fraction of the light kept from the last step.
+    #[inspector(min = 0.0, max = 0.99, speed = 0.01)]
+    trail: f32,
+}
+
+impl Default for NBody {
+    fn default() -> Self {
+        Self {
+            scene: NBodyScene::Galaxy,
+            count: 1 << 15,
+            gravity: 1.0,
+            softening: 0.01,
+            time_step: 0.002,
+            mass: 1.0,
+            core_mass: 1.0,
+            yaw: 0.0,
+            pitch: 0.5,
+            zoom: 250.0,
+            brightness: 0.2,
+            trail: 0.5,
+        }
+    }
+}
+
+#[derive(ShaderType)]
+pub(crate) struct NBodyParams {
+    scene: u32,
+    gravity: f32,
+    softening: f32,
+    time_step: f32,
+    mass: f32,
+    core_mass: f32,
+    /// Rows of the view rotation.
+    view_x: Vec3,
+    view_y: Vec3,
+    zoom: f32,
+    brightness: f32,
+    trail: f32,
+}
+
+impl Simulation for NBody {
+    const NAME: &'static str = "nbody";
+    const SHADER: &'static str = "shaders/nbody.wgsl";
+    const ENTRY_POINTS: &'static [&'static str] = &[
+        "init",
+        "accelerate",
+        "clear",
+        "drift",
+        "forces",
+        "splat",
+        "draw",
+    ];
+    const BUFFERS: &'static [&'static str] = &["nbody_bodies", "nbody_deposits"];
+
+    type Params = NBodyParams;
+
+    fn params(&self) -> NBodyParams {
+        let view = Mat3::from_rotation_x(self.pitch) * Mat3::from_rotation_y(self.yaw);
+        let rows = view.transpose();
+        NBodyParams {
+            scene: self.scene as u32,
+            gravity: self.gravity,
+            softening: self.softening,
+            time_step: self.time_step,
+            mass: self.mass,
+            core_mass: self.core_mass,
+            view_x: rows.x_axis,
+            view_y: rows.y_axis,
+            zoom: self.zoom,
+            brightness: self.brightness,
+            trail: self.trail,
+        }
+    }
+
+    /// The bodies are only placed on a reset.
+    fn restart_key(&self) -> impl Hash {
+        (self.scene, self.count)
+    }
+
+    /// The bodies and the light they deposit, alpha is unused.
+    fn buffers(&self, size: UVec2) -> Vec<BufferInit> {
+        vec![
+            BufferInit::Zeroed(self.count.max(1) as u64 * BODY_SIZE),
+            trail::deposit_buffer(size),
+        ]
+    }
+
+    fn init_passes(&self) -> Vec<Pass> {
+        vec![
+            Pass::items("init", self.count),
+            Pass::workgroups("accelerate", self.force_workgroups()),
+            Pass::pixels("clear").swap(),
+        ]
+    }
+
+    fn passes(&self) -> Vec<Pass> {
+        vec![
+            Pass::items("drift", self.count),
+            Pass::workgroups("forces", self.force_workgroups()),
+            Pass::items("splat", self.count),
+            Pass::pixels("draw").swap(),
+        ]
+    }
+}
+
+impl NBody {
+    fn force_workgroups(&self) -> UVec3 {
+        UVec3::new(self.count.max(1).div_ceil(TILE), 1, 1)
+    }
+}
+
+fn orbit_view(
+    mut settings: ResMut<NBody>,
+    mut wheel: EventReader<MouseWheel>,
+    mut contexts: EguiContexts,
+    frame: Res<SimulationFrame>,
+) {
+    let scroll: f32 = wheel
+        .iter()
+        .map(|event| match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / 40.0,
+        })
+        .sum();
+    if scroll != 0.0 && !contexts.ctx_mut().is_pointer_over_area() {
+        settings.zoom = (settings.zoom * 1.2_f32.powf(scroll)).max(1.0);
+    }
+
+    let pointer = frame.pointer;
+    if pointer.buttons & 1 != 0 && pointer.delta != Vec2::ZERO {
+        settings.yaw += pointer.delta.x * 0.01;
+        settings.pitch = (settings.pitch + pointer.delta.y * 0.01).clamp(-1.57, 1.57);
+    }
+}
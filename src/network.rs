@@ -0,0 +1,623 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    fmt::Write as _,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+
+use crate::{
+    image::intensity,
+    readback::{Readback, ReadbackId, ReadbackSource},
+    simulation::{SimulationDisplay, SimulationFrame},
+};
+
+/// Extracts the transport network from the displayed image.
+///
+/// `F7` reads the displayed image back, skeletonizes it and writes the graph to
+/// [`NetworkSettings::path`] as `.graphml` and `.json`.
+pub(crate) struct NetworkPlugin;
+impl Plugin for NetworkPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NetworkSettings>()
+            .init_resource::<PendingNetwork>()
+            .add_system(network_hotkey)
+            .add_system(finish_network);
+    }
+}
+
+#[derive(Resource, Clone)]
+pub struct NetworkSettings {
+    /// Trail intensity in `0..=1` above which a pixel is part of the network.
+    pub threshold: f32,
+    /// Output path without extension.
+    pub path: PathBuf,
+}
+
+impl Default for NetworkSettings {
+    fn default() -> Self {
+        Self {
+            threshold: 0.3,
+            path: PathBuf::from("network"),
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+struct PendingNetwork(Option<(ReadbackId, UVec2)>);
+
+fn network_hotkey(
+    keys: Res<Input<KeyCode>>,
+    mut pending: ResMut<PendingNetwork>,
+    readback: Res<Readback>,
+    display: Res<SimulationDisplay>,
+    frame: Res<SimulationFrame>,
+) {
+    if keys.just_pressed(KeyCode::F7) && pending.0.is_none() {
+        pending.0 = Some((
+            readback.request(ReadbackSource::Image(display.0.clone())),
+            frame.size,
+        ));
+    }
+}
+
+fn finish_network(
+    mut pending: ResMut<PendingNetwork>,
+    readback: Res<Readback>,
+    settings: Res<NetworkSettings>,
+) {
+    let Some((id, size)) = pending.0 else {return;};
+    let Some(result) = readback.take(id) else {return;};
+    pending.0 = None;
+    let Ok(bytes) = result else {return;};
+
+    let intensity = intensity(&bytes);
+    let network = Network::extract(&intensity, size, settings.threshold);
+    info!(
+        "extracted network with {} nodes and {} edges",
+        network.nodes.len(),
+        network.edges.len()
+    );
+    if let Err(err) = network.save(&settings.path) {
+        error!(
+            "saving network to {} failed: {err}",
+            settings.path.display()
+        );
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum NodeKind {
+    Endpoint,
+    Junction,
+    /// Arbitrary point on a closed loop without junctions.
+    Loop,
+}
+
+struct NetworkNode {
+    position: Vec2,
+    kind: NodeKind,
+    degree: usize,
+}
+
+struct NetworkEdge {
+    source: usize,
+    target: usize,
+    /// Length along the skeleton in pixels.
+    length: f32,
+    /// Mean width of the thresholded trail along the edge in pixels.
+    thickness: f32,
+}
+
+pub(crate) struct Network {
+    size: UVec2,
+    threshold: f32,
+    nodes: Vec<NetworkNode>,
+    edges: Vec<NetworkEdge>,
+}
+
+const NEIGHBOURS: [IVec2; 8] = [
+    IVec2::new(0, -1),
+    IVec2::new(1, -1),
+    IVec2::new(1, 0),
+    IVec2::new(1, 1),
+    IVec2::new(0, 1),
+    IVec2::new(-1, 1),
+    IVec2::new(-1, 0),
+    IVec2::new(-1, -1),
+];
+
+struct Grid<T> {
+    size: IVec2,
+    data: Vec<T>,
+}
+
+impl<T: Copy + Default> Grid<T> {
+    fn new(size: UVec2) -> Self {
+        Self {
+            size: size.as_ivec2(),
+            data: vec![T::default(); (size.x * size.y) as usize],
+        }
+    }
+
+    fn get(&self, p: IVec2) -> T {
+        if p.cmplt(IVec2::ZERO).any() || p.cmpge(self.size).any() {
+            return T::default();
+        }
+        self.data[(p.y * self.size.x + p.x) as usize]
+    }
+
+    fn set(&mut self, p: IVec2, value: T) {
+        self.data[(p.y * self.size.x + p.x) as usize] = value;
+    }
+
+    fn positions(&self) -> impl Iterator<Item = IVec2> {
+        let w = self.size.x;
+        (0..self.size.x * self.size.y).map(move |i| IVec2::new(i % w, i / w))
+    }
+}
+
+impl Network {
+    pub(crate) fn extract(intensity: &[f32], size: UVec2, threshold: f32) -> Self {
+        let mut mask = Grid::<bool>::new(size);
+        for (v, m) in intensity.iter().zip(mask.data.iter_mut()) {
+            *m = *v >= threshold;
+        }
+        let distance = distance_transform(&mask);
+        let skeleton = skeletonize(mask);
+
+        let neighbours = |p: IVec2| {
+            NEIGHBOURS
+                .iter()
+                .map(move |n| p + *n)
+                .filter(|n| skeleton.get(*n))
+        };
+        // endpoints and junctions, skeleton pixels in the middle of an edge
+        // have exactly two transitions between background and skeleton
+        let transitions = |p: IVec2| {
+            let n = NEIGHBOURS.map(|n| skeleton.get(p + n));
+            (0..8).filter(|i| !n[*i] && n[(i + 1) % 8]).count()
+        };
+        let is_node = |p: IVec2| skeleton.get(p) && transitions(p) != 2 && transitions(p) != 0;
+
+        // adjacent junction pixels are merged into a single node
+        let mut node_of = Grid::<Option<usize>>::new(size);
+        let mut nodes = Vec::new();
+        for p in skeleton.positions() {
+            if !is_node(p) || node_of.get(p).is_some() {
+                continue;
+            }
+            let id = nodes.len();
+            let mut sum = Vec2::ZERO;
+            let mut count = 0;
+            let mut queue = VecDeque::from([p]);
+            node_of.set(p, Some(id));
+            while let Some(q) = queue.pop_front() {
+                sum += q.as_vec2();
+                count += 1;
+                for n in neighbours(q) {
+                    if is_node(n) && node_of.get(n).is_none() {
+                        node_of.set(n, Some(id));
+                        queue.push_back(n);
+                    }
+                }
+            }
+            nodes.push(NetworkNode {
+                position: sum / count as f32,
+                kind: if transitions(p) == 1 && count == 1 {
+                    NodeKind::Endpoint
+                } else {
+                    NodeKind::Junction
+                },
+                degree: 0,
+            });
+        }
+
+        let mut tracer = Tracer {
+            skeleton: &skeleton,
+            distance: &distance,
+            visited: Grid::new(size),
+        };
+        let mut edges = Vec::new();
+        let mut seen_direct = HashSet::new();
+        for p in skeleton.positions() {
+            let Some(id) = node_of.get(p) else {continue;};
+            for n in neighbours(p) {
+                match node_of.get(n) {
+                    // pixels of the same node
+                    Some(other) if other == id => {}
+                    // two nodes touching directly
+                    Some(other) => {
+                        let key = (id.min(other), id.max(other));
+                        if seen_direct.insert(key) {
+                            edges.push(NetworkEdge {
+                                source: id,
+                                target: other,
+                                length: (n - p).as_vec2().length(),
+                                thickness: distance.get(p) + distance.get(n),
+                            });
+                        }
+                    }
+                    None => edges.extend(tracer.trace(p, n, &node_of)),
+                }
+            }
+        }
+
+        // what is left are closed loops without any junction
+        for p in skeleton.positions() {
+            if !skeleton.get(p) || tracer.visited.get(p) || node_of.get(p).is_some() {
+                continue;
+            }
+            let id = nodes.len();
+            nodes.push(NetworkNode {
+                position: p.as_vec2(),
+                kind: NodeKind::Loop,
+                degree: 0,
+            });
+            node_of.set(p, Some(id));
+            if let Some(first) = neighbours(p).next() {
+                edges.extend(tracer.trace(p, first, &node_of));
+            }
+        }
+
+        for edge in edges.iter() {
+            nodes[edge.source].degree += 1;
+            nodes[edge.target].degree += 1;
+        }
+
+        Network {
+            size,
+            threshold,
+            nodes,
+            edges,
+        }
+    }
+
+    pub(crate) fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path.with_extension("graphml"), self.to_graphml())?;
+        fs::write(path.with_extension("json"), self.to_json())
+    }
+
+    fn to_graphml(&self) -> String {
+        let mut out = String::new();
+        out.push_str(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+            "  <key id=\"x\" for=\"node\" attr.name=\"x\" attr.type=\"double\"/>\n",
+            "  <key id=\"y\" for=\"node\" attr.name=\"y\" attr.type=\"double\"/>\n",
+            "  <key id=\"kind\" for=\"node\" attr.name=\"kind\" attr.type=\"string\"/>\n",
+            "  <key id=\"length\" for=\"edge\" attr.name=\"length\" attr.type=\"double\"/>\n",
+            "  <key id=\"thickness\" for=\"edge\" attr.name=\"thickness\" attr.type=\"double\"/>\n",
+            "  <graph id=\"network\" edgedefault=\"undirected\">\n",
+        ));
+        for (i, node) in self.nodes.iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "    <node id=\"n{i}\"><data key=\"x\">{}</data><data key=\"y\">{}</data><data key=\"kind\">{}</data></node>",
+                node.position.x,
+                node.position.y,
+                node.kind.name()
+            );
+        }
+        for (i, edge) in self.edges.iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "    <edge id=\"e{i}\" source=\"n{}\" target=\"n{}\"><data key=\"length\">{}</data><data key=\"thickness\">{}</data></edge>",
+                edge.source, edge.target, edge.length, edge.thickness
+            );
+        }
+        out.push_str("  </graph>\n</graphml>\n");
+        out
+    }
+
+    fn to_json(&self) -> String {
+        let nodes: Vec<String> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| {
+                format!(
+                    "{{\"id\":{i},\"x\":{},\"y\":{},\"kind\":\"{}\",\"degree\":{}}}",
+                    node.position.x,
+                    node.position.y,
+                    node.kind.name(),
+                    node.degree
+                )
+            })
+            .collect();
+        let edges: Vec<String> = self
+            .edges
+            .iter()
+            .map(|edge| {
+                format!(
+                    "{{\"source\":{},\"target\":{},\"length\":{},\"thickness\":{}}}",
+                    edge.source, edge.target, edge.length, edge.thickness
+                )
+            })
+            .collect();
+        format!(
+            "{{\"width\":{},\"height\":{},\"threshold\":{},\"nodes\":[\n{}\n],\"edges\":[\n{}\n]}}\n",
+            self.size.x,
+            self.size.y,
+            self.threshold,
+            nodes.join(",\n"),
+            edges.join(",\n")
+        )
+    }
+}
+
+/// Walks skeleton pixels from one node to the next.
+struct Tracer<'a> {
+    skeleton: &'a Grid<bool>,
+    distance: &'a Grid<f32>,
+    visited: Grid<bool>,
+}
+
+impl Tracer<'_> {
+    fn trace(
+        &mut self,
+        start: IVec2,
+        first: IVec2,
+        node_of: &Grid<Option<usize>>,
+    ) -> Option<NetworkEdge> {
+        let source = node_of.get(start).unwrap();
+        let mut length = (first - start).as_vec2().length();
+        let mut thickness = 0.0;
+        let mut pixels = 0;
+        let (mut prev, mut current) = (start, first);
+        loop {
+            if let Some(target) = node_of.get(current) {
+                return Some(NetworkEdge {
+                    source,
+                    target,
+                    length,
+                    thickness: if pixels == 0 {
+                        self.distance.get(current) * 2.0
+                    } else {
+                        thickness / pixels as f32
+                    },
+                });
+            }
+            if self.visited.get(current) {
+                return None;
+            }
+            self.visited.set(current, true);
+            thickness += self.distance.get(current) * 2.0;
+            pixels += 1;
+            let next = NEIGHBOURS
+                .iter()
+                .map(|n| current + *n)
+                .filter(|n| self.skeleton.get(*n) && *n != prev)
+                .filter(|n| match node_of.get(*n) {
+                    // don't step straight back into the node we started from
+                    Some(node) => node != source || pixels > 1,
+                    None => !self.visited.get(*n),
+                })
+                // prefer straight steps, diagonals can skip a corner pixel
+                .min_by_key(|n| (n.x - current.x).abs() + (n.y - current.y).abs())?;
+            length += (next - current).as_vec2().length();
+            prev = current;
+            current = next;
+        }
+    }
+}
+
+impl NodeKind {
+    fn name(self) -> &'static str {
+        match self {
+            NodeKind::Endpoint => "endpoint",
+            NodeKind::Junction => "junction",
+            NodeKind::Loop => "loop",
+        }
+    }
+}
+
+/// Zhang-Suen thinning down to a one pixel wide, 8-connected skeleton.
+fn skeletonize(mut mask: Grid<bool>) -> Grid<bool> {
+    let mut remove = Vec::new();
+    loop {
+        let mut changed = false;
+        for sub_iteration in 0..2 {
+            for p in mask.positions() {
+                if !mask.get(p) {
+                    continue;
+                }
+                let n = NEIGHBOURS.map(|n| mask.get(p + n));
+                let count = n.iter().filter(|v| **v).count();
+                let transitions = (0..8).filter(|i| !n[*i] && n[(i + 1) % 8]).count();
+                // n[0] is north, going clockwise
+                let (a, b) = if sub_iteration == 0 {
+                    (n[0] && n[2] && n[4], n[2] && n[4] && n[6])
+                } else {
+                    (n[0] && n[2] && n[6], n[0] && n[4] && n[6])
+                };
+                if (2..=6).contains(&count) && transitions == 1 && !a && !b {
+                    remove.push(p);
+                }
+            }
+            changed |= !remove.is_empty();
+            for p in remove.drain(..) {
+                mask.set(p, false);
+            }
+        }
+        if !changed {
+            return mask;
+        }
+    }
+}
+
+/// Chamfer distance of every pixel to the nearest pixel outside the mask.
+fn distance_transform(mask: &Grid<bool>) -> Grid<f32> {
+    const STRAIGHT: f32 = 1.0;
+    const DIAGONAL: f32 = std::f32::consts::SQRT_2;
+    let mut distance = Grid::<f32>::new(mask.size.as_uvec2());
+    for p in mask.positions() {
+        distance.set(p, if mask.get(p) { f32::MAX } else { 0.0 });
+    }
+    let relax = |distance: &mut Grid<f32>, p: IVec2, offsets: &[(IVec2, f32)]| {
+        let mut best = distance.get(p);
+        for (offset, cost) in offsets {
+            let n = p + *offset;
+            // outside the image counts as background
+            let d = if n.cmplt(IVec2::ZERO).any() || n.cmpge(distance.size).any() {
+                0.0
+            } else {
+                distance.get(n)
+            };
+            best = best.min(d + cost);
+        }
+        distance.set(p, best);
+    };
+    let forward = [
+        (IVec2::new(-1, 0), STRAIGHT),
+        (IVec2::new(-1, -1), DIAGONAL),
+        (IVec2::new(0, -1), STRAIGHT),
+        (IVec2::new(1, -1), DIAGONAL),
+    ];
+    let backward = forward.map(|(offset, cost)| (-offset, cost));
+    let positions: Vec<IVec2> = mask.positions().collect();
+    for p in positions.iter() {
+        if mask.get(*p) {
+            relax(&mut distance, *p, &forward);
+        }
+    }
+    for p in positions.iter().rev() {
+        if mask.get(*p) {
+            relax(&mut distance, *p, &backward);
+        }
+    }
+    distance
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `#` is inside the mask.
+    fn mask(rows: &[&str]) -> Grid<bool> {
+        let mut mask = Grid::new(UVec2::new(rows[0].len() as u32, rows.len() as u32));
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                mask.set(IVec2::new(x as i32, y as i32), c == '#');
+            }
+        }
+        mask
+    }
+
+    fn network(rows: &[&str]) -> Network {
+        let mask = mask(rows);
+        let intensity: Vec<f32> = mask.data.iter().map(|m| *m as u8 as f32).collect();
+        Network::extract(&intensity, mask.size.as_uvec2(), 0.5)
+    }
+
+    fn kinds(network: &Network) -> Vec<&'static str> {
+        let mut kinds: Vec<_> = network.nodes.iter().map(|node| node.kind.name()).collect();
+        kinds.sort();
+        kinds
+    }
+
+    const T_JUNCTION: &[&str] = &[
+        ".........",
+        ".#######.",
+        "....#....",
+        "....#....",
+        "....#....",
+        ".........",
+    ];
+
+    #[test]
+    fn thinning() {
+        let skeleton = skeletonize(mask(&[
+            "..........",
+            ".########.",
+            ".########.",
+            ".########.",
+            "..........",
+        ]));
+        let pixels: Vec<IVec2> = skeleton.positions().filter(|p| skeleton.get(*p)).collect();
+        assert!(!pixels.is_empty());
+        assert!(pixels.iter().all(|p| p.y == 2), "{pixels:?}");
+
+        // a one pixel wide line is already thin
+        let line = &[".....", ".###.", "....."];
+        assert_eq!(skeletonize(mask(line)).data, mask(line).data);
+    }
+
+    #[test]
+    fn chamfer_distance() {
+        let distance = distance_transform(&mask(&[
+            ".......", ".#####.", ".#####.", ".#####.", ".#####.", ".#####.", ".......",
+        ]));
+        assert_eq!(distance.get(IVec2::new(0, 0)), 0.0);
+        assert_eq!(distance.get(IVec2::new(1, 1)), 1.0);
+        assert_eq!(distance.get(IVec2::new(3, 1)), 1.0);
+        assert_eq!(distance.get(IVec2::new(2, 2)), 2.0);
+        assert_eq!(distance.get(IVec2::new(3, 3)), 3.0);
+
+        // the image border counts as background
+        let distance = distance_transform(&mask(&["###"]));
+        assert_eq!(distance.data, [1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn line() {
+        let network = network(&[".........", ".#######.", "........."]);
+        assert_eq!(kinds(&network), ["endpoint", "endpoint"]);
+        assert_eq!(network.edges.len(), 1);
+        assert_eq!(network.edges[0].length, 6.0);
+        assert_eq!(network.edges[0].thickness, 2.0);
+        assert!(network.nodes.iter().all(|node| node.degree == 1));
+    }
+
+    #[test]
+    fn t_junction() {
+        let network = network(T_JUNCTION);
+        assert_eq!(kinds(&network), ["endpoint", "endpoint", "endpoint", "junction"]);
+        assert_eq!(network.edges.len(), 3);
+        let junction = network
+            .nodes
+            .iter()
+            .position(|node| node.kind == NodeKind::Junction)
+            .unwrap();
+        assert_eq!(network.nodes[junction].degree, 3);
+        assert!(network
+            .edges
+            .iter()
+            .all(|edge| edge.source == junction || edge.target == junction));
+    }
+
+    #[test]
+    fn ring() {
+        let network = network(&[
+            ".......", ".#####.", ".#...#.", ".#...#.", ".#...#.", ".#####.", ".......",
+        ]);
+        assert_eq!(kinds(&network), ["loop"]);
+        assert_eq!(network.edges.len(), 1);
+        assert_eq!((network.edges[0].source, network.edges[0].target), (0, 0));
+        assert_eq!(network.edges[0].length, 16.0);
+    }
+
+    #[test]
+    fn graphml_and_json() {
+        let network = network(T_JUNCTION);
+        let graphml = network.to_graphml();
+        assert!(graphml.starts_with("<?xml"));
+        assert_eq!(graphml.matches("<node ").count(), 4);
+        assert_eq!(graphml.matches("<edge ").count(), 3);
+        assert!(graphml.ends_with("</graph>\n</graphml>\n"));
+
+        let json: serde_json::Value = serde_json::from_str(&network.to_json()).unwrap();
+        assert_eq!(json["width"], 9);
+        assert_eq!(json["height"], 6);
+        assert_eq!(json["threshold"], 0.5);
+        let nodes = json["nodes"].as_array().unwrap();
+        assert_eq!(nodes.len(), 4);
+        assert_eq!(nodes.iter().filter(|node| node["kind"] == "junction").count(), 1);
+        let edges = json["edges"].as_array().unwrap();
+        assert_eq!(edges.len(), 3);
+        for edge in edges {
+            assert!(edge["length"].as_f64().unwrap() > 0.0);
+        }
+    }
+}
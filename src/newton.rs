@@ -0,0 +1,281 @@
+use bevy::{
+    input::mouse::{MouseScrollUnit, MouseWheel},
+    prelude::*,
+    render::{extract_resource::ExtractResource, render_resource::ShaderType},
+};
+use bevy_inspector_egui::{bevy_egui::EguiContexts, prelude::*};
+
+use crate::simulation::{
+    simulation_active, ComputeSimulationPlugin, Pass, Simulation, SimulationFrame,
+};
+
+/// Roots beyond this are ignored, the shader has a fixed size array.
+const MAX_ROOTS: usize = 8;
+
+/// Distance in pixels from a root at which it can be grabbed.
+const HANDLE_RADIUS: f32 = 10.0;
+
+/// Narrower views run out of `f32` precision.
+const MIN_HEIGHT: f32 = 1e-4;
+
+/// Newton's method on a polynomial given by its roots, colored by the
+/// root every pixel converges to and shaded by the iterations it took.
+///
+/// The left mouse button drags roots or pans the view, the right one adds
+/// a root or removes the one under the pointer and the mouse wheel zooms
+/// smoothly towards the pointer.
+pub(crate) struct NewtonPlugin;
+impl Plugin for NewtonPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(ComputeSimulationPlugin::<NewtonFractal>::default())
+            .init_resource::<Navigation>()
+            .add_systems(
+                (navigate, zoom_smooth.after(navigate))
+                    .distributive_run_if(simulation_active(NewtonFractal::NAME)),
+            );
+    }
+}
+
+#[derive(Resource, ExtractResource, Clone, Reflect, InspectorOptions)]
+#[reflect(Resource, InspectorOptions)]
+pub(crate) struct NewtonFractal {
+    /// Roots of the polynomial, at most eight.
+    roots: Vec<Vec2>,
+    #[inspector(min = 1, max = 500)]
+    iterations: u32,
+    /// Scales every Newton step, 1 is the plain method.
+    #[inspector(min = 0.05, max = 2.0, speed = 0.01)]
+    damping: f32,
+    /// How much slow convergence darkens a pixel.
+    #[inspector(min = 0.0, max = 1.0, speed = 0.01)]
+    shading: f32,
+    /// Shown part of the complex plane as min x, min y, max x, max y. The
+    /// width follows the aspect ratio of the window.
+    extents: Vec4,
+    /// Root under the pointer or being dragged.
+    #[reflect(ignore)]
+    highlighted: Option<usize>,
+}
+
+impl Default for NewtonFractal {
+    fn default() -> Self {
+        // z³ - 1
+        let roots = (0..3)
+            .map(|i| Vec2::from_angle(i as f32 * std::f32::consts::TAU / 3.0))
+            .collect();
+        Self {
+            roots,
+            iterations: 64,
+            damping: 1.0,
+            shading: 0.6,
+            extents: Vec4::new(-2.0, -2.0, 2.0, 2.0),
+            highlighted: None,
+        }
+    }
+}
+
+impl NewtonFractal {
+    /// The point of the complex plane shown at a pixel from the top left.
+    fn to_plane(&self, pixel: Vec2, size: Vec2) -> Vec2 {
+        let t = pixel / size;
+        Vec2::new(
+            self.extents.x + (self.extents.z - self.extents.x) * t.x,
+            self.extents.w + (self.extents.y - self.extents.w) * t.y,
+        )
+    }
+
+    /// Pixels per unit of the complex plane.
+    fn scale(&self, size: Vec2) -> f32 {
+        size.y / (self.extents.w - self.extents.y)
+    }
+
+    fn root_at(&self, pixel: Vec2, size: Vec2) -> Option<usize> {
+        let point = self.to_plane(pixel, size);
+        let radius = HANDLE_RADIUS / self.scale(size);
+        self.roots
+            .iter()
+            .take(MAX_ROOTS)
+            .enumerate()
+            .map(|(i, root)| (i, root.distance(point)))
+            .filter(|&(_, distance)| distance < radius)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, _)| i)
+    }
+}
+
+#[derive(ShaderType)]
+pub(crate) struct NewtonParams {
+    /// Roots in `xy`.
+    roots: [Vec4; MAX_ROOTS],
+    root_count: u32,
+    iterations: u32,
+    damping: f32,
+    shading: f32,
+    extents: Vec4,
+    /// Index of the highlighted root, `MAX_ROOTS` for none.
+    highlighted: u32,
+    handle_radius: f32,
+}
+
+impl Simulation for NewtonFractal {
+    const NAME: &'static str = "newton";
+    const SHADER: &'static str = "shaders/newton.wgsl";
+    const ENTRY_POINTS: &'static [&'static str] = &[];
+
+    type Params = NewtonParams;
+
+    fn params(&self) -> NewtonParams {
+        let mut roots = [Vec4::ZERO; MAX_ROOTS];
+        for (slot, root) in roots.iter_mut().zip(&self.roots) {
+            *slot = root.extend(0.0).extend(0.0);
+        }
+        NewtonParams {
+            roots,
+            root_count: self.roots.len().min(MAX_ROOTS) as u32,
+            iterations: self.iterations,
+            damping: self.damping,
+            shading: self.shading,
+            extents: self.extents,
+            highlighted: self.highlighted.unwrap_or(MAX_ROOTS) as u32,
+            handle_radius: HANDLE_RADIUS,
+        }
+    }
+
+    /// The fractal only depends on the parameters, the display pass draws
+    /// it every frame.
+    fn passes(&self) -> Vec<Pass> {
+        Vec::new()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Drag {
+    Root(usize),
+    Pan,
+}
+
+/// Pointer interaction and the extents the view zooms towards.
+#[derive(Resource, Default)]
+struct Navigation {
+    target: Vec4,
+    timer: Timer,
+    drag: Option<Drag>,
+    buttons: u32,
+}
+
+fn navigate(
+    mut settings: ResMut<NewtonFractal>,
+    mut navigation: ResMut<Navigation>,
+    mut wheel: EventReader<MouseWheel>,
+    mut contexts: EguiContexts,
+    frame: Res<SimulationFrame>,
+) {
+    let size = frame.size.as_vec2();
+    if size.min_element() < 1.0 {
+        return;
+    }
+    let scroll: f32 = wheel
+        .iter()
+        .map(|event| match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / 40.0,
+        })
+        .sum();
+
+    // the height is kept, the width follows the window
+    let mut extents = settings.extents;
+    let center = (extents.x + extents.z) / 2.0;
+    let half_width = (extents.w - extents.y) * size.x / size.y / 2.0;
+    if ((extents.z - extents.x) / 2.0 - half_width).abs() > half_width * 1e-3 {
+        extents.x = center - half_width;
+        extents.z = center + half_width;
+        settings.extents = extents;
+    }
+
+    let pointer = frame.pointer;
+    let pressed = pointer.buttons & !navigation.buttons;
+    navigation.buttons = pointer.buttons;
+    let Some(position) = pointer.position else {
+        navigation.drag = None;
+        return;
+    };
+
+    let over_ui = contexts.ctx_mut().is_pointer_over_area();
+    if scroll != 0.0 && !over_ui {
+        // zooms around the point under the pointer, further while zooming
+        if navigation.timer.finished() || navigation.timer.duration().is_zero() {
+            navigation.target = settings.extents;
+        }
+        let target = navigation.target;
+        let t = position / size;
+        let anchor = Vec2::new(
+            target.x + (target.z - target.x) * t.x,
+            target.w + (target.y - target.w) * t.y,
+        );
+        let factor = 0.8_f32.powf(scroll).max(MIN_HEIGHT / (target.w - target.y));
+        let anchor = anchor.extend(anchor.x).extend(anchor.y);
+        navigation.target = anchor + (target - anchor) * factor;
+        navigation.timer = Timer::from_seconds(0.4, TimerMode::Once);
+    }
+
+    if pressed & 1 != 0 {
+        navigation.drag = Some(match settings.root_at(position, size) {
+            Some(root) => Drag::Root(root),
+            None => Drag::Pan,
+        });
+    }
+    if pointer.buttons & 1 == 0 {
+        navigation.drag = None;
+    }
+    let delta = pointer.delta / settings.scale(size) * Vec2::new(1.0, -1.0);
+    match navigation.drag {
+        Some(Drag::Root(root)) if delta != Vec2::ZERO => {
+            if let Some(root) = settings.roots.get_mut(root) {
+                *root += delta;
+            }
+        }
+        Some(Drag::Pan) if delta != Vec2::ZERO => {
+            // the plane follows the pointer
+            let offset = -delta.extend(delta.x).extend(delta.y);
+            settings.extents += offset;
+            navigation.target += offset;
+        }
+        _ => {}
+    }
+
+    if pressed & 2 != 0 {
+        match settings.root_at(position, size) {
+            Some(root) if settings.roots.len() > 2 => {
+                settings.roots.remove(root);
+            }
+            None if settings.roots.len() < MAX_ROOTS => {
+                let root = settings.to_plane(position, size);
+                settings.roots.push(root);
+            }
+            _ => {}
+        }
+    }
+
+    let highlighted = match navigation.drag {
+        Some(Drag::Root(root)) => Some(root),
+        _ if over_ui => None,
+        _ => settings.root_at(position, size),
+    };
+    if settings.highlighted != highlighted {
+        settings.highlighted = highlighted;
+    }
+}
+
+/// Moves the view towards the zoom target over the time of the timer.
+fn zoom_smooth(
+    mut navigation: ResMut<Navigation>,
+    mut settings: ResMut<NewtonFractal>,
+    time: Res<Time>,
+) {
+    if navigation.timer.duration().is_zero() || navigation.timer.finished() {
+        return;
+    }
+    navigation.timer.tick(time.delta());
+    let target = navigation.target;
+    settings.extents = settings.extents.lerp(target, navigation.timer.percent());
+}
@@ -0,0 +1,345 @@
+use std::borrow::Cow;
+
+use bevy::{
+    prelude::*,
+    render::{
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+        render_asset::RenderAssets,
+        render_graph::{Node, NodeRunError, RenderGraph, RenderGraphContext},
+        render_resource::{
+            encase::UniformBuffer, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+            BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, BufferBindingType,
+            BufferInitDescriptor, BufferUsages, CachedRenderPipelineId, ColorTargetState,
+            ColorWrites, Extent3d, FragmentState, LoadOp, MultisampleState, Operations,
+            PipelineCache, PrimitiveState, PrimitiveTopology, RenderPassColorAttachment,
+            RenderPassDescriptor, RenderPipelineDescriptor, ShaderStages, ShaderType,
+            TextureDimension, TextureFormat, TextureUsages, VertexState,
+        },
+        renderer::{RenderContext, RenderDevice},
+        RenderApp, RenderSet,
+    },
+};
+use bevy_inspector_egui::{prelude::*, quick::ResourceInspectorPlugin};
+
+use crate::{
+    physarum::Physarum,
+    simulation::{
+        simulation_active, ActiveSimulation, Simulation, SimulationBuffers, SimulationFrame,
+    },
+};
+
+/// Draws the agents themselves on top of the trail map.
+///
+/// The agents are read straight from the agents buffer of [`Physarum`] by
+/// a render pass into a transparent image shown above the trail sprite.
+/// `F2` toggles it.
+pub(crate) struct OverlayPlugin;
+impl Plugin for OverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AgentOverlay>()
+            .init_resource::<OverlayImage>()
+            .add_plugin(
+                ResourceInspectorPlugin::<AgentOverlay>::default()
+                    .run_if(simulation_active(Physarum::NAME)),
+            )
+            .add_plugin(ExtractResourcePlugin::<AgentOverlay>::default())
+            .add_plugin(ExtractResourcePlugin::<OverlayImage>::default())
+            .add_system(toggle_overlay.run_if(simulation_active(Physarum::NAME)))
+            .add_system(update_overlay_image);
+
+        let render_app = app.sub_app_mut(RenderApp);
+        render_app
+            .init_resource::<OverlayPipeline>()
+            .add_system(queue_overlay_bind_group.in_set(RenderSet::Queue));
+        let mut render_graph = render_app.world.resource_mut::<RenderGraph>();
+        render_graph.add_node("agent_overlay", OverlayNode);
+        render_graph.add_node_edge(Physarum::NAME, "agent_overlay");
+        render_graph.add_node_edge(
+            "agent_overlay",
+            bevy::render::main_graph::node::CAMERA_DRIVER,
+        );
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Reflect, FromReflect)]
+enum OverlayMode {
+    #[default]
+    Points,
+    Headings,
+}
+
+#[derive(Resource, ExtractResource, Clone, Reflect, InspectorOptions)]
+#[reflect(Resource, InspectorOptions)]
+struct AgentOverlay {
+    enabled: bool,
+    mode: OverlayMode,
+    /// Only every `stride`th agent is drawn.
+    #[inspector(min = 1)]
+    stride: u32,
+    #[inspector(min = 0.0, max = 50.0)]
+    line_length: f32,
+    color: Color,
+}
+
+impl Default for AgentOverlay {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            mode: OverlayMode::Points,
+            stride: 16,
+            line_length: 6.0,
+            color: Color::rgba(1.0, 0.2, 0.1, 1.0),
+        }
+    }
+}
+
+#[derive(ShaderType)]
+struct OverlayParams {
+    size: Vec2,
+    stride: u32,
+    line_length: f32,
+    color: Vec4,
+}
+
+#[derive(Resource, ExtractResource, Clone)]
+struct OverlayImage(Handle<Image>);
+
+#[derive(Component)]
+struct OverlayMarker;
+
+impl FromWorld for OverlayImage {
+    fn from_world(world: &mut World) -> Self {
+        let mut win = world.query::<&Window>();
+        let win = win.single(world);
+        let (w, h) = (win.width() as u32, win.height() as u32);
+        let image = world
+            .resource_mut::<Assets<Image>>()
+            .add(create_overlay_image(w, h));
+        world.spawn((
+            SpriteBundle {
+                texture: image.clone(),
+                transform: Transform::from_xyz(0.0, 0.0, 1.0),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            OverlayMarker,
+        ));
+        OverlayImage(image)
+    }
+}
+
+fn create_overlay_image(width: u32, height: u32) -> Image {
+    let mut image = Image::new_fill(
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, 0],
+        TextureFormat::Rgba8Unorm,
+    );
+    image.texture_descriptor.usage =
+        TextureUsages::COPY_DST | TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING;
+    image
+}
+
+fn toggle_overlay(keys: Res<Input<KeyCode>>, mut overlay: ResMut<AgentOverlay>) {
+    if keys.just_pressed(KeyCode::F2) {
+        overlay.enabled = !overlay.enabled;
+    }
+}
+
+fn update_overlay_image(
+    overlay: Res<AgentOverlay>,
+    active: Res<ActiveSimulation>,
+    handle: Res<OverlayImage>,
+    frame: Res<SimulationFrame>,
+    mut images: ResMut<Assets<Image>>,
+    mut sprites: Query<&mut Visibility, With<OverlayMarker>>,
+) {
+    for mut visibility in sprites.iter_mut() {
+        *visibility = if overlay.enabled && active.0 == Physarum::NAME {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+    }
+    let size = frame.size;
+    let Some(image) = images.get(&handle.0) else {return;};
+    if size.x > 0 && size.y > 0 && image.size().as_uvec2() != size {
+        images.set_untracked(&handle.0, create_overlay_image(size.x, size.y));
+    }
+}
+
+#[derive(Resource)]
+struct OverlayPipeline {
+    points_pipeline: CachedRenderPipelineId,
+    headings_pipeline: CachedRenderPipelineId,
+    bind_group_layout: BindGroupLayout,
+}
+
+impl FromWorld for OverlayPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+        let bind_group_layout =
+            render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("LayoutOverlayBindGroup"),
+                entries: &[
+                    BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: ShaderStages::VERTEX,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: ShaderStages::VERTEX_FRAGMENT,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: Some(OverlayParams::min_size()),
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let shader = world.resource::<AssetServer>().load("shaders/overlay.wgsl");
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let queue = |entry_point: &'static str, topology| {
+            pipeline_cache.queue_render_pipeline(RenderPipelineDescriptor {
+                label: Some(Cow::from(entry_point)),
+                layout: vec![bind_group_layout.clone()],
+                push_constant_ranges: vec![],
+                vertex: VertexState {
+                    shader: shader.clone(),
+                    shader_defs: vec![],
+                    entry_point: Cow::from(entry_point),
+                    buffers: vec![],
+                },
+                primitive: PrimitiveState {
+                    topology,
+                    ..default()
+                },
+                depth_stencil: None,
+                multisample: MultisampleState::default(),
+                fragment: Some(FragmentState {
+                    shader: shader.clone(),
+                    shader_defs: vec![],
+                    entry_point: Cow::from("fragment"),
+                    targets: vec![Some(ColorTargetState {
+                        format: TextureFormat::Rgba8Unorm,
+                        blend: None,
+                        write_mask: ColorWrites::ALL,
+                    })],
+                }),
+            })
+        };
+
+        Self {
+            points_pipeline: queue("points", PrimitiveTopology::PointList),
+            headings_pipeline: queue("headings", PrimitiveTopology::LineList),
+            bind_group_layout,
+        }
+    }
+}
+
+#[derive(Resource)]
+struct OverlayBindGroup(BindGroup);
+
+fn queue_overlay_bind_group(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    pipeline: Res<OverlayPipeline>,
+    overlay: Res<AgentOverlay>,
+    buffers: Option<Res<SimulationBuffers<Physarum>>>,
+    frame: Res<SimulationFrame>,
+) {
+    let Some(agents) = buffers.as_ref().and_then(|buffers| buffers.get("agents")) else {return;};
+    if !overlay.enabled {
+        return;
+    }
+    let mut uniform = UniformBuffer::new(Vec::new());
+    uniform
+        .write(&OverlayParams {
+            size: frame.size.as_vec2(),
+            stride: overlay.stride.max(1),
+            line_length: overlay.line_length,
+            color: Vec4::from(overlay.color.as_rgba_f32()),
+        })
+        .unwrap();
+    let params = render_device.create_buffer_with_data(&BufferInitDescriptor {
+        label: Some("overlay_params"),
+        contents: uniform.as_ref(),
+        usage: BufferUsages::UNIFORM,
+    });
+    commands.insert_resource(OverlayBindGroup(render_device.create_bind_group(
+        &BindGroupDescriptor {
+            label: Some("OverlayBindGroup"),
+            layout: &pipeline.bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: agents.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: params.as_entire_binding(),
+                },
+            ],
+        },
+    )));
+}
+
+struct OverlayNode;
+
+impl Node for OverlayNode {
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let overlay = world.resource::<AgentOverlay>();
+        if !overlay.enabled || world.resource::<ActiveSimulation>().0 != Physarum::NAME {
+            return Ok(());
+        }
+        let Some(bind_group) = world.get_resource::<OverlayBindGroup>() else {return Ok(());};
+        let gpu_images = world.resource::<RenderAssets<Image>>();
+        let Some(target) = gpu_images.get(&world.resource::<OverlayImage>().0) else {return Ok(());};
+        let pipeline = world.resource::<OverlayPipeline>();
+        let (id, vertices_per_agent) = match overlay.mode {
+            OverlayMode::Points => (pipeline.points_pipeline, 1),
+            OverlayMode::Headings => (pipeline.headings_pipeline, 2),
+        };
+        let Some(render_pipeline) = world.resource::<PipelineCache>().get_render_pipeline(id)
+        else {return Ok(());};
+
+        let agents_len = world.resource::<Physarum>().agents.len() as u32;
+        let drawn = agents_len.div_ceil(overlay.stride.max(1));
+
+        let mut pass = render_context
+            .command_encoder()
+            .begin_render_pass(&RenderPassDescriptor {
+                label: Some("agent_overlay"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: &target.texture_view,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Clear(Color::NONE.into()),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+        pass.set_pipeline(render_pipeline);
+        pass.set_bind_group(0, &bind_group.0, &[]);
+        pass.draw(0..drawn * vertices_per_agent, 0..1);
+        Ok(())
+    }
+}
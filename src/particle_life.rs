@@ -0,0 +1,358 @@
+use std::{fmt::Write, fs, hash::Hash, path::PathBuf};
+
+use bevy::{
+    prelude::*,
+    render::{extract_resource::ExtractResource, render_resource::ShaderType},
+};
+use bevy_inspector_egui::{
+    bevy_egui::{egui, EguiContexts},
+    prelude::*,
+};
+use rand::Rng;
+
+use crate::{
+    grid,
+    simulation::{simulation_active, BufferInit, ComputeSimulationPlugin, Pass, Simulation},
+    trail,
+};
+
+/// Types beyond this are ignored, the matrix is a fixed size uniform.
+const MAX_TYPES: usize = 8;
+
+/// Bytes of a particle, its position, velocity and type.
+const PARTICLE_SIZE: u64 = 24;
+
+/// Particle Life: particles of a few types attract or repel each other
+/// by a type x type matrix, in a world that wraps around at the edges.
+///
+/// Like the boids, the particles are sorted into a grid of cells as large
+/// as the interaction radius every step. The matrix is edited in its own
+/// window, which can randomize, mirror, save and load it.
+pub(crate) struct ParticleLifePlugin;
+impl Plugin for ParticleLifePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(ComputeSimulationPlugin::<ParticleLife>::default())
+            .add_system(attraction_window.run_if(simulation_active(ParticleLife::NAME)));
+    }
+}
+
+#[derive(Resource, ExtractResource, Clone, Reflect, InspectorOptions)]
+#[reflect(Resource, InspectorOptions)]
+pub(crate) struct ParticleLife {
+    #[inspector(min = 1, max = 1_000_000)]
+    count: u32,
+    #[inspector(min = 1, max = 8)]
+    types: u32,
+    /// Distance up to which particles interact, also the grid cell size.
+    #[inspector(min = 4.0, max = 200.0)]
+    radius: f32,
+    /// Fraction of the radius within which every particle repels.
+    #[inspector(min = 0.01, max = 0.99, speed = 0.01)]
+    repulsion: f32,
+    #[inspector(min = 0.0, max = 100.0)]
+    force: f32,
+    /// Fraction of the velocity lost per second.
+    #[inspector(min = 0.0, max = 0.99, speed = 0.01)]
+    friction: f32,
+    /// File written and read by the matrix window.
+    matrix_path: PathBuf,
+    /// How much a row type is attracted to a column type, -1 to 1.
+    #[reflect(ignore)]
+    matrix: [[f32; MAX_TYPES]; MAX_TYPES],
+}
+
+impl Default for ParticleLife {
+    fn default() -> Self {
+        let mut settings = Self {
+            count: 40_000,
+            types: 6,
+            radius: 40.0,
+            repulsion: 0.3,
+            force: 10.0,
+            friction: 0.9,
+            matrix_path: "attraction.txt".into(),
+            matrix: default(),
+        };
+        settings.randomize();
+        settings
+    }
+}
+
+impl ParticleLife {
+    fn types(&self) -> usize {
+        (self.types as usize).clamp(1, MAX_TYPES)
+    }
+
+    fn randomize(&mut self) {
+        let mut rng = rand::thread_rng();
+        for row in self.matrix.iter_mut() {
+            for value in row.iter_mut() {
+                *value = rng.gen_range(-1.0..=1.0);
+            }
+        }
+    }
+
+    /// Makes the attraction symmetric, the upper triangle wins.
+    fn mirror(&mut self) {
+        for i in 0..MAX_TYPES {
+            for j in 0..i {
+                self.matrix[i][j] = self.matrix[j][i];
+            }
+        }
+    }
+
+    /// One row per line, values separated by spaces.
+    fn matrix_to_text(&self) -> String {
+        let types = self.types();
+        let mut text = String::new();
+        for row in &self.matrix[..types] {
+            let row: Vec<_> = row[..types]
+                .iter()
+                .map(|value| format!("{value:.3}"))
+                .collect();
+            let _ = writeln!(text, "{}", row.join(" "));
+        }
+        text
+    }
+
+    /// Reads a square matrix written by [`ParticleLife::matrix_to_text`],
+    /// its size sets the number of types.
+    fn matrix_from_text(&mut self, text: &str) -> Result<(), String> {
+        let rows = text
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                line.split_whitespace()
+                    .map(|value| {
+                        // `parse` takes "NaN" and "inf", which `clamp` lets through
+                        value
+                            .parse::<f32>()
+                            .ok()
+                            .filter(|value| value.is_finite())
+                            .ok_or_else(|| format!("{value} is not a number"))
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if rows.is_empty() || rows.len() > MAX_TYPES {
+            return Err(format!(
+                "expected 1 to {MAX_TYPES} rows, found {}",
+                rows.len()
+            ));
+        }
+        if rows.iter().any(|row| row.len() != rows.len()) {
+            return Err("the matrix is not square".to_owned());
+        }
+        for (i, row) in rows.iter().enumerate() {
+            for (j, value) in row.iter().enumerate() {
+                self.matrix[i][j] = value.clamp(-1.0, 1.0);
+            }
+        }
+        self.types = rows.len() as u32;
+        Ok(())
+    }
+}
+
+#[derive(ShaderType)]
+pub(crate) struct ParticleLifeParams {
+    /// Row `i` holds the attraction of type `i` in two vectors.
+    matrix: [Vec4; MAX_TYPES * 2],
+    count: u32,
+    types: u32,
+    radius: f32,
+    repulsion: f32,
+    force: f32,
+    friction: f32,
+    /// Side of a grid cell, at least the radius.
+    cell_size: f32,
+}
+
+impl Simulation for ParticleLife {
+    const NAME: &'static str = "particle-life";
+    const SHADER: &'static str = "shaders/particle_life.wgsl";
+    const ENTRY_POINTS: &'static [&'static str] = &[
+        "init", "clear", "count", "scan", "scatter", "update", "draw",
+    ];
+    const BUFFERS: &'static [&'static str] = &[
+        "particles",
+        "particle_deposits",
+        "particle_cell_counts",
+        "particle_cell_starts",
+        "particles_sorted",
+    ];
+
+    type Params = ParticleLifeParams;
+
+    fn params(&self) -> ParticleLifeParams {
+        let mut matrix = [Vec4::ZERO; MAX_TYPES * 2];
+        for (i, row) in self.matrix.iter().enumerate() {
+            matrix[i * 2] = Vec4::from_slice(&row[..4]);
+            matrix[i * 2 + 1] = Vec4::from_slice(&row[4..]);
+        }
+        ParticleLifeParams {
+            matrix,
+            count: self.count,
+            types: self.types() as u32,
+            radius: self.radius,
+            repulsion: self.repulsion,
+            force: self.force,
+            friction: self.friction,
+            cell_size: grid::cell_size(self.radius),
+        }
+    }
+
+    /// The buffers are sized for the count and the types are assigned
+    /// when the particles are placed.
+    fn restart_key(&self) -> impl Hash {
+        (self.count, self.types)
+    }
+
+    /// The particles, their colors per pixel, two `u32` per grid cell and
+    /// the particles again, sorted by cell.
+    fn buffers(&self, size: UVec2) -> Vec<BufferInit> {
+        let particles = self.count as u64 * PARTICLE_SIZE;
+        let [cell_counts, cell_starts] = grid::cell_buffers(size);
+        vec![
+            BufferInit::Zeroed(particles),
+            trail::deposit_buffer(size),
+            cell_counts,
+            cell_starts,
+            BufferInit::Zeroed(particles),
+        ]
+    }
+
+    fn init_passes(&self) -> Vec<Pass> {
+        vec![Pass::items("init", self.count)]
+    }
+
+    fn passes(&self) -> Vec<Pass> {
+        let [clear, count, scan, scatter] = grid::sort_passes(self.count);
+        vec![
+            clear,
+            count,
+            scan,
+            scatter,
+            Pass::items("update", self.count),
+            Pass::pixels("draw").swap(),
+        ]
+    }
+}
+
+fn attraction_window(mut contexts: EguiContexts, mut settings: ResMut<ParticleLife>) {
+    egui::Window::new("Attraction").show(contexts.ctx_mut(), |ui| {
+        let types = settings.types();
+        let color = |i: usize| {
+            let [r, g, b, _] = Color::hsl(360.0 * i as f32 / types as f32, 1.0, 0.5).as_rgba_f32();
+            let channel = |value: f32| (value * 255.0) as u8;
+            egui::Color32::from_rgb(channel(r), channel(g), channel(b))
+        };
+        // only write back edits, so change detection isn't triggered every frame
+        let mut matrix = settings.matrix;
+        egui::Grid::new("attraction_matrix").show(ui, |ui| {
+            ui.label("");
+            for j in 0..types {
+                ui.colored_label(color(j), j.to_string());
+            }
+            ui.end_row();
+            for (i, row) in matrix[..types].iter_mut().enumerate() {
+                ui.colored_label(color(i), i.to_string());
+                for value in row[..types].iter_mut() {
+                    ui.add(
+                        egui::DragValue::new(value)
+                            .speed(0.01)
+                            .clamp_range(-1.0..=1.0)
+                            .fixed_decimals(2),
+                    );
+                }
+                ui.end_row();
+            }
+        });
+        if matrix != settings.matrix {
+            settings.matrix = matrix;
+        }
+
+        ui.horizontal(|ui| {
+            if ui.button("randomize").clicked() {
+                settings.randomize();
+            }
+            if ui.button("mirror").clicked() {
+                settings.mirror();
+            }
+            if ui.button("save").clicked() {
+                let path = settings.matrix_path.clone();
+                match fs::write(&path, settings.matrix_to_text()) {
+                    Ok(()) => info!("attraction matrix saved to {}", path.display()),
+                    Err(err) => error!("saving the matrix to {} failed: {err}", path.display()),
+                }
+            }
+            if ui.button("load").clicked() {
+                let path = settings.matrix_path.clone();
+                let loaded = fs::read_to_string(&path)
+                    .map_err(|err| err.to_string())
+                    .and_then(|text| settings.matrix_from_text(&text));
+                if let Err(err) = loaded {
+                    warn!("loading {} failed: {err}", path.display());
+                }
+            }
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matrix_round_trip() {
+        let mut settings = ParticleLife {
+            types: 3,
+            ..default()
+        };
+        settings.matrix[0][..3].copy_from_slice(&[1.0, -0.5, 0.25]);
+        settings.matrix[1][..3].copy_from_slice(&[0.0, 0.125, -1.0]);
+        settings.matrix[2][..3].copy_from_slice(&[0.75, -0.25, 0.5]);
+        let text = settings.matrix_to_text();
+        assert_eq!(
+            text,
+            "1.000 -0.500 0.250\n0.000 0.125 -1.000\n0.750 -0.250 0.500\n"
+        );
+
+        let mut loaded = ParticleLife::default();
+        assert_eq!(loaded.matrix_from_text(&text), Ok(()));
+        assert_eq!(loaded.types, 3);
+        for (loaded, row) in loaded.matrix.iter().zip(&settings.matrix).take(3) {
+            assert_eq!(loaded[..3], row[..3]);
+        }
+        assert_eq!(loaded.matrix_to_text(), text);
+    }
+
+    #[test]
+    fn matrix_values_are_clamped() {
+        let mut settings = ParticleLife::default();
+        assert_eq!(settings.matrix_from_text("\n2 -3\n\n0.5 0\n"), Ok(()));
+        assert_eq!(settings.types, 2);
+        assert_eq!(settings.matrix[0][..2], [1.0, -1.0]);
+        assert_eq!(settings.matrix[1][..2], [0.5, 0.0]);
+    }
+
+    #[test]
+    fn invalid_matrices() {
+        let mut settings = ParticleLife::default();
+        let before = settings.matrix;
+        let row = "0 ".repeat(MAX_TYPES + 1) + "\n";
+        for text in [
+            "",
+            " \n\n",
+            "1 0\n0",
+            "1 0 0\n0 1 0",
+            &row.repeat(MAX_TYPES + 1),
+            "NaN",
+            "1 0\n0 inf",
+            "1 x\n0 1",
+        ] {
+            assert!(settings.matrix_from_text(text).is_err(), "{text:?}");
+        }
+        assert_eq!(settings.types, ParticleLife::default().types);
+        assert_eq!(settings.matrix, before);
+    }
+}
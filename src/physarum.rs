@@ -0,0 +1,209 @@
+use std::{f32::consts::PI, sync::Arc};
+
+use bevy::{
+    core::{Pod, Zeroable},
+    prelude::*,
+    render::{
+        extract_resource::ExtractResource,
+        render_resource::{ShaderType, TextureFormat},
+    },
+};
+use bevy_inspector_egui::prelude::*;
+use rand::prelude::*;
+
+use crate::{
+    simulation::{BufferInit, ComputeSimulationPlugin, Pass, Simulation},
+    trail,
+};
+
+/// The slime mold agents the playground started out with.
+///
+/// Every step the agents sense the trail map ahead of them, turn towards
+/// the strongest trail and deposit into the deposits buffer, which the
+/// `resolve` pass adds onto the trail map. The `blur` pass then diffuses
+/// and evaporates the trail map.
+pub(crate) struct PhysarumPlugin;
+impl Plugin for PhysarumPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(ComputeSimulationPlugin::<Physarum>::default());
+    }
+}
+
+pub(crate) const SPECIES: u32 = 3;
+
+#[derive(ShaderType, Pod, Zeroable, Copy, Clone)]
+#[repr(C)]
+pub(crate) struct Agent {
+    pub(crate) positon: Vec2,
+    pub(crate) angle: f32,
+    pub(crate) species: u32,
+}
+
+// `InspectorOptions` are completely optional
+#[derive(Clone, Copy, Reflect, FromReflect, InspectorOptions)]
+#[reflect(InspectorOptions)]
+pub(crate) struct TrailParams {
+    #[inspector(min = 0.0, max = 1.0, speed = 0.01)]
+    pub(crate) diffusion: f32,
+    #[inspector(min = 0.0, max = 1.0, speed = 0.01)]
+    pub(crate) evaporation: f32,
+}
+
+impl Default for TrailParams {
+    fn default() -> Self {
+        TrailParams {
+            diffusion: 0.2,
+            evaporation: 2.4,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Reflect, FromReflect, InspectorOptions)]
+#[reflect(InspectorOptions)]
+pub(crate) struct SensorParams {
+    pub(crate) sensor_size: i32,
+    pub(crate) sensor_distance: f32,
+    pub(crate) sensor_angle_between: f32,
+}
+
+impl Default for SensorParams {
+    fn default() -> Self {
+        Self {
+            sensor_size: 2,
+            sensor_distance: 12.0,
+            sensor_angle_between: 0.7,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Reflect, FromReflect, InspectorOptions)]
+#[reflect(InspectorOptions)]
+pub(crate) struct AgentParams {
+    pub(crate) turn_speed: f32,
+    pub(crate) move_speed: f32,
+}
+
+impl Default for AgentParams {
+    fn default() -> Self {
+        Self {
+            turn_speed: 70.0,
+            move_speed: 55.0,
+        }
+    }
+}
+
+/// Color the agents deposit, values match `deposit_color` in `physarum.wgsl`.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Reflect, FromReflect)]
+pub(crate) enum DepositMode {
+    #[default]
+    White = 0,
+    /// The heading angle on a hue wheel.
+    Heading = 1,
+    /// One hue per species.
+    Species = 2,
+}
+
+#[derive(Clone, Copy, Reflect, FromReflect, InspectorOptions)]
+#[reflect(InspectorOptions)]
+pub(crate) struct DepositParams {
+    pub(crate) mode: DepositMode,
+    /// Added to the trail every step, deposits add up until saturated.
+    #[inspector(min = 0.0, max = 1.0, speed = 0.01)]
+    pub(crate) strength: f32,
+}
+
+impl Default for DepositParams {
+    fn default() -> Self {
+        Self {
+            mode: DepositMode::White,
+            strength: 1.0,
+        }
+    }
+}
+
+#[derive(Resource, ExtractResource, Clone, Default, Reflect, InspectorOptions)]
+#[reflect(Resource, InspectorOptions)]
+pub(crate) struct Physarum {
+    pub(crate) trail: TrailParams,
+    pub(crate) sensor: SensorParams,
+    pub(crate) agent: AgentParams,
+    pub(crate) deposit: DepositParams,
+    /// The agents buffer starts from these, they are spawned anew on every
+    /// reset and replaced when a snapshot is loaded.
+    #[reflect(ignore)]
+    pub(crate) agents: Arc<Vec<Agent>>,
+}
+
+#[derive(ShaderType)]
+pub(crate) struct PhysarumParams {
+    diffusion: f32,
+    evaporation: f32,
+    sensor_size: i32,
+    sensor_distance: f32,
+    sensor_angle_between: f32,
+    turn_speed: f32,
+    move_speed: f32,
+    deposit_mode: u32,
+    deposit_strength: f32,
+}
+
+impl Simulation for Physarum {
+    const NAME: &'static str = "physarum";
+    const SHADER: &'static str = "shaders/physarum.wgsl";
+    const ENTRY_POINTS: &'static [&'static str] = &["update", "resolve", "blur"];
+    const STATE_FORMAT: TextureFormat = TextureFormat::Rgba8Unorm;
+    const BUFFERS: &'static [&'static str] = &["agents", "deposits"];
+
+    type Params = PhysarumParams;
+
+    fn params(&self) -> PhysarumParams {
+        PhysarumParams {
+            diffusion: self.trail.diffusion,
+            evaporation: self.trail.evaporation,
+            sensor_size: self.sensor.sensor_size,
+            sensor_distance: self.sensor.sensor_distance,
+            sensor_angle_between: self.sensor.sensor_angle_between,
+            turn_speed: self.agent.turn_speed,
+            move_speed: self.agent.move_speed,
+            deposit_mode: self.deposit.mode as u32,
+            deposit_strength: self.deposit.strength,
+        }
+    }
+
+    fn reload(&mut self, seed: u32) {
+        self.agents = Arc::new(spawn_agents(seed));
+    }
+
+    /// The agents and their deposits.
+    fn buffers(&self, size: UVec2) -> Vec<BufferInit> {
+        vec![
+            BufferInit::Data(bytemuck::cast_slice(self.agents.as_slice()).to_vec()),
+            trail::deposit_buffer(size),
+        ]
+    }
+
+    fn passes(&self) -> Vec<Pass> {
+        vec![
+            Pass::items("update", self.agents.len() as u32),
+            Pass::pixels("resolve").swap(),
+            Pass::pixels("blur").swap(),
+        ]
+    }
+}
+
+fn spawn_agents(seed: u32) -> Vec<Agent> {
+    let x_size = 1000;
+    let y_size = 1000;
+    let mut agents = Vec::with_capacity(x_size * y_size);
+    let mut rng = StdRng::seed_from_u64(seed as u64);
+    for x in 0..x_size {
+        for y in 0..y_size {
+            agents.push(Agent {
+                angle: rng.gen_range(0.0..PI * 2.0),
+                positon: Vec2::new(x as f32, y as f32),
+                species: rng.gen_range(0..SPECIES),
+            });
+        }
+    }
+    agents
+}
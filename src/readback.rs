@@ -0,0 +1,312 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssets,
+        render_resource::{
+            Buffer, BufferDescriptor, BufferUsages, CommandEncoderDescriptor, Extent3d,
+            ImageCopyBuffer, ImageDataLayout, MapMode,
+        },
+        renderer::{render_system, RenderDevice, RenderQueue},
+        Extract, RenderApp, RenderSet,
+    },
+};
+
+/// Copies textures and buffers back from the GPU.
+///
+/// Requests are made from the main world with [`Readback::request`] and are
+/// served after the render graph ran in the same frame, so the data matches
+/// the simulation state of the frame the request was made in. A request
+/// whose source does not show up or whose copy is not mapped within
+/// [`MAX_WAIT_FRAMES`] fails.
+pub(crate) struct ReadbackPlugin;
+impl Plugin for ReadbackPlugin {
+    fn build(&self, app: &mut App) {
+        let readback = Readback::default();
+        app.insert_resource(readback.clone());
+
+        let render_app = app.sub_app_mut(RenderApp);
+        render_app
+            .insert_resource(readback)
+            .init_resource::<PendingReadbacks>()
+            .init_resource::<ReadbackBuffers>()
+            .add_system(extract_requests.in_schedule(ExtractSchedule))
+            .add_system(
+                copy_readbacks
+                    .in_set(RenderSet::Render)
+                    .after(render_system),
+            )
+            .add_system(map_readbacks.in_set(RenderSet::Cleanup));
+    }
+}
+
+/// Frames a request waits for its image to be uploaded or its buffer to
+/// be registered, and then again for its copy to be mapped.
+const MAX_WAIT_FRAMES: u32 = 120;
+
+#[derive(Clone, Debug)]
+pub(crate) enum ReadbackSource {
+    Image(Handle<Image>),
+    /// A buffer registered in [`ReadbackBuffers`].
+    Buffer(&'static str),
+}
+
+/// Render world buffers that can be read back by name.
+#[derive(Resource, Default)]
+pub(crate) struct ReadbackBuffers(pub(crate) HashMap<&'static str, Buffer>);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct ReadbackId(u64);
+
+/// The source of a readback never became available, or its copy could
+/// not be mapped.
+#[derive(Debug)]
+pub(crate) struct ReadbackFailed;
+
+#[derive(Default)]
+struct ReadbackQueue {
+    next_id: u64,
+    requested: Vec<Request>,
+    finished: HashMap<ReadbackId, Result<Vec<u8>, ReadbackFailed>>,
+    /// Cancelled while the render world had them, dropped there.
+    cancelled: HashSet<ReadbackId>,
+}
+
+struct Request {
+    id: ReadbackId,
+    source: ReadbackSource,
+    waited: u32,
+}
+
+/// Shared between the main and the render world.
+#[derive(Resource, Clone, Default)]
+pub(crate) struct Readback(Arc<Mutex<ReadbackQueue>>);
+
+impl Readback {
+    pub(crate) fn request(&self, source: ReadbackSource) -> ReadbackId {
+        let mut queue = self.0.lock().unwrap();
+        let id = ReadbackId(queue.next_id);
+        queue.next_id += 1;
+        queue.requested.push(Request {
+            id,
+            source,
+            waited: 0,
+        });
+        id
+    }
+
+    /// Returns the tightly packed bytes of a finished readback, every
+    /// request has to be taken or cancelled.
+    pub(crate) fn take(&self, id: ReadbackId) -> Option<Result<Vec<u8>, ReadbackFailed>> {
+        self.0.lock().unwrap().finished.remove(&id)
+    }
+
+    /// Drops a request that is no longer needed, wherever it is.
+    pub(crate) fn cancel(&self, id: ReadbackId) {
+        let mut queue = self.0.lock().unwrap();
+        let requested = queue.requested.len();
+        queue.requested.retain(|request| request.id != id);
+        if queue.requested.len() == requested && queue.finished.remove(&id).is_none() {
+            queue.cancelled.insert(id);
+        }
+    }
+}
+
+struct PendingReadback {
+    id: ReadbackId,
+    buffer: Buffer,
+    /// Bytes per row actually used, for textures the buffer rows are padded.
+    row_bytes: usize,
+    padded_row_bytes: usize,
+    mapped: Arc<AtomicBool>,
+    waited: u32,
+}
+
+/// Unmapped staging buffers kept for later readbacks of the same size.
+const MAX_FREE_BUFFERS: usize = 8;
+
+#[derive(Resource, Default)]
+struct PendingReadbacks {
+    requested: Vec<Request>,
+    copying: Vec<PendingReadback>,
+    /// Staging buffers of finished readbacks, reused by repeated
+    /// readbacks like the metrics of every frame.
+    free: Vec<Buffer>,
+}
+
+impl PendingReadbacks {
+    fn staging_buffer(&mut self, render_device: &RenderDevice, size: u64) -> Buffer {
+        if let Some(i) = self.free.iter().position(|buffer| buffer.size() == size) {
+            return self.free.swap_remove(i);
+        }
+        render_device.create_buffer(&BufferDescriptor {
+            label: Some("readback_buffer"),
+            size,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn recycle(&mut self, buffer: Buffer) {
+        buffer.unmap();
+        if self.free.len() == MAX_FREE_BUFFERS {
+            self.free.remove(0);
+        }
+        self.free.push(buffer);
+    }
+}
+
+fn extract_requests(readback: Extract<Res<Readback>>, mut pending: ResMut<PendingReadbacks>) {
+    let mut queue = readback.0.lock().unwrap();
+    let queue = &mut *queue;
+    pending.requested.append(&mut queue.requested);
+    pending
+        .requested
+        .retain(|request| !queue.cancelled.remove(&request.id));
+
+    // requests still waiting for their source fail after a while
+    pending.requested.retain_mut(|request| {
+        request.waited += 1;
+        if request.waited <= MAX_WAIT_FRAMES {
+            return true;
+        }
+        warn!("readback of {:?} failed, the source is missing", request.source);
+        queue.finished.insert(request.id, Err(ReadbackFailed));
+        false
+    });
+}
+
+fn copy_readbacks(
+    mut pending: ResMut<PendingReadbacks>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    gpu_images: Res<RenderAssets<Image>>,
+    buffers: Res<ReadbackBuffers>,
+) {
+    if pending.requested.is_empty() {
+        return;
+    }
+    let mut encoder = render_device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("readback_encoder"),
+    });
+    let requested = std::mem::take(&mut pending.requested);
+    let mut copying = Vec::new();
+    for request in requested {
+        let id = request.id;
+        let (buffer, row_bytes, padded_row_bytes) = match &request.source {
+            ReadbackSource::Image(handle) => {
+                let Some(image) = gpu_images.get(handle) else {
+                    // not uploaded yet, try again next frame
+                    pending.requested.push(request);
+                    continue;
+                };
+                let (w, h) = (image.size.x as u32, image.size.y as u32);
+                let block_size = image.texture_format.describe().block_size as usize;
+                let row_bytes = w as usize * block_size;
+                let padded_row_bytes = RenderDevice::align_copy_bytes_per_row(row_bytes);
+                let buffer = pending
+                    .staging_buffer(&render_device, (padded_row_bytes * h as usize) as u64);
+                encoder.copy_texture_to_buffer(
+                    image.texture.as_image_copy(),
+                    ImageCopyBuffer {
+                        buffer: &buffer,
+                        layout: ImageDataLayout {
+                            offset: 0,
+                            bytes_per_row: Some(
+                                std::num::NonZeroU32::new(padded_row_bytes as u32).unwrap(),
+                            ),
+                            rows_per_image: None,
+                        },
+                    },
+                    Extent3d {
+                        width: w,
+                        height: h,
+                        depth_or_array_layers: 1,
+                    },
+                );
+                (buffer, row_bytes, padded_row_bytes)
+            }
+            ReadbackSource::Buffer(name) => {
+                let Some(source_buffer) = buffers.0.get(name) else {
+                    pending.requested.push(request);
+                    continue;
+                };
+                let size = source_buffer.size();
+                let buffer = pending.staging_buffer(&render_device, size);
+                encoder.copy_buffer_to_buffer(source_buffer, 0, &buffer, 0, size);
+                (buffer, size as usize, size as usize)
+            }
+        };
+        copying.push(PendingReadback {
+            id,
+            buffer,
+            row_bytes,
+            padded_row_bytes,
+            mapped: Arc::new(AtomicBool::new(false)),
+            waited: 0,
+        });
+    }
+    render_queue.submit([encoder.finish()]);
+
+    for readback in copying.iter() {
+        let mapped = readback.mapped.clone();
+        readback
+            .buffer
+            .slice(..)
+            .map_async(MapMode::Read, move |result| {
+                // a failed mapping, like of a lost device, fails the readback once it waited too long
+                if result.is_ok() {
+                    mapped.store(true, Ordering::Release);
+                }
+            });
+    }
+    pending.copying.append(&mut copying);
+}
+
+fn map_readbacks(
+    mut pending: ResMut<PendingReadbacks>,
+    render_device: Res<RenderDevice>,
+    readback: Res<Readback>,
+) {
+    if pending.copying.is_empty() {
+        return;
+    }
+    render_device.poll(wgpu::Maintain::Poll);
+
+    let mut queue = readback.0.lock().unwrap();
+    let (mapped, copying) = std::mem::take(&mut pending.copying)
+        .into_iter()
+        .partition(|readback| readback.mapped.load(Ordering::Acquire));
+    pending.copying = copying;
+    pending.copying.retain_mut(|readback| {
+        readback.waited += 1;
+        if readback.waited <= MAX_WAIT_FRAMES {
+            return true;
+        }
+        warn!("readback failed, its copy was never mapped");
+        if !queue.cancelled.remove(&readback.id) {
+            queue.finished.insert(readback.id, Err(ReadbackFailed));
+        }
+        false
+    });
+    for readback in mapped {
+        if !queue.cancelled.remove(&readback.id) {
+            let data = readback.buffer.slice(..).get_mapped_range();
+            let bytes = data
+                .chunks(readback.padded_row_bytes)
+                .flat_map(|row| &row[..readback.row_bytes])
+                .copied()
+                .collect();
+            drop(data);
+            queue.finished.insert(readback.id, Ok(bytes));
+        }
+        pending.recycle(readback.buffer);
+    }
+}
@@ -0,0 +1,130 @@
+use std::{collections::VecDeque, path::PathBuf};
+
+use bevy::{prelude::*, render::render_resource::TextureFormat, tasks::IoTaskPool};
+
+use crate::{
+    image::{save_png, MainImageMarker},
+    readback::{Readback, ReadbackId, ReadbackSource},
+};
+
+/// Records whatever the main sprite shows as numbered png frames.
+///
+/// Works for every simulation, `F12` starts and stops a recording into
+/// a new directory below [`RecordingSettings::directory`].
+pub(crate) struct RecordingPlugin;
+impl Plugin for RecordingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RecordingSettings>()
+            .init_resource::<Recording>()
+            .add_systems((toggle_recording, record_frame, save_frames).chain());
+    }
+}
+
+#[derive(Resource, Clone)]
+pub(crate) struct RecordingSettings {
+    pub(crate) directory: PathBuf,
+    /// Records every nth frame.
+    pub(crate) every: u32,
+}
+
+impl Default for RecordingSettings {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from("recordings"),
+            every: 1,
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+struct Recording {
+    /// Directory of the running recording.
+    directory: Option<PathBuf>,
+    frame: u32,
+    recorded: u32,
+    pending: VecDeque<(u32, UVec2, ReadbackId)>,
+}
+
+fn toggle_recording(
+    keys: Res<Input<KeyCode>>,
+    settings: Res<RecordingSettings>,
+    mut recording: ResMut<Recording>,
+) {
+    if !keys.just_pressed(KeyCode::F12) {
+        return;
+    }
+    if let Some(directory) = recording.directory.take() {
+        info!(
+            "recorded {} frames to {}",
+            recording.recorded,
+            directory.display()
+        );
+        return;
+    }
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let directory = settings.directory.join(format!("recording-{timestamp}"));
+    if let Err(err) = std::fs::create_dir_all(&directory) {
+        error!("creating {} failed: {err}", directory.display());
+        return;
+    }
+    info!("recording to {}", directory.display());
+    *recording = Recording {
+        directory: Some(directory),
+        ..default()
+    };
+}
+
+fn record_frame(
+    settings: Res<RecordingSettings>,
+    mut recording: ResMut<Recording>,
+    readback: Res<Readback>,
+    images: Res<Assets<Image>>,
+    sprites: Query<&Handle<Image>, With<MainImageMarker>>,
+) {
+    if recording.directory.is_none() {
+        return;
+    }
+    let frame = recording.frame;
+    recording.frame += 1;
+    if !frame.is_multiple_of(settings.every.max(1)) {
+        return;
+    }
+    let Ok(handle) = sprites.get_single() else {return;};
+    let Some(image) = images.get(handle) else {return;};
+    if image.texture_descriptor.format != TextureFormat::Rgba8Unorm {
+        return;
+    }
+    let id = readback.request(ReadbackSource::Image(handle.clone()));
+    let index = recording.recorded;
+    recording.recorded += 1;
+    recording
+        .pending
+        .push_back((index, image.size().as_uvec2(), id));
+}
+
+fn save_frames(mut recording: ResMut<Recording>, readback: Res<Readback>) {
+    // readbacks finish in order, frames still pending when a recording
+    // stops are dropped
+    let Some(directory) = recording.directory.clone() else {
+        for (_, _, id) in recording.pending.drain(..) {
+            readback.cancel(id);
+        }
+        return;
+    };
+    while let Some(&(index, size, id)) = recording.pending.front() {
+        let Some(result) = readback.take(id) else {break;};
+        recording.pending.pop_front();
+        let Ok(bytes) = result else {continue;};
+        let path = directory.join(format!("frame-{index:05}.png"));
+        IoTaskPool::get()
+            .spawn(async move {
+                if let Err(err) = save_png(&path, size, bytes) {
+                    error!("saving {} failed: {err}", path.display());
+                }
+            })
+            .detach();
+    }
+}
@@ -0,0 +1,158 @@
+use bevy::{
+    prelude::*,
+    render::{
+        extract_resource::ExtractResource,
+        render_resource::{ShaderType, TextureFormat},
+    },
+};
+use bevy_inspector_egui::{
+    bevy_egui::{egui, EguiContexts},
+    prelude::*,
+};
+
+use crate::simulation::{simulation_active, ComputeSimulationPlugin, Pass, Simulation};
+
+/// Falling sand on an integer state image.
+///
+/// Every step moves the cells in 2x2 blocks of a Margolus neighbourhood,
+/// once on even and once on odd block offsets, so every invocation owns
+/// the four cells it writes. The left mouse button paints the material
+/// picked in the palette, the right one erases.
+pub(crate) struct SandPlugin;
+impl Plugin for SandPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(ComputeSimulationPlugin::<FallingSand>::default())
+            .add_system(material_palette.run_if(simulation_active(FallingSand::NAME)));
+    }
+}
+
+/// Values match the material ids in `sand.wgsl`.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Reflect, FromReflect)]
+enum Material {
+    Empty = 0,
+    #[default]
+    Sand = 1,
+    Water = 2,
+    Stone = 3,
+    /// Burns out into smoke and is put out by water.
+    Fire = 4,
+    /// Rises and fades away.
+    Smoke = 5,
+}
+
+impl Material {
+    const ALL: [Material; 6] = [
+        Material::Sand,
+        Material::Water,
+        Material::Stone,
+        Material::Fire,
+        Material::Smoke,
+        Material::Empty,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Material::Empty => "eraser",
+            Material::Sand => "sand",
+            Material::Water => "water",
+            Material::Stone => "stone",
+            Material::Fire => "fire",
+            Material::Smoke => "smoke",
+        }
+    }
+
+    /// Matches the colors of the display pass.
+    fn color(self) -> egui::Color32 {
+        match self {
+            Material::Empty => egui::Color32::from_rgb(20, 20, 26),
+            Material::Sand => egui::Color32::from_rgb(219, 184, 112),
+            Material::Water => egui::Color32::from_rgb(51, 107, 219),
+            Material::Stone => egui::Color32::from_rgb(120, 120, 128),
+            Material::Fire => egui::Color32::from_rgb(255, 115, 26),
+            Material::Smoke => egui::Color32::from_rgb(150, 150, 150),
+        }
+    }
+}
+
+#[derive(Resource, ExtractResource, Clone, Reflect, InspectorOptions)]
+#[reflect(Resource, InspectorOptions)]
+pub(crate) struct FallingSand {
+    /// Painted with the left mouse button.
+    material: Material,
+    #[inspector(min = 1.0, max = 100.0)]
+    brush_radius: f32,
+    /// Fraction of the cells under the brush that are painted every step.
+    #[inspector(min = 0.0, max = 1.0, speed = 0.01)]
+    brush_density: f32,
+    /// Chance of a fire cell to burn down a bit per block pass.
+    #[inspector(min = 0.0, max = 1.0, speed = 0.01)]
+    burn_rate: f32,
+}
+
+impl Default for FallingSand {
+    fn default() -> Self {
+        Self {
+            material: Material::Sand,
+            brush_radius: 10.0,
+            brush_density: 0.3,
+            burn_rate: 0.3,
+        }
+    }
+}
+
+#[derive(ShaderType)]
+pub(crate) struct FallingSandParams {
+    material: u32,
+    brush_radius: f32,
+    brush_density: f32,
+    burn_rate: f32,
+}
+
+impl Simulation for FallingSand {
+    const NAME: &'static str = "sand";
+    const SHADER: &'static str = "shaders/sand.wgsl";
+    const ENTRY_POINTS: &'static [&'static str] = &["clear", "paint", "blocks_even", "blocks_odd"];
+    const STATE_FORMAT: TextureFormat = TextureFormat::R32Uint;
+    const STEPS_PER_FRAME: u32 = 2;
+
+    type Params = FallingSandParams;
+
+    fn params(&self) -> FallingSandParams {
+        FallingSandParams {
+            material: self.material as u32,
+            brush_radius: self.brush_radius,
+            brush_density: self.brush_density,
+            burn_rate: self.burn_rate,
+        }
+    }
+
+    fn init_passes(&self) -> Vec<Pass> {
+        vec![Pass::pixels("clear").swap()]
+    }
+
+    /// The block passes dispatch a thread per pixel, only the first
+    /// quarter has a block to update.
+    fn passes(&self) -> Vec<Pass> {
+        vec![
+            Pass::pixels("paint").swap(),
+            Pass::pixels("blocks_even").swap(),
+            Pass::pixels("blocks_odd").swap(),
+        ]
+    }
+}
+
+fn material_palette(mut contexts: EguiContexts, mut settings: ResMut<FallingSand>) {
+    egui::Window::new("Materials").show(contexts.ctx_mut(), |ui| {
+        let mut material = settings.material;
+        ui.horizontal_wrapped(|ui| {
+            for option in Material::ALL {
+                let text = egui::RichText::new(option.name()).color(option.color());
+                ui.selectable_value(&mut material, option, text);
+            }
+        });
+        // only write back a new pick, so change detection isn't triggered every frame
+        if material != settings.material {
+            settings.material = material;
+        }
+    });
+}
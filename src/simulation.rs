@@ -0,0 +1,1199 @@
+use std::{
+    borrow::Cow,
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+};
+
+use bevy::{
+    prelude::*,
+    reflect::GetTypeRegistration,
+    render::{
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+        render_asset::RenderAssets,
+        render_graph::{Node, NodeRunError, RenderGraph, RenderGraphContext},
+        render_resource::{
+            encase::{internal::WriteInto, UniformBuffer},
+            BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+            BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, Buffer,
+            BufferBindingType, BufferDescriptor, BufferInitDescriptor, BufferUsages,
+            CachedComputePipelineId, CachedPipelineState, ComputePass, ComputePassDescriptor,
+            ComputePipelineDescriptor, Extent3d, PipelineCache, ShaderStages, ShaderType,
+            StorageTextureAccess, Texture, TextureDescriptor, TextureDimension, TextureFormat,
+            TextureUsages, TextureView, TextureViewDescriptor, TextureViewDimension,
+        },
+        renderer::{RenderContext, RenderDevice},
+        texture::GpuImage,
+        RenderApp, RenderSet,
+    },
+    window::PrimaryWindow,
+};
+use bevy_inspector_egui::{
+    bevy_egui::{egui, EguiContexts, EguiPlugin},
+    quick::ResourceInspectorPlugin,
+};
+
+use crate::{
+    image::{create_display_image, ComputePlaygroundImages, MainImageMarker},
+    physarum::Physarum,
+    readback::ReadbackBuffers,
+    Backend,
+};
+
+/// The simulation menu, pause/step/reset controls and the pointer shared
+/// by all simulations.
+///
+/// Simulations implement [`Simulation`] and are added with a
+/// [`ComputeSimulationPlugin`], the slime mold agents of [`Physarum`] run
+/// by default. Switching recreates the ping-pong images of
+/// [`ComputePlaygroundImages`] in the format of the new simulation.
+pub(crate) struct SimulationPlugin;
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugin(EguiPlugin);
+        }
+        app.init_resource::<SimulationRegistry>()
+            .init_resource::<ShaderModules>()
+            .init_resource::<ActiveSimulation>()
+            .init_resource::<SimulationControl>()
+            .insert_resource(SimulationFrame {
+                seed: rand::random(),
+                ..default()
+            })
+            .init_resource::<SimulationDisplay>()
+            .add_plugin(ExtractResourcePlugin::<ActiveSimulation>::default())
+            .add_plugin(ExtractResourcePlugin::<SimulationFrame>::default())
+            .add_plugin(ExtractResourcePlugin::<SimulationDisplay>::default())
+            .add_system(start_frame.in_base_set(CoreSet::First))
+            .add_systems((
+                simulation_menu,
+                control_hotkeys,
+                update_pointer,
+                update_display,
+                switch_simulation,
+                show_simulation.after(switch_simulation),
+                clear_on_reset,
+            ));
+    }
+}
+
+/// Shaders imported by the simulations, by their `#define_import_path`.
+const SHADER_MODULES: &[&str] = &[
+    "shaders/agents.wgsl",
+    "shaders/common.wgsl",
+    "shaders/grid.wgsl",
+    "shaders/trail.wgsl",
+];
+
+/// Keeps the [`SHADER_MODULES`] loaded, imports only resolve while they are.
+#[derive(Resource)]
+struct ShaderModules {
+    _handles: Vec<Handle<Shader>>,
+}
+
+impl FromWorld for ShaderModules {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.resource::<AssetServer>();
+        ShaderModules {
+            _handles: SHADER_MODULES
+                .iter()
+                .map(|&path| asset_server.load(path))
+                .collect(),
+        }
+    }
+}
+
+/// Registered simulations.
+#[derive(Resource, Default)]
+pub(crate) struct SimulationRegistry(Vec<SimulationInfo>);
+
+struct SimulationInfo {
+    name: &'static str,
+    /// Format of the state images.
+    format: TextureFormat,
+    steps_per_frame: u32,
+}
+
+impl SimulationRegistry {
+    fn get(&self, name: &str) -> Option<&SimulationInfo> {
+        self.0.iter().find(|info| info.name == name)
+    }
+}
+
+/// The simulation that currently runs, by name. Inserting it before
+/// [`ComputePlaygroundPlugin`](crate::ComputePlaygroundPlugin) is added
+/// selects the simulation on startup.
+#[derive(Resource, ExtractResource, Clone, PartialEq, Eq, Debug)]
+pub struct ActiveSimulation(pub String);
+
+impl Default for ActiveSimulation {
+    fn default() -> Self {
+        ActiveSimulation(Physarum::NAME.to_owned())
+    }
+}
+
+/// Run condition for systems that only apply to one simulation, in both
+/// the main and the render world.
+pub(crate) fn simulation_active(
+    name: &'static str,
+) -> impl FnMut(Res<ActiveSimulation>) -> bool + Clone {
+    move |active: Res<ActiveSimulation>| active.0 == name
+}
+
+/// Set from the menu and the hotkeys, applied at the start of the next frame.
+#[derive(Resource)]
+pub(crate) struct SimulationControl {
+    pub(crate) paused: bool,
+    /// Runs a single step while paused.
+    pub(crate) step_once: bool,
+    pub(crate) steps_per_frame: u32,
+    /// Restarts the active simulation from its initial state.
+    pub(crate) reset: bool,
+    /// Continues from a loaded snapshot at this seed and step. The buffers
+    /// are recreated from the simulation, but the state images are kept
+    /// and the init passes are skipped.
+    pub(crate) restore: Option<(u32, u32)>,
+}
+
+impl Default for SimulationControl {
+    fn default() -> Self {
+        Self {
+            paused: false,
+            step_once: false,
+            steps_per_frame: 1,
+            reset: false,
+            restore: None,
+        }
+    }
+}
+
+/// The pointer over the simulation image.
+#[derive(Clone, Copy, Default, Debug)]
+pub(crate) struct SimulationPointer {
+    /// In image pixels from the top left, `None` outside of the window.
+    pub(crate) position: Option<Vec2>,
+    /// Movement since the last frame, in image pixels.
+    pub(crate) delta: Vec2,
+    /// Buttons pressed outside of the UI, 1: left, 2: right, 4: middle.
+    pub(crate) buttons: u32,
+}
+
+/// What the simulation does this frame, fixed at its start.
+#[derive(Resource, ExtractResource, Clone, Default)]
+pub(crate) struct SimulationFrame {
+    pub(crate) size: UVec2,
+    /// Steps to run this frame, 0 while paused.
+    pub(crate) steps: u32,
+    /// Index of the first step of this frame, counted from the last reset.
+    pub(crate) step: u32,
+    /// Changes on every reset.
+    pub(crate) seed: u32,
+    /// A reset was requested, simulations start over from their initial
+    /// state on cleared state images.
+    pub(crate) reset: bool,
+    /// The state images were recreated at a new size and are cleared.
+    pub(crate) resized: bool,
+    /// A snapshot was loaded into the state images, see
+    /// [`SimulationControl::restore`].
+    pub(crate) restored: bool,
+    pub(crate) delta_time: f32,
+    pub(crate) pointer: SimulationPointer,
+}
+
+fn start_frame(
+    mut control: ResMut<SimulationControl>,
+    mut frame: ResMut<SimulationFrame>,
+    handles: Res<ComputePlaygroundImages>,
+    images: Res<Assets<Image>>,
+    time: Res<Time>,
+) {
+    frame.step = frame.step.wrapping_add(frame.steps);
+    frame.steps = if control.paused {
+        control.step_once as u32
+    } else {
+        control.steps_per_frame.max(1)
+    };
+    control.step_once = false;
+
+    let size = images
+        .get(&handles.main_textures.0)
+        .map_or(UVec2::ZERO, |image| image.size().as_uvec2());
+    frame.resized = frame.size != size;
+    frame.size = size;
+    frame.reset = std::mem::take(&mut control.reset);
+    if frame.reset {
+        frame.step = 0;
+        frame.seed = rand::random();
+    }
+    frame.restored = false;
+    if let Some((seed, step)) = control.restore.take() {
+        frame.restored = true;
+        frame.seed = seed;
+        frame.step = step;
+    }
+    frame.delta_time = time.delta_seconds();
+}
+
+fn simulation_menu(
+    mut contexts: EguiContexts,
+    registry: Res<SimulationRegistry>,
+    mut active: ResMut<ActiveSimulation>,
+    mut control: ResMut<SimulationControl>,
+) {
+    egui::Window::new("Simulation").show(contexts.ctx_mut(), |ui| {
+        let mut selected = active.0.clone();
+        for info in registry.0.iter() {
+            ui.radio_value(&mut selected, info.name.to_owned(), info.name);
+        }
+        if selected != active.0 {
+            active.0 = selected;
+        }
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut control.paused, "paused (space)");
+            if ui.button("step (.)").clicked() {
+                control.paused = true;
+                control.step_once = true;
+            }
+            if ui.button("reset (R)").clicked() {
+                control.reset = true;
+            }
+        });
+        ui.add(egui::Slider::new(&mut control.steps_per_frame, 1..=64).text("steps per frame"));
+    });
+}
+
+fn control_hotkeys(
+    keys: Res<Input<KeyCode>>,
+    mut contexts: EguiContexts,
+    mut control: ResMut<SimulationControl>,
+) {
+    if contexts.ctx_mut().wants_keyboard_input() {
+        return;
+    }
+    if keys.just_pressed(KeyCode::Space) {
+        control.paused = !control.paused;
+    }
+    if keys.just_pressed(KeyCode::Period) {
+        control.paused = true;
+        control.step_once = true;
+    }
+    if keys.just_pressed(KeyCode::R) {
+        control.reset = true;
+    }
+}
+
+fn update_pointer(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    buttons: Res<Input<MouseButton>>,
+    mut contexts: EguiContexts,
+    mut frame: ResMut<SimulationFrame>,
+) {
+    let Ok(window) = windows.get_single() else {return;};
+    // the image is centered in the window at its own size, flipped in y
+    let position = window
+        .cursor_position()
+        .map(|cursor| Vec2::new(cursor.x, window.height() - cursor.y));
+    let over_ui = {
+        let ctx = contexts.ctx_mut();
+        ctx.is_pointer_over_area() || ctx.wants_pointer_input()
+    };
+    let pointer = &mut frame.pointer;
+    pointer.delta = match (position, pointer.position) {
+        (Some(position), Some(previous)) => position - previous,
+        _ => Vec2::ZERO,
+    };
+    pointer.position = position;
+    pointer.buttons = 0;
+    if !over_ui {
+        for (button, bit) in [
+            (MouseButton::Left, 1),
+            (MouseButton::Right, 2),
+            (MouseButton::Middle, 4),
+        ] {
+            if buttons.pressed(button) {
+                pointer.buttons |= bit;
+            }
+        }
+    }
+}
+
+/// Written by the `display` pass of the active [`Simulation`] and shown
+/// instead of the raw state.
+#[derive(Resource, ExtractResource, Clone)]
+pub(crate) struct SimulationDisplay(pub(crate) Handle<Image>);
+
+impl FromWorld for SimulationDisplay {
+    fn from_world(world: &mut World) -> Self {
+        let backend = *world.resource::<Backend>();
+        let image = world
+            .resource_mut::<Assets<Image>>()
+            .add(create_display_image(UVec2::ONE, backend));
+        SimulationDisplay(image)
+    }
+}
+
+fn update_display(
+    handle: Res<SimulationDisplay>,
+    frame: Res<SimulationFrame>,
+    backend: Res<Backend>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let size = frame.size;
+    let Some(image) = images.get(&handle.0) else {return;};
+    if size.x > 0 && size.y > 0 && image.size().as_uvec2() != size {
+        images.set_untracked(&handle.0, create_display_image(size, *backend));
+    }
+}
+
+/// Recreates the state images in the format of the new simulation.
+pub(crate) fn switch_simulation(
+    mut active: ResMut<ActiveSimulation>,
+    registry: Res<SimulationRegistry>,
+    backend: Res<Backend>,
+    mut handles: ResMut<ComputePlaygroundImages>,
+    mut images: ResMut<Assets<Image>>,
+    mut control: ResMut<SimulationControl>,
+) {
+    if !active.is_changed() {
+        return;
+    }
+    let Some(info) = registry.get(&active.0) else {
+        let names: Vec<_> = registry.0.iter().map(|info| info.name).collect();
+        warn!("unknown simulation {}, registered are {names:?}", active.0);
+        active.0 = Physarum::NAME.to_owned();
+        return;
+    };
+    if handles.format != info.format {
+        let size = images
+            .get(&handles.main_textures.0)
+            .map_or(UVec2::ONE, |image| image.size().as_uvec2());
+        handles.recreate(&mut images, size, info.format, *backend);
+    }
+    control.steps_per_frame = info.steps_per_frame;
+    // the initial state is set up without a reset
+    if !active.is_added() {
+        control.reset = true;
+    }
+    info!("running the {} simulation", active.0);
+}
+
+/// Recreates the state images on a reset, the init passes start from
+/// cleared images.
+fn clear_on_reset(
+    frame: Res<SimulationFrame>,
+    backend: Res<Backend>,
+    mut handles: ResMut<ComputePlaygroundImages>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    if !frame.reset {
+        return;
+    }
+    let format = handles.format;
+    handles.recreate(&mut images, frame.size.max(UVec2::ONE), format, *backend);
+}
+
+/// Shows the display image of the active simulation.
+pub(crate) fn show_simulation(
+    active: Res<ActiveSimulation>,
+    display: Res<SimulationDisplay>,
+    mut sprites: Query<&mut Handle<Image>, With<MainImageMarker>>,
+) {
+    if !active.is_changed() {
+        return;
+    }
+    for mut texture in sprites.iter_mut() {
+        *texture = display.0.clone();
+    }
+}
+
+/// Uniforms of every simulation step, group 0 binding 0. Shaders get
+/// `frame`, `hash` and `randomFloat` from `#import compute_playground::common`.
+///
+/// ```wgsl
+/// struct Frame {
+///     size: vec2<f32>,
+///     pointer: vec2<f32>,
+///     pointer_delta: vec2<f32>,
+///     buttons: u32,
+///     delta_time: f32,
+///     step: u32,
+///     seed: u32,
+/// }
+/// ```
+#[derive(ShaderType)]
+struct FrameParams {
+    size: Vec2,
+    /// Far outside of the image if there is no pointer.
+    pointer: Vec2,
+    pointer_delta: Vec2,
+    buttons: u32,
+    delta_time: f32,
+    step: u32,
+    seed: u32,
+}
+
+/// How many workgroups a [`Pass`] dispatches.
+#[derive(Clone, Copy, Debug)]
+pub enum Dispatch {
+    /// One thread per pixel, the entry point uses `@workgroup_size(8, 8, 1)`.
+    Pixels,
+    /// One thread per item, the entry point uses `@workgroup_size(64, 1, 1)`.
+    Items(u32),
+    Workgroups(UVec3),
+}
+
+/// One compute dispatch of a [`Simulation`].
+#[derive(Clone, Copy, Debug)]
+pub struct Pass {
+    pub entry_point: &'static str,
+    pub dispatch: Dispatch,
+    /// The pass wrote the next state, passes after it read what it wrote.
+    pub swap: bool,
+}
+
+impl Pass {
+    pub fn pixels(entry_point: &'static str) -> Self {
+        Self {
+            entry_point,
+            dispatch: Dispatch::Pixels,
+            swap: false,
+        }
+    }
+
+    pub fn items(entry_point: &'static str, count: u32) -> Self {
+        Self {
+            entry_point,
+            dispatch: Dispatch::Items(count),
+            swap: false,
+        }
+    }
+
+    pub fn workgroups(entry_point: &'static str, workgroups: UVec3) -> Self {
+        Self {
+            entry_point,
+            dispatch: Dispatch::Workgroups(workgroups),
+            swap: false,
+        }
+    }
+
+    /// Swaps the state images after this pass, and the volumes of a
+    /// simulation with a [`Simulation::VOLUME_FORMAT`].
+    pub fn swap(mut self) -> Self {
+        self.swap = true;
+        self
+    }
+}
+
+/// Initial contents of a storage buffer of a [`Simulation`].
+pub enum BufferInit {
+    /// Zeroed, with the given size in bytes.
+    Zeroed(u64),
+    Data(Vec<u8>),
+}
+
+impl BufferInit {
+    /// Largest buffer every adapter can bind, wgpu's default
+    /// `max_storage_buffer_binding_size`. Data read from files is checked
+    /// against it before it ends up in a buffer.
+    pub const MAX_SIZE: u64 = 128 << 20;
+}
+
+/// A compute simulation the playground can host.
+///
+/// The resource holds the parameters, it gets an inspector and is
+/// extracted to the render world every frame it changed. Every step runs
+/// [`Simulation::passes`] in order, then the `display` entry point writes
+/// what is shown. All entry points share the layout
+///
+/// - group 0: `Frame` (see [`FrameParams`]) at binding 0, [`Simulation::Params`] at binding 1
+/// - group 1: the next state as `texture_storage_2d<STATE_FORMAT, write>` at binding 0,
+///   the current state as `texture_2d` at binding 1 and the displayed
+///   `texture_storage_2d<rgba8unorm, write>` at binding 2
+/// - group 2: one `storage, read_write` buffer per [`Simulation::BUFFERS`] entry
+/// - group 3, only with a [`Simulation::VOLUME_FORMAT`]: the next volume as
+///   `texture_storage_3d<VOLUME_FORMAT, write>` at binding 0 and the
+///   current one as `texture_3d` at binding 1
+///
+/// Buffers are recreated and the init passes run after a reset or when the
+/// window is resized. Both state images are cleared then, to zero or to
+/// opaque black for `Rgba8Unorm`, new volumes start zeroed. After a snapshot was loaded, the buffers
+/// are recreated too but the init passes are skipped.
+pub trait Simulation:
+    Resource + ExtractResource<Source = Self> + Reflect + GetTypeRegistration + Default + Clone
+{
+    /// Shown in the menu and used by `--simulation`.
+    const NAME: &'static str;
+    const SHADER: &'static str;
+    /// Every entry point the passes use, besides `display`.
+    const ENTRY_POINTS: &'static [&'static str];
+    const STATE_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+    /// Default of the steps per frame control, set when switching to it.
+    const STEPS_PER_FRAME: u32 = 1;
+    /// Labels of the storage buffers, they can be read back by these names.
+    const BUFFERS: &'static [&'static str] = &[];
+    /// Format of a 3D state besides the 2D one, a ping-pong pair of
+    /// volumes of [`Simulation::volume_size`] voxels.
+    const VOLUME_FORMAT: Option<TextureFormat> = None;
+
+    type Params: ShaderType + WriteInto;
+
+    fn params(&self) -> Self::Params;
+
+    /// Settings only read when starting over, like counts that size the
+    /// buffers. Changing them resets the simulation. Paths of the files
+    /// [`Simulation::reload`] reads are left out, they are typed into the
+    /// inspector a key at a time and a new one is loaded on the next reset.
+    fn restart_key(&self) -> impl Hash {}
+
+    /// Runs on startup and on every reset of this simulation, before its
+    /// buffers are recreated, with the seed of the new run. Files the
+    /// initial state is read from are loaded here, so edits to them show up.
+    fn reload(&mut self, _seed: u32) {}
+
+    /// Contents of [`Simulation::BUFFERS`], in the same order.
+    fn buffers(&self, _size: UVec2) -> Vec<BufferInit> {
+        Vec::new()
+    }
+
+    /// Voxels of the volumes along each axis, read when they are recreated
+    /// together with the buffers.
+    fn volume_size(&self) -> UVec3 {
+        UVec3::ONE
+    }
+
+    /// Passes writing the initial state, run once after a reset.
+    fn init_passes(&self) -> Vec<Pass> {
+        Vec::new()
+    }
+
+    fn passes(&self) -> Vec<Pass>;
+}
+
+/// Adds a [`Simulation`] to the menu and the render graph, GPU only.
+///
+/// Tools working on the state of one simulation run as render graph nodes
+/// after the node named [`Simulation::NAME`], with the pipeline layout of
+/// [`SimulationPipeline::layouts`] and the groups bound by
+/// [`SimulationBindGroups::set_latest`].
+pub struct ComputeSimulationPlugin<S>(PhantomData<fn() -> S>);
+
+impl<S> Default for ComputeSimulationPlugin<S> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<S: Simulation> Plugin for ComputeSimulationPlugin<S> {
+    fn build(&self, app: &mut App) {
+        register_simulation::<S>(app);
+        app.add_plugin(ExtractResourcePlugin::<S>::default());
+
+        let render_app = app.sub_app_mut(RenderApp);
+        render_app
+            .init_resource::<SimulationPipeline<S>>()
+            .add_system(
+                prepare_simulation_buffers::<S>
+                    .in_set(RenderSet::Prepare)
+                    .run_if(simulation_active(S::NAME)),
+            )
+            .add_system(
+                queue_simulation_bind_groups::<S>
+                    .in_set(RenderSet::Queue)
+                    .run_if(simulation_active(S::NAME)),
+            )
+            .add_system(
+                remove_simulation_bind_groups::<S>
+                    .in_set(RenderSet::Queue)
+                    .run_if(not(simulation_active(S::NAME))),
+            );
+        let mut render_graph = render_app.world.resource_mut::<RenderGraph>();
+        render_graph.add_node(S::NAME, SimulationNode::<S>::default());
+        render_graph.add_node_edge(S::NAME, bevy::render::main_graph::node::CAMERA_DRIVER);
+    }
+}
+
+/// Adds a [`Simulation`] to the menu with its inspector, restarts and
+/// reloads. The CPU backend runs the steps itself.
+pub(crate) fn register_simulation<S: Simulation>(app: &mut App) {
+    app.init_resource::<SimulationRegistry>()
+        .init_resource::<S>()
+        .register_type::<S>()
+        .add_plugin(ResourceInspectorPlugin::<S>::default().run_if(simulation_active(S::NAME)))
+        .add_system(restart_simulation::<S>);
+    app.world
+        .resource_mut::<SimulationRegistry>()
+        .0
+        .push(SimulationInfo {
+            name: S::NAME,
+            format: S::STATE_FORMAT,
+            steps_per_frame: S::STEPS_PER_FRAME,
+        });
+}
+
+/// Resets when the [`Simulation::restart_key`] changed and reloads the
+/// simulation on startup and on its resets.
+pub(crate) fn restart_simulation<S: Simulation>(
+    mut simulation: ResMut<S>,
+    frame: Res<SimulationFrame>,
+    active: Res<ActiveSimulation>,
+    mut control: ResMut<SimulationControl>,
+    mut restart_key: Local<Option<u64>>,
+) {
+    let mut hasher = DefaultHasher::new();
+    simulation.restart_key().hash(&mut hasher);
+    let key = Some(hasher.finish());
+    let first = restart_key.is_none();
+    if !first && *restart_key != key && active.0 == S::NAME {
+        control.reset = true;
+    }
+    *restart_key = key;
+    // a restored snapshot brings its own initial state
+    if (first || (frame.reset && active.0 == S::NAME)) && !frame.restored {
+        simulation.reload(frame.seed);
+    }
+}
+
+#[derive(Resource)]
+pub(crate) struct SimulationPipeline<S> {
+    pipelines: HashMap<&'static str, CachedComputePipelineId>,
+    pub(crate) frame_bind_group_layout: BindGroupLayout,
+    pub(crate) texture_bind_group_layout: BindGroupLayout,
+    pub(crate) buffers_bind_group_layout: BindGroupLayout,
+    volume_bind_group_layout: Option<BindGroupLayout>,
+    marker: PhantomData<fn() -> S>,
+}
+
+impl<S: Simulation> SimulationPipeline<S> {
+    /// Layouts of the groups 0 to 3 the passes use.
+    pub(crate) fn layouts(&self) -> Vec<BindGroupLayout> {
+        let mut layouts = vec![
+            self.frame_bind_group_layout.clone(),
+            self.texture_bind_group_layout.clone(),
+        ];
+        // group 2 stays in place in front of the volumes, even if empty
+        if !S::BUFFERS.is_empty() || S::VOLUME_FORMAT.is_some() {
+            layouts.push(self.buffers_bind_group_layout.clone());
+        }
+        layouts.extend(self.volume_bind_group_layout.clone());
+        layouts
+    }
+}
+
+impl<S: Simulation> FromWorld for SimulationPipeline<S> {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+
+        let uniform = |binding, min_size| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: Some(min_size),
+            },
+            count: None,
+        };
+        let frame_bind_group_layout =
+            render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("SimulationFrameLayout"),
+                entries: &[
+                    uniform(0, FrameParams::min_size()),
+                    uniform(1, S::Params::min_size()),
+                ],
+            });
+
+        let storage_texture = |binding, format, view_dimension| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::StorageTexture {
+                access: StorageTextureAccess::WriteOnly,
+                format,
+                view_dimension,
+            },
+            count: None,
+        };
+        let texture = |binding, format: TextureFormat, view_dimension| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Texture {
+                sample_type: format.describe().sample_type,
+                view_dimension,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let texture_bind_group_layout =
+            render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("SimulationTextureLayout"),
+                entries: &[
+                    storage_texture(0, S::STATE_FORMAT, TextureViewDimension::D2),
+                    texture(1, S::STATE_FORMAT, TextureViewDimension::D2),
+                    storage_texture(2, TextureFormat::Rgba8Unorm, TextureViewDimension::D2),
+                ],
+            });
+        let volume_bind_group_layout = S::VOLUME_FORMAT.map(|format| {
+            render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("SimulationVolumeLayout"),
+                entries: &[
+                    storage_texture(0, format, TextureViewDimension::D3),
+                    texture(1, format, TextureViewDimension::D3),
+                ],
+            })
+        });
+
+        let buffer_entries: Vec<_> = (0..S::BUFFERS.len() as u32)
+            .map(|binding| BindGroupLayoutEntry {
+                binding,
+                visibility: ShaderStages::COMPUTE,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Storage { read_only: false },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            })
+            .collect();
+        let buffers_bind_group_layout =
+            render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("SimulationBuffersLayout"),
+                entries: &buffer_entries,
+            });
+
+        let mut pipeline = Self {
+            pipelines: HashMap::new(),
+            frame_bind_group_layout,
+            texture_bind_group_layout,
+            buffers_bind_group_layout,
+            volume_bind_group_layout,
+            marker: PhantomData,
+        };
+        let layout = pipeline.layouts();
+        let shader = world.resource::<AssetServer>().load(S::SHADER);
+        let pipeline_cache = world.resource::<PipelineCache>();
+        pipeline.pipelines = S::ENTRY_POINTS
+            .iter()
+            .chain(&["display"])
+            .map(|&entry_point| {
+                let id = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+                    label: Some(Cow::from(format!("{}_{entry_point}", S::NAME))),
+                    layout: layout.clone(),
+                    push_constant_ranges: vec![],
+                    shader: shader.clone(),
+                    shader_defs: vec![],
+                    entry_point: Cow::from(entry_point),
+                });
+                (entry_point, id)
+            })
+            .collect();
+        pipeline
+    }
+}
+
+/// Render world buffers of a [`Simulation`].
+#[derive(Resource)]
+pub(crate) struct SimulationBuffers<S> {
+    buffers: Vec<Buffer>,
+    /// The pair of volumes of a [`Simulation::VOLUME_FORMAT`].
+    volumes: Option<[(Texture, TextureView); 2]>,
+    /// Counts the recreations, the init passes run once per generation.
+    generation: u32,
+    /// Recreated for a loaded snapshot, without running the init passes.
+    restored: bool,
+    /// One uniform buffer of [`FrameParams`] and the params per step.
+    frames: Vec<(Buffer, Buffer)>,
+    marker: PhantomData<fn() -> S>,
+}
+
+impl<S: Simulation> SimulationBuffers<S> {
+    /// The buffer with the given label of [`Simulation::BUFFERS`].
+    pub(crate) fn get(&self, label: &str) -> Option<&Buffer> {
+        let index = S::BUFFERS.iter().position(|&buffer| buffer == label)?;
+        self.buffers.get(index)
+    }
+}
+
+fn prepare_simulation_buffers<S: Simulation>(
+    mut commands: Commands,
+    simulation: Res<S>,
+    frame: Res<SimulationFrame>,
+    buffers: Option<ResMut<SimulationBuffers<S>>>,
+    mut readback_buffers: ResMut<ReadbackBuffers>,
+    render_device: Res<RenderDevice>,
+) {
+    let uniform = |value: &dyn Fn(&mut UniformBuffer<Vec<u8>>)| {
+        let mut uniform = UniformBuffer::new(Vec::new());
+        value(&mut uniform);
+        render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some(S::NAME),
+            contents: uniform.as_ref(),
+            usage: BufferUsages::UNIFORM,
+        })
+    };
+    let params = simulation.params();
+    let frames = (0..frame.steps.max(1))
+        .map(|i| {
+            let pointer = &frame.pointer;
+            let frame = uniform(&|buffer| {
+                buffer
+                    .write(&FrameParams {
+                        size: frame.size.as_vec2(),
+                        pointer: pointer.position.unwrap_or(Vec2::splat(-1e6)),
+                        pointer_delta: pointer.delta,
+                        buttons: pointer.buttons,
+                        delta_time: frame.delta_time,
+                        step: frame.step.wrapping_add(i),
+                        seed: frame.seed,
+                    })
+                    .unwrap();
+            });
+            let params = uniform(&|buffer| buffer.write(&params).unwrap());
+            (frame, params)
+        })
+        .collect();
+
+    let generation = match buffers {
+        Some(mut buffers) if !frame.reset && !frame.resized && !frame.restored => {
+            buffers.frames = frames;
+            return;
+        }
+        Some(buffers) => buffers.generation.wrapping_add(1),
+        None => 0,
+    };
+
+    let contents = simulation.buffers(frame.size.max(UVec2::ONE));
+    assert_eq!(
+        contents.len(),
+        S::BUFFERS.len(),
+        "{} must create one buffer per label",
+        S::NAME
+    );
+    let usage = BufferUsages::STORAGE | BufferUsages::COPY_SRC | BufferUsages::COPY_DST;
+    let buffers = S::BUFFERS
+        .iter()
+        .zip(contents)
+        .map(|(label, init)| {
+            let buffer = match init {
+                BufferInit::Zeroed(size) => render_device.create_buffer(&BufferDescriptor {
+                    label: Some(label),
+                    size: size.max(4),
+                    usage,
+                    mapped_at_creation: false,
+                }),
+                BufferInit::Data(data) => {
+                    render_device.create_buffer_with_data(&BufferInitDescriptor {
+                        label: Some(label),
+                        contents: &data,
+                        usage,
+                    })
+                }
+            };
+            readback_buffers.0.insert(label, buffer.clone());
+            buffer
+        })
+        .collect();
+    let volumes = S::VOLUME_FORMAT.map(|format| {
+        let size = simulation.volume_size().max(UVec3::ONE);
+        [0, 1].map(|_| {
+            let texture = render_device.create_texture(&TextureDescriptor {
+                label: Some(S::NAME),
+                size: Extent3d {
+                    width: size.x,
+                    height: size.y,
+                    depth_or_array_layers: size.z,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D3,
+                format,
+                usage: TextureUsages::STORAGE_BINDING | TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&TextureViewDescriptor::default());
+            (texture, view)
+        })
+    });
+    commands.insert_resource(SimulationBuffers::<S> {
+        buffers,
+        volumes,
+        generation,
+        restored: frame.restored,
+        frames,
+        marker: PhantomData,
+    });
+}
+
+/// Bind groups of a [`Simulation`], they only exist while it runs.
+#[derive(Resource)]
+pub(crate) struct SimulationBindGroups<S> {
+    frames: Vec<BindGroup>,
+    /// Writing the first image and reading the second, then the other way round.
+    textures: [BindGroup; 2],
+    buffers: Option<BindGroup>,
+    /// Like the texture groups, for the volumes.
+    volumes: Option<[BindGroup; 2]>,
+    /// Index of the texture group reading the latest state once all passes
+    /// of this frame ran, set by the node.
+    latest: usize,
+    marker: PhantomData<fn() -> S>,
+}
+
+impl<S> SimulationBindGroups<S> {
+    /// Group 0 of the last step of this frame.
+    pub(crate) fn frame(&self) -> &BindGroup {
+        &self.frames[self.frames.len() - 1]
+    }
+
+    /// Binds the groups of the last step of this frame, reading the latest
+    /// state like the `display` pass does.
+    pub(crate) fn set_latest<'a>(&'a self, pass: &mut ComputePass<'a>) {
+        pass.set_bind_group(0, self.frame(), &[]);
+        pass.set_bind_group(1, &self.textures[self.latest], &[]);
+        if let Some(buffers) = &self.buffers {
+            pass.set_bind_group(2, buffers, &[]);
+        }
+        if let Some(volumes) = &self.volumes {
+            pass.set_bind_group(3, &volumes[self.latest], &[]);
+        }
+    }
+}
+
+/// Tools look for the bind groups, they must not run on another simulation.
+fn remove_simulation_bind_groups<S: Simulation>(mut commands: Commands) {
+    commands.remove_resource::<SimulationBindGroups<S>>();
+}
+
+fn queue_simulation_bind_groups<S: Simulation>(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    pipeline: Res<SimulationPipeline<S>>,
+    buffers: Option<Res<SimulationBuffers<S>>>,
+    handles: Res<ComputePlaygroundImages>,
+    display: Res<SimulationDisplay>,
+    gpu_images: Res<RenderAssets<Image>>,
+) {
+    // the images are recreated in the new format a frame after switching
+    let (Some(buffers), true, Some(a), Some(b), Some(display)) = (
+        buffers,
+        handles.format == S::STATE_FORMAT,
+        gpu_images.get(&handles.main_textures.0),
+        gpu_images.get(&handles.main_textures.1),
+        gpu_images.get(&display.0),
+    ) else {
+        commands.remove_resource::<SimulationBindGroups<S>>();
+        return;
+    };
+
+    let frames = buffers
+        .frames
+        .iter()
+        .map(|(frame, params)| {
+            render_device.create_bind_group(&BindGroupDescriptor {
+                label: Some("SimulationFrameBindGroup"),
+                layout: &pipeline.frame_bind_group_layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: frame.as_entire_binding(),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: params.as_entire_binding(),
+                    },
+                ],
+            })
+        })
+        .collect();
+    let textures = |output: &GpuImage, input: &GpuImage| {
+        render_device.create_bind_group(&BindGroupDescriptor {
+            label: Some("SimulationTextureBindGroup"),
+            layout: &pipeline.texture_bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&output.texture_view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&input.texture_view),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::TextureView(&display.texture_view),
+                },
+            ],
+        })
+    };
+    let volumes = pipeline
+        .volume_bind_group_layout
+        .as_ref()
+        .zip(buffers.volumes.as_ref())
+        .map(|(layout, [(_, a), (_, b)])| {
+            let volumes = |output: &TextureView, input: &TextureView| {
+                render_device.create_bind_group(&BindGroupDescriptor {
+                    label: Some("SimulationVolumeBindGroup"),
+                    layout,
+                    entries: &[
+                        BindGroupEntry {
+                            binding: 0,
+                            resource: BindingResource::TextureView(output),
+                        },
+                        BindGroupEntry {
+                            binding: 1,
+                            resource: BindingResource::TextureView(input),
+                        },
+                    ],
+                })
+            };
+            [volumes(a, b), volumes(b, a)]
+        });
+    let entries: Vec<_> = buffers
+        .buffers
+        .iter()
+        .enumerate()
+        .map(|(binding, buffer)| BindGroupEntry {
+            binding: binding as u32,
+            resource: buffer.as_entire_binding(),
+        })
+        .collect();
+    let buffers = (!entries.is_empty() || S::VOLUME_FORMAT.is_some()).then(|| {
+        render_device.create_bind_group(&BindGroupDescriptor {
+            label: Some("SimulationBuffersBindGroup"),
+            layout: &pipeline.buffers_bind_group_layout,
+            entries: &entries,
+        })
+    });
+
+    commands.insert_resource(SimulationBindGroups::<S> {
+        frames,
+        textures: [textures(a, b), textures(b, a)],
+        buffers,
+        volumes,
+        latest: 0,
+        marker: PhantomData,
+    });
+}
+
+struct SimulationNode<S> {
+    /// Generation of the [`SimulationBuffers`] whose init passes ran.
+    initialized: Option<u32>,
+    init: bool,
+    ready: bool,
+    /// Whether the first image holds the latest state, after the init
+    /// passes and after all passes of this frame.
+    flipped: (bool, bool),
+    marker: PhantomData<fn() -> S>,
+}
+
+impl<S> Default for SimulationNode<S> {
+    fn default() -> Self {
+        Self {
+            initialized: None,
+            init: false,
+            ready: false,
+            flipped: (false, false),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<S: Simulation> Node for SimulationNode<S> {
+    fn update(&mut self, world: &mut World) {
+        self.init = false;
+        self.ready = false;
+        if world.resource::<ActiveSimulation>().0 != S::NAME
+            || !world.contains_resource::<SimulationBindGroups<S>>()
+        {
+            return;
+        }
+        let Some(buffers) = world.get_resource::<SimulationBuffers<S>>() else {return;};
+        let pipeline_cache = world.resource::<PipelineCache>();
+        self.ready = world
+            .resource::<SimulationPipeline<S>>()
+            .pipelines
+            .values()
+            .all(|&id| {
+                matches!(
+                    pipeline_cache.get_compute_pipeline_state(id),
+                    CachedPipelineState::Ok(_)
+                )
+            });
+        if !self.ready {
+            return;
+        }
+
+        // the parity follows from the number of swaps, the images start cleared
+        let simulation = world.resource::<S>();
+        let odd_swaps = |passes: &[Pass]| passes.iter().filter(|pass| pass.swap).count() % 2 == 1;
+        let mut flipped = self.flipped.1;
+        if self.initialized != Some(buffers.generation) {
+            self.init = !buffers.restored;
+            self.initialized = Some(buffers.generation);
+            flipped = self.init && odd_swaps(&simulation.init_passes());
+        }
+        let steps = world.resource::<SimulationFrame>().steps;
+        let odd = odd_swaps(&simulation.passes()) && steps % 2 == 1;
+        self.flipped = (flipped, flipped ^ odd);
+        world.resource_mut::<SimulationBindGroups<S>>().latest = self.flipped.1 as usize;
+    }
+
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        if !self.ready || world.resource::<ActiveSimulation>().0 != S::NAME {
+            return Ok(());
+        }
+        let Some(bind_groups) = world.get_resource::<SimulationBindGroups<S>>() else {return Ok(());};
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let pipeline = world.resource::<SimulationPipeline<S>>();
+        let simulation = world.resource::<S>();
+        let frame = world.resource::<SimulationFrame>();
+        let size = frame.size;
+
+        let mut pass = render_context
+            .command_encoder()
+            .begin_compute_pass(&ComputePassDescriptor::default());
+        if let Some(buffers) = &bind_groups.buffers {
+            pass.set_bind_group(2, buffers, &[]);
+        }
+
+        // every pass with the index of its frame bind group
+        let mut steps = Vec::new();
+        if self.init {
+            steps.extend(simulation.init_passes().into_iter().map(|step| (0, step)));
+        }
+        let passes = simulation.passes();
+        for i in 0..frame.steps as usize {
+            steps.extend(passes.iter().map(|&step| (i, step)));
+        }
+        steps.push((bind_groups.frames.len() - 1, Pass::pixels("display")));
+
+        let mut flipped = if self.init { false } else { self.flipped.0 };
+        for (i, step) in steps {
+            let Some(&id) = pipeline.pipelines.get(step.entry_point) else {
+                panic!("{} is missing from the entry points of {}", step.entry_point, S::NAME);
+            };
+            let Some(compute_pipeline) = pipeline_cache.get_compute_pipeline(id) else {return Ok(());};
+            pass.set_bind_group(0, &bind_groups.frames[i], &[]);
+            pass.set_bind_group(1, &bind_groups.textures[flipped as usize], &[]);
+            if let Some(volumes) = &bind_groups.volumes {
+                pass.set_bind_group(3, &volumes[flipped as usize], &[]);
+            }
+            pass.set_pipeline(compute_pipeline);
+            let workgroups = match step.dispatch {
+                Dispatch::Pixels => UVec3::new(size.x.div_ceil(8), size.y.div_ceil(8), 1),
+                Dispatch::Items(count) => UVec3::new(count.div_ceil(64), 1, 1),
+                Dispatch::Workgroups(workgroups) => workgroups,
+            };
+            pass.dispatch_workgroups(workgroups.x, workgroups.y, workgroups.z);
+            if step.swap {
+                flipped = !flipped;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,478 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use bevy::{core::Zeroable, prelude::*, render::render_resource::encase::StorageBuffer};
+
+use crate::{
+    image::{create_image, ComputePlaygroundImages},
+    physarum::{
+        Agent, AgentParams, DepositMode, DepositParams, Physarum, SensorParams, TrailParams,
+    },
+    readback::{Readback, ReadbackId, ReadbackSource},
+    simulation::{
+        simulation_active, ActiveSimulation, Simulation, SimulationControl, SimulationFrame,
+    },
+};
+
+const MAGIC: &[u8; 8] = b"CPSNAP\0\0";
+const VERSION: u32 = 2;
+
+/// Larger images and agent counts are rejected as corrupt before the
+/// memory for them is allocated.
+const MAX_SIZE: u32 = 8192;
+const MAX_AGENTS: u64 = 1 << 26;
+
+/// Saves and restores the whole state of the slime mold agents.
+///
+/// `F5` saves to [`SnapshotSettings::path`], `F9` loads from it. The
+/// passes of [`Physarum`] swap the state images an even number of times,
+/// so the latest trail map is always the second one.
+pub(crate) struct SnapshotPlugin;
+impl Plugin for SnapshotPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SnapshotSettings>()
+            .init_resource::<PendingSnapshot>()
+            .add_startup_system(load_on_startup.in_base_set(StartupSet::PostStartup))
+            .add_systems(
+                (save_hotkey, load_hotkey).distributive_run_if(simulation_active(Physarum::NAME)),
+            )
+            .add_system(finish_snapshot);
+    }
+}
+
+/// Where snapshots go, set from the command line in `main.rs`.
+#[derive(Resource, Clone)]
+pub struct SnapshotSettings {
+    /// File used by the save and load hotkeys.
+    pub path: PathBuf,
+    /// Snapshot to resume from on startup.
+    pub load_on_startup: Option<PathBuf>,
+}
+
+impl Default for SnapshotSettings {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("snapshot.cpsnap"),
+            load_on_startup: None,
+        }
+    }
+}
+
+/// A save that is waiting for its GPU readbacks.
+#[derive(Resource, Default)]
+struct PendingSnapshot(Option<SnapshotRequest>);
+
+struct SnapshotRequest {
+    path: PathBuf,
+    data: Physarum,
+    seed: u32,
+    step: u32,
+    delta_time: f32,
+    size: UVec2,
+    agents: SnapshotPart,
+    textures: (SnapshotPart, SnapshotPart),
+}
+
+/// One readback of a [`SnapshotRequest`], kept once taken until the
+/// others are done too.
+enum SnapshotPart {
+    Requested(ReadbackId),
+    Finished(Vec<u8>),
+}
+
+struct Snapshot {
+    /// The parameters and the agents.
+    data: Physarum,
+    seed: u32,
+    /// The next step to run.
+    step: u32,
+    delta_time: f32,
+    size: UVec2,
+    textures: (Vec<u8>, Vec<u8>),
+}
+
+fn save_hotkey(
+    keys: Res<Input<KeyCode>>,
+    settings: Res<SnapshotSettings>,
+    mut pending: ResMut<PendingSnapshot>,
+    readback: Res<Readback>,
+    data: Res<Physarum>,
+    frame: Res<SimulationFrame>,
+    handles: Res<ComputePlaygroundImages>,
+) {
+    if keys.just_pressed(KeyCode::F5) && pending.0.is_none() {
+        info!("saving snapshot to {}", settings.path.display());
+        pending.0 = Some(SnapshotRequest {
+            path: settings.path.clone(),
+            data: data.clone(),
+            // the readbacks are taken after all steps of this frame
+            seed: frame.seed,
+            step: frame.step.wrapping_add(frame.steps),
+            delta_time: frame.delta_time,
+            size: frame.size,
+            agents: SnapshotPart::Requested(readback.request(ReadbackSource::Buffer("agents"))),
+            textures: (
+                SnapshotPart::Requested(
+                    readback.request(ReadbackSource::Image(handles.main_textures.0.clone())),
+                ),
+                SnapshotPart::Requested(
+                    readback.request(ReadbackSource::Image(handles.main_textures.1.clone())),
+                ),
+            ),
+        });
+    }
+}
+
+fn load_hotkey(
+    keys: Res<Input<KeyCode>>,
+    settings: Res<SnapshotSettings>,
+    mut data: ResMut<Physarum>,
+    mut control: ResMut<SimulationControl>,
+    handles: Res<ComputePlaygroundImages>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    if keys.just_pressed(KeyCode::F9) {
+        load(
+            &settings.path,
+            &mut data,
+            &mut control,
+            &handles,
+            &mut images,
+        );
+    }
+}
+
+fn finish_snapshot(mut pending: ResMut<PendingSnapshot>, readback: Res<Readback>) {
+    let Some(request) = pending.0.as_mut() else {return;};
+    let parts = [
+        &mut request.agents,
+        &mut request.textures.0,
+        &mut request.textures.1,
+    ];
+    let mut failed = false;
+    for part in parts {
+        let SnapshotPart::Requested(id) = *part else {continue;};
+        match readback.take(id) {
+            Some(Ok(bytes)) => *part = SnapshotPart::Finished(bytes),
+            Some(Err(_)) => failed = true,
+            None => {}
+        }
+    }
+    if failed {
+        let request = pending.0.take().unwrap();
+        for part in [request.agents, request.textures.0, request.textures.1] {
+            if let SnapshotPart::Requested(id) = part {
+                readback.cancel(id);
+            }
+        }
+        error!("saving snapshot to {} failed", request.path.display());
+        return;
+    }
+    let (
+        SnapshotPart::Finished(_),
+        (SnapshotPart::Finished(_), SnapshotPart::Finished(_)),
+    ) = (&request.agents, &request.textures) else {return;};
+    let request = pending.0.take().unwrap();
+    let (
+        SnapshotPart::Finished(agents),
+        (SnapshotPart::Finished(a), SnapshotPart::Finished(b)),
+    ) = (request.agents, request.textures) else {unreachable!()};
+
+    let agents: Vec<Agent> = StorageBuffer::new(agents).create().unwrap();
+    let snapshot = Snapshot {
+        data: Physarum {
+            agents: Arc::new(agents),
+            ..request.data
+        },
+        seed: request.seed,
+        step: request.step,
+        delta_time: request.delta_time,
+        size: request.size,
+        textures: (a, b),
+    };
+    match File::create(&request.path).and_then(|file| snapshot.write(BufWriter::new(file))) {
+        Ok(()) => info!("snapshot saved to {}", request.path.display()),
+        Err(err) => error!(
+            "saving snapshot to {} failed: {err}",
+            request.path.display()
+        ),
+    }
+}
+
+fn load_on_startup(
+    settings: Res<SnapshotSettings>,
+    active: Res<ActiveSimulation>,
+    mut data: ResMut<Physarum>,
+    mut control: ResMut<SimulationControl>,
+    handles: Res<ComputePlaygroundImages>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let Some(path) = &settings.load_on_startup else {return;};
+    // the images would be taken as the state of another simulation
+    if active.0 != Physarum::NAME {
+        warn!(
+            "not loading {}, snapshots are of {} but {} runs",
+            path.display(),
+            Physarum::NAME,
+            active.0
+        );
+        return;
+    }
+    load(path, &mut data, &mut control, &handles, &mut images);
+}
+
+fn load(
+    path: &Path,
+    data: &mut Physarum,
+    control: &mut SimulationControl,
+    handles: &ComputePlaygroundImages,
+    images: &mut Assets<Image>,
+) {
+    let snapshot = match File::open(path).and_then(|file| Snapshot::read(BufReader::new(file))) {
+        Ok(snapshot) => snapshot,
+        Err(err) => {
+            error!("loading snapshot from {} failed: {err}", path.display());
+            return;
+        }
+    };
+
+    for (handle, texture) in [
+        (&handles.main_textures.0, snapshot.textures.0),
+        (&handles.main_textures.1, snapshot.textures.1),
+    ] {
+        let mut image = create_image(snapshot.size.x, snapshot.size.y);
+        image.data = texture;
+        images.set_untracked(handle, image);
+    }
+    *data = snapshot.data;
+    control.restore = Some((snapshot.seed, snapshot.step));
+    info!("snapshot loaded from {}", path.display());
+}
+
+impl Snapshot {
+    fn write(&self, mut w: impl Write) -> io::Result<()> {
+        let TrailParams {
+            diffusion,
+            evaporation,
+        } = self.data.trail;
+        let SensorParams {
+            sensor_size,
+            sensor_distance,
+            sensor_angle_between,
+        } = self.data.sensor;
+        let AgentParams {
+            turn_speed,
+            move_speed,
+        } = self.data.agent;
+        let DepositParams { mode, strength } = self.data.deposit;
+
+        w.write_all(MAGIC)?;
+        w.write_all(&VERSION.to_le_bytes())?;
+        w.write_all(&self.size.x.to_le_bytes())?;
+        w.write_all(&self.size.y.to_le_bytes())?;
+        for v in [diffusion, evaporation, self.delta_time] {
+            w.write_all(&v.to_le_bytes())?;
+        }
+        w.write_all(&self.seed.to_le_bytes())?;
+        w.write_all(&self.step.to_le_bytes())?;
+        w.write_all(&sensor_size.to_le_bytes())?;
+        for v in [
+            sensor_distance,
+            sensor_angle_between,
+            turn_speed,
+            move_speed,
+        ] {
+            w.write_all(&v.to_le_bytes())?;
+        }
+        w.write_all(&(mode as u32).to_le_bytes())?;
+        w.write_all(&strength.to_le_bytes())?;
+        let agents = self.data.agents.as_slice();
+        w.write_all(&(agents.len() as u64).to_le_bytes())?;
+        w.write_all(bytemuck::cast_slice(agents))?;
+        w.write_all(&self.textures.0)?;
+        w.write_all(&self.textures.1)?;
+        w.flush()
+    }
+
+    fn read(mut r: impl Read) -> io::Result<Self> {
+        let mut magic = [0; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a snapshot"));
+        }
+        let version = read_u32(&mut r)?;
+        if version != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported snapshot version {version}"),
+            ));
+        }
+        let size = UVec2::new(read_u32(&mut r)?, read_u32(&mut r)?);
+        if size.x > MAX_SIZE || size.y > MAX_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("image size {}x{} is too large", size.x, size.y),
+            ));
+        }
+        let trail = TrailParams {
+            diffusion: read_f32(&mut r)?,
+            evaporation: read_f32(&mut r)?,
+        };
+        let delta_time = read_f32(&mut r)?;
+        let seed = read_u32(&mut r)?;
+        let step = read_u32(&mut r)?;
+        let sensor = SensorParams {
+            sensor_size: read_u32(&mut r)? as i32,
+            sensor_distance: read_f32(&mut r)?,
+            sensor_angle_between: read_f32(&mut r)?,
+        };
+        let agent = AgentParams {
+            turn_speed: read_f32(&mut r)?,
+            move_speed: read_f32(&mut r)?,
+        };
+        let mode = match read_u32(&mut r)? {
+            0 => DepositMode::White,
+            1 => DepositMode::Heading,
+            2 => DepositMode::Species,
+            mode => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unknown deposit mode {mode}"),
+                ))
+            }
+        };
+        let deposit = DepositParams {
+            mode,
+            strength: read_f32(&mut r)?,
+        };
+
+        let mut len = [0; 8];
+        r.read_exact(&mut len)?;
+        let len = u64::from_le_bytes(len);
+        if len > MAX_AGENTS {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{len} agents are too many"),
+            ));
+        }
+        let mut agents = vec![Agent::zeroed(); len as usize];
+        r.read_exact(bytemuck::cast_slice_mut(&mut agents))?;
+
+        let texture_len = size.x as usize * size.y as usize * 4;
+        let mut textures = (vec![0; texture_len], vec![0; texture_len]);
+        r.read_exact(&mut textures.0)?;
+        r.read_exact(&mut textures.1)?;
+
+        Ok(Snapshot {
+            data: Physarum {
+                trail,
+                sensor,
+                agent,
+                deposit,
+                agents: Arc::new(agents),
+            },
+            seed,
+            step,
+            delta_time,
+            size,
+            textures,
+        })
+    }
+}
+
+fn read_u32(r: &mut impl Read) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_f32(r: &mut impl Read) -> io::Result<f32> {
+    read_u32(r).map(f32::from_bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physarum::SPECIES;
+
+    fn snapshot() -> Snapshot {
+        let mut data = Physarum::default();
+        data.sensor.sensor_size = -1;
+        data.deposit.mode = DepositMode::Species;
+        data.deposit.strength = 0.5;
+        data.agents = Arc::new(
+            (0..5)
+                .map(|i| Agent {
+                    positon: Vec2::new(i as f32, 0.5),
+                    angle: i as f32 * 0.1,
+                    species: i % SPECIES,
+                })
+                .collect(),
+        );
+        Snapshot {
+            data,
+            seed: 7,
+            step: 42,
+            delta_time: 0.016,
+            size: UVec2::new(3, 2),
+            textures: ((0..24).collect(), (24..48).collect()),
+        }
+    }
+
+    fn bytes(snapshot: &Snapshot) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        snapshot.write(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn round_trip() {
+        let written = snapshot();
+        let read = Snapshot::read(bytes(&written).as_slice()).unwrap();
+        assert_eq!(read.size, written.size);
+        assert_eq!(read.seed, 7);
+        assert_eq!(read.step, 42);
+        assert_eq!(read.delta_time, written.delta_time);
+        assert_eq!(read.data.trail.diffusion, written.data.trail.diffusion);
+        assert_eq!(read.data.sensor.sensor_size, -1);
+        assert_eq!(read.data.agent.move_speed, written.data.agent.move_speed);
+        assert_eq!(read.data.deposit.mode, DepositMode::Species);
+        assert_eq!(read.data.deposit.strength, 0.5);
+        assert_eq!(
+            bytemuck::cast_slice::<_, u8>(read.data.agents.as_slice()),
+            bytemuck::cast_slice::<_, u8>(written.data.agents.as_slice())
+        );
+        assert_eq!(read.textures, written.textures);
+        // a second round writes the same bytes
+        assert_eq!(bytes(&read), bytes(&written));
+    }
+
+    #[test]
+    fn truncated() {
+        let bytes = bytes(&snapshot());
+        for len in [0, 7, 12, 40, bytes.len() - 1] {
+            let err = Snapshot::read(&bytes[..len]).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof, "{len} bytes");
+        }
+    }
+
+    #[test]
+    fn rejects_huge_headers() {
+        let bytes = bytes(&snapshot());
+        let mut size = bytes.clone();
+        size[12..16].copy_from_slice(&u32::MAX.to_le_bytes());
+        let agents_offset = 20 + 12 * 4;
+        let mut agents = bytes.clone();
+        agents[agents_offset..agents_offset + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        let mut magic = bytes;
+        magic[0] = b'X';
+        for bytes in [size, agents, magic] {
+            let err = Snapshot::read(bytes.as_slice()).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+}
@@ -0,0 +1,439 @@
+use std::borrow::Cow;
+
+use bevy::{
+    app::AppExit,
+    prelude::*,
+    render::{
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+        render_graph::{Node, NodeRunError, RenderGraph, RenderGraphContext},
+        render_resource::{
+            encase::UniformBuffer, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+            BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, Buffer,
+            BufferBindingType, BufferDescriptor, BufferInitDescriptor, BufferUsages,
+            CachedComputePipelineId, ComputePassDescriptor, ComputePipelineDescriptor,
+            PipelineCache, ShaderStages, ShaderType,
+        },
+        renderer::{RenderContext, RenderDevice},
+        RenderApp, RenderSet,
+    },
+    window::{PresentMode, PrimaryWindow},
+};
+use bevy_inspector_egui::{prelude::*, quick::ResourceInspectorPlugin};
+
+use crate::{
+    physarum::{Agent, Physarum},
+    simulation::{
+        simulation_active, Simulation, SimulationBindGroups, SimulationBuffers, SimulationFrame,
+        SimulationPipeline,
+    },
+};
+
+/// Must match `TILE_SIZE` in `sort.wgsl`.
+const TILE_SIZE: u32 = 16;
+/// Seconds skipped at the start of every benchmark phase.
+const BENCHMARK_WARMUP: f64 = 1.0;
+
+/// Periodically reorders the agents buffer by position.
+///
+/// A counting sort by 16px tile runs on the GPU before the agents pass,
+/// so neighbouring invocations sense and deposit in the same part of the
+/// trail map. `F10` runs a benchmark comparing steps per second of a
+/// shuffled buffer against a sorted one.
+pub(crate) struct SortPlugin;
+impl Plugin for SortPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SortSettings>()
+            .init_resource::<SortBenchmark>()
+            .init_resource::<SortPass>()
+            .add_plugin(
+                ResourceInspectorPlugin::<SortSettings>::default()
+                    .run_if(simulation_active(Physarum::NAME)),
+            )
+            .add_plugin(ExtractResourcePlugin::<SortPass>::default())
+            .add_startup_system(start_benchmark_on_startup)
+            .add_system(run_benchmark.run_if(simulation_active(Physarum::NAME)))
+            .add_system(schedule_sort.in_base_set(CoreSet::PostUpdate));
+
+        let render_app = app.sub_app_mut(RenderApp);
+        render_app
+            .init_resource::<SortPipeline>()
+            .add_system(prepare_sort_buffers.in_set(RenderSet::Prepare))
+            .add_system(queue_sort_bind_group.in_set(RenderSet::Queue));
+        let mut render_graph = render_app.world.resource_mut::<RenderGraph>();
+        render_graph.add_node("agent_sort", SortNode);
+        render_graph.add_node_edge("agent_sort", Physarum::NAME);
+    }
+}
+
+#[derive(Resource, Clone, Reflect, InspectorOptions)]
+#[reflect(Resource, InspectorOptions)]
+pub struct SortSettings {
+    pub enabled: bool,
+    /// Steps between two sorts.
+    #[inspector(min = 1)]
+    pub interval: u32,
+    /// Seconds measured per benchmark phase.
+    #[inspector(min = 1.0)]
+    pub benchmark_duration: f32,
+    /// Run the benchmark on startup and exit once it is done.
+    #[reflect(ignore)]
+    pub benchmark_on_startup: bool,
+}
+
+impl Default for SortSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval: 64,
+            benchmark_duration: 5.0,
+            benchmark_on_startup: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SortKey {
+    Tiles,
+    Shuffle,
+}
+
+/// The sort to run on the GPU this step, if any.
+#[derive(Resource, ExtractResource, Clone, Default)]
+struct SortPass(Option<SortKey>);
+
+#[derive(ShaderType)]
+struct SortParams {
+    key: u32,
+    seed: u32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum BenchmarkPhase {
+    Shuffled,
+    Sorted,
+}
+
+#[derive(Resource, Default)]
+struct SortBenchmark {
+    start: bool,
+    phase: Option<BenchmarkPhase>,
+    phase_start: f64,
+    steps: u32,
+    /// Shuffle the agents buffer once at the start of the shuffled phase.
+    shuffle: bool,
+    shuffled_rate: f64,
+    present_mode: PresentMode,
+    exit_when_done: bool,
+}
+
+fn start_benchmark_on_startup(settings: Res<SortSettings>, mut benchmark: ResMut<SortBenchmark>) {
+    if settings.benchmark_on_startup {
+        benchmark.start = true;
+        benchmark.exit_when_done = true;
+    }
+}
+
+/// Steps run as fast as possible, without vsync, while the benchmark is
+/// running. The shuffled phase stands in for a long running simulation
+/// whose agents have spread far from their creation order.
+fn run_benchmark(
+    keys: Res<Input<KeyCode>>,
+    time: Res<Time>,
+    settings: Res<SortSettings>,
+    frame: Res<SimulationFrame>,
+    mut benchmark: ResMut<SortBenchmark>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let Ok(mut window) = windows.get_single_mut() else {return;};
+    let now = time.raw_elapsed_seconds_f64();
+    if keys.just_pressed(KeyCode::F10) && benchmark.phase.is_none() {
+        benchmark.start = true;
+    }
+    if benchmark.start {
+        info!("sort benchmark: {}s per phase", settings.benchmark_duration);
+        benchmark.start = false;
+        benchmark.present_mode = window.present_mode;
+        window.present_mode = PresentMode::AutoNoVsync;
+        benchmark.phase = Some(BenchmarkPhase::Shuffled);
+        benchmark.phase_start = now;
+        benchmark.steps = 0;
+        benchmark.shuffle = true;
+        return;
+    }
+    let Some(phase) = benchmark.phase else {return;};
+
+    let elapsed = now - benchmark.phase_start;
+    if elapsed < BENCHMARK_WARMUP {
+        return;
+    }
+    benchmark.steps += frame.steps;
+    let measured = elapsed - BENCHMARK_WARMUP;
+    if measured < settings.benchmark_duration as f64 {
+        return;
+    }
+    let rate = benchmark.steps as f64 / measured;
+    match phase {
+        BenchmarkPhase::Shuffled => {
+            info!("sort benchmark: shuffled agents {rate:.1} steps/s");
+            benchmark.shuffled_rate = rate;
+            benchmark.phase = Some(BenchmarkPhase::Sorted);
+            benchmark.phase_start = now;
+            benchmark.steps = 0;
+        }
+        BenchmarkPhase::Sorted => {
+            let gain = (rate / benchmark.shuffled_rate - 1.0) * 100.0;
+            info!("sort benchmark: sorted agents {rate:.1} steps/s ({gain:+.1}%)");
+            benchmark.phase = None;
+            window.present_mode = benchmark.present_mode;
+            if benchmark.exit_when_done {
+                exit.send(AppExit);
+            }
+        }
+    }
+}
+
+fn schedule_sort(
+    settings: Res<SortSettings>,
+    mut benchmark: ResMut<SortBenchmark>,
+    mut pass: ResMut<SortPass>,
+    mut last_sorted: Local<Option<u32>>,
+    frame: Res<SimulationFrame>,
+) {
+    let sorting = match benchmark.phase {
+        Some(BenchmarkPhase::Shuffled) => false,
+        Some(BenchmarkPhase::Sorted) => true,
+        None => settings.enabled,
+    };
+    pass.0 = if benchmark.shuffle {
+        benchmark.shuffle = false;
+        Some(SortKey::Shuffle)
+    } else if sorting && frame.steps > 0 && sort_due(*last_sorted, frame.step, settings.interval) {
+        *last_sorted = Some(frame.step);
+        Some(SortKey::Tiles)
+    } else {
+        None
+    };
+}
+
+/// Any number of steps can run per frame, so the sort is due once
+/// `interval` steps have passed since the last one. A reset or a restored
+/// snapshot moving the step back sorts right away.
+fn sort_due(last_sorted: Option<u32>, step: u32, interval: u32) -> bool {
+    let Some(last) = last_sorted else {return true;};
+    !(last..last.saturating_add(interval.max(1))).contains(&step)
+}
+
+#[derive(Resource)]
+struct SortPipeline {
+    count_pipeline: CachedComputePipelineId,
+    scan_pipeline: CachedComputePipelineId,
+    scatter_pipeline: CachedComputePipelineId,
+    sort_bind_group_layout: BindGroupLayout,
+}
+
+impl FromWorld for SortPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+
+        let storage_entry = |binding| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let sort_bind_group_layout =
+            render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("LayoutSortBindGroup"),
+                entries: &[
+                    storage_entry(0),
+                    storage_entry(1),
+                    BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: ShaderStages::COMPUTE,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: Some(SortParams::min_size()),
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let mut layout = world.resource::<SimulationPipeline<Physarum>>().layouts();
+        layout.push(sort_bind_group_layout.clone());
+
+        let sort_shader = world.resource::<AssetServer>().load("shaders/sort.wgsl");
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let queue = |entry_point: &'static str| {
+            pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+                label: Some(Cow::from(entry_point)),
+                layout: layout.clone(),
+                push_constant_ranges: vec![],
+                shader: sort_shader.clone(),
+                shader_defs: vec![],
+                entry_point: Cow::from(entry_point),
+            })
+        };
+
+        Self {
+            count_pipeline: queue("count"),
+            scan_pipeline: queue("scan"),
+            scatter_pipeline: queue("scatter"),
+            sort_bind_group_layout,
+        }
+    }
+}
+
+#[derive(Resource)]
+struct SortBuffers {
+    counts: Buffer,
+    sorted: Buffer,
+    tiles: UVec2,
+    agents: usize,
+}
+
+fn prepare_sort_buffers(
+    mut commands: Commands,
+    buffers: Option<Res<SortBuffers>>,
+    render_device: Res<RenderDevice>,
+    frame: Res<SimulationFrame>,
+    physarum: Res<Physarum>,
+) {
+    let tiles = (frame.size + TILE_SIZE - 1) / TILE_SIZE;
+    let agents = physarum.agents.len();
+    if buffers.is_some_and(|b| b.tiles == tiles && b.agents == agents) {
+        return;
+    }
+
+    let counts = render_device.create_buffer(&BufferDescriptor {
+        label: Some("sort_counts"),
+        size: (tiles.x * tiles.y).max(1) as u64 * 4,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let sorted = render_device.create_buffer(&BufferDescriptor {
+        label: Some("sorted_agents"),
+        size: (agents.max(1) * std::mem::size_of::<Agent>()) as u64,
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+    commands.insert_resource(SortBuffers {
+        counts,
+        sorted,
+        tiles,
+        agents,
+    });
+}
+
+#[derive(Resource)]
+struct SortBindGroup(BindGroup);
+
+fn queue_sort_bind_group(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    pipeline: Res<SortPipeline>,
+    pass: Res<SortPass>,
+    buffers: Res<SortBuffers>,
+    frame: Res<SimulationFrame>,
+) {
+    let Some(key) = pass.0 else {return;};
+    let mut uniform = UniformBuffer::new(Vec::new());
+    uniform
+        .write(&SortParams {
+            key: match key {
+                SortKey::Tiles => 0,
+                SortKey::Shuffle => 1,
+            },
+            seed: frame.seed ^ frame.step,
+        })
+        .unwrap();
+    let params = render_device.create_buffer_with_data(&BufferInitDescriptor {
+        label: Some("sort_params"),
+        contents: uniform.as_ref(),
+        usage: BufferUsages::UNIFORM,
+    });
+    commands.insert_resource(SortBindGroup(render_device.create_bind_group(
+        &BindGroupDescriptor {
+            label: Some("SortBindGroup"),
+            layout: &pipeline.sort_bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: buffers.counts.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: buffers.sorted.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: params.as_entire_binding(),
+                },
+            ],
+        },
+    )));
+}
+
+struct SortNode;
+
+impl Node for SortNode {
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        if world.resource::<SortPass>().0.is_none() {
+            return Ok(());
+        }
+        let (Some(bind_groups), Some(sort_bind_group), Some(buffers), Some(simulation_buffers)) = (
+            world.get_resource::<SimulationBindGroups<Physarum>>(),
+            world.get_resource::<SortBindGroup>(),
+            world.get_resource::<SortBuffers>(),
+            world.get_resource::<SimulationBuffers<Physarum>>(),
+        ) else {return Ok(());};
+        let Some(agents_buffer) = simulation_buffers.get("agents") else {return Ok(());};
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let pipeline = world.resource::<SortPipeline>();
+        let (Some(count_pipeline), Some(scan_pipeline), Some(scatter_pipeline)) = (
+            pipeline_cache.get_compute_pipeline(pipeline.count_pipeline),
+            pipeline_cache.get_compute_pipeline(pipeline.scan_pipeline),
+            pipeline_cache.get_compute_pipeline(pipeline.scatter_pipeline),
+        ) else {return Ok(());};
+
+        let agents_len = world.resource::<Physarum>().agents.len();
+        if agents_len != buffers.agents {
+            return Ok(());
+        }
+
+        let encoder = render_context.command_encoder();
+        encoder.clear_buffer(&buffers.counts, 0, None);
+        {
+            let mut pass = encoder.begin_compute_pass(&ComputePassDescriptor::default());
+            bind_groups.set_latest(&mut pass);
+            pass.set_bind_group(3, &sort_bind_group.0, &[]);
+
+            let workgroups = (agents_len as u32).div_ceil(64);
+            pass.set_pipeline(count_pipeline);
+            pass.dispatch_workgroups(workgroups, 1, 1);
+            pass.set_pipeline(scan_pipeline);
+            pass.dispatch_workgroups(1, 1, 1);
+            pass.set_pipeline(scatter_pipeline);
+            pass.dispatch_workgroups(workgroups, 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(
+            &buffers.sorted,
+            0,
+            agents_buffer,
+            0,
+            (agents_len * std::mem::size_of::<Agent>()) as u64,
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,9 @@
+use bevy::prelude::*;
+
+use crate::simulation::BufferInit;
+
+/// The `deposits` buffer of trail.wgsl, four fixed point color channels
+/// per pixel.
+pub(crate) fn deposit_buffer(size: UVec2) -> BufferInit {
+    BufferInit::Zeroed(size.x as u64 * size.y as u64 * 4 * 4)
+}
@@ -0,0 +1,207 @@
+use std::hash::Hash;
+
+use bevy::{
+    prelude::*,
+    render::{
+        extract_resource::ExtractResource,
+        render_resource::{ShaderType, TextureFormat},
+    },
+};
+use bevy_inspector_egui::prelude::*;
+
+use crate::{
+    physarum::{AgentParams, SensorParams, TrailParams},
+    simulation::{simulation_active, BufferInit, ComputeSimulationPlugin, Pass, Simulation},
+};
+
+/// Must match the size of `VolumeAgent` in `volume.wgsl`.
+const VOLUME_AGENT_SIZE: u64 = 32;
+
+/// A 3D variant of physarum.
+///
+/// Agents with a yaw and pitch move through a cubic trail volume that
+/// wraps around at its faces, sensing and depositing like the 2D agents
+/// but with distances in voxels. The trail is the `r32float` volume pair
+/// of the simulation, each step diffuses it from one into the other. It
+/// is shown either as an axis aligned slice or as a raymarched render
+/// from an orbiting camera.
+pub(crate) struct VolumePlugin;
+impl Plugin for VolumePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(ComputeSimulationPlugin::<Volume>::default())
+            .add_system(orbit_camera.run_if(simulation_active(Volume::NAME)));
+    }
+}
+
+/// Values match `params.view` in `volume.wgsl`.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Reflect, FromReflect)]
+enum VolumeView {
+    #[default]
+    Raymarch = 0,
+    Slice = 1,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Reflect, FromReflect)]
+enum SliceAxis {
+    X = 0,
+    Y = 1,
+    #[default]
+    Z = 2,
+}
+
+#[derive(Resource, ExtractResource, Clone, Reflect, InspectorOptions)]
+#[reflect(Resource, InspectorOptions)]
+pub(crate) struct Volume {
+    /// Voxels along each side.
+    #[inspector(min = 16, max = 256)]
+    size: u32,
+    #[inspector(min = 64, max = 2097152)]
+    agents: u32,
+    trail: TrailParams,
+    sensor: SensorParams,
+    agent: AgentParams,
+    #[inspector(min = 0.0, max = 1.0, speed = 0.01)]
+    deposit_strength: f32,
+    view: VolumeView,
+    axis: SliceAxis,
+    /// Position of the slice along its axis.
+    #[inspector(min = 0.0, max = 1.0, speed = 0.002)]
+    slice: f32,
+    /// Opacity of the raymarched trail.
+    #[inspector(min = 0.0, speed = 0.1)]
+    density: f32,
+    /// Raymarch samples along the cube diagonal.
+    #[inspector(min = 8, max = 1024)]
+    samples: u32,
+    /// Camera yaw change in radians per second.
+    orbit_speed: f32,
+    camera_yaw: f32,
+    #[inspector(min = -1.5, max = 1.5, speed = 0.01)]
+    camera_pitch: f32,
+}
+
+impl Default for Volume {
+    fn default() -> Self {
+        Self {
+            size: 128,
+            agents: 1 << 18,
+            trail: TrailParams::default(),
+            // a 128 voxel cube is about a fifth the height of the 2D trail map
+            sensor: SensorParams {
+                sensor_size: 1,
+                sensor_distance: 3.0,
+                sensor_angle_between: 0.7,
+            },
+            agent: AgentParams {
+                turn_speed: 70.0,
+                move_speed: 10.0,
+            },
+            deposit_strength: 1.0,
+            view: VolumeView::Raymarch,
+            axis: SliceAxis::Z,
+            slice: 0.5,
+            density: 20.0,
+            samples: 256,
+            orbit_speed: 0.2,
+            camera_yaw: 0.0,
+            camera_pitch: 0.4,
+        }
+    }
+}
+
+#[derive(ShaderType)]
+pub(crate) struct VolumeParams {
+    diffusion: f32,
+    evaporation: f32,
+    sensor_size: i32,
+    sensor_distance: f32,
+    sensor_angle_between: f32,
+    turn_speed: f32,
+    move_speed: f32,
+    deposit_strength: f32,
+    size: u32,
+    agents: u32,
+    view: u32,
+    axis: u32,
+    slice: f32,
+    density: f32,
+    samples: u32,
+    camera: Vec2,
+}
+
+impl Simulation for Volume {
+    const NAME: &'static str = "volume";
+    const SHADER: &'static str = "shaders/volume.wgsl";
+    const ENTRY_POINTS: &'static [&'static str] = &["init_agents", "update", "diffuse"];
+    const BUFFERS: &'static [&'static str] = &["volume_agents", "volume_deposits"];
+    const VOLUME_FORMAT: Option<TextureFormat> = Some(TextureFormat::R32Float);
+
+    type Params = VolumeParams;
+
+    fn params(&self) -> VolumeParams {
+        VolumeParams {
+            diffusion: self.trail.diffusion,
+            evaporation: self.trail.evaporation,
+            sensor_size: self.sensor.sensor_size,
+            sensor_distance: self.sensor.sensor_distance,
+            sensor_angle_between: self.sensor.sensor_angle_between,
+            turn_speed: self.agent.turn_speed,
+            move_speed: self.agent.move_speed,
+            deposit_strength: self.deposit_strength,
+            size: self.size(),
+            agents: self.agents(),
+            view: self.view as u32,
+            axis: self.axis as u32,
+            slice: self.slice,
+            density: self.density,
+            samples: self.samples,
+            camera: Vec2::new(self.camera_yaw, self.camera_pitch),
+        }
+    }
+
+    /// The buffers are sized by both.
+    fn restart_key(&self) -> impl Hash {
+        (self.size(), self.agents())
+    }
+
+    /// The agents and their deposits in fixed point, one per voxel.
+    fn buffers(&self, _size: UVec2) -> Vec<BufferInit> {
+        vec![
+            BufferInit::Zeroed(self.agents() as u64 * VOLUME_AGENT_SIZE),
+            BufferInit::Zeroed((self.size() as u64).pow(3) * 4),
+        ]
+    }
+
+    fn volume_size(&self) -> UVec3 {
+        UVec3::splat(self.size())
+    }
+
+    fn init_passes(&self) -> Vec<Pass> {
+        vec![Pass::items("init_agents", self.agents())]
+    }
+
+    fn passes(&self) -> Vec<Pass> {
+        let voxels = UVec3::splat(self.size().div_ceil(4));
+        vec![
+            Pass::items("update", self.agents()),
+            Pass::workgroups("diffuse", voxels).swap(),
+        ]
+    }
+}
+
+impl Volume {
+    fn size(&self) -> u32 {
+        self.size.clamp(16, 256)
+    }
+
+    fn agents(&self) -> u32 {
+        self.agents.clamp(64, 1 << 21)
+    }
+}
+
+fn orbit_camera(time: Res<Time>, mut settings: ResMut<Volume>) {
+    if settings.orbit_speed != 0.0 {
+        settings.camera_yaw = (settings.camera_yaw + settings.orbit_speed * time.delta_seconds())
+            % std::f32::consts::TAU;
+    }
+}
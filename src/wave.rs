@@ -0,0 +1,303 @@
+use std::hash::Hash;
+
+use bevy::{
+    prelude::*,
+    render::{
+        extract_resource::ExtractResource,
+        render_resource::{ShaderType, TextureFormat},
+        texture::{CompressedImageFormats, ImageType},
+    },
+};
+use bevy_inspector_egui::prelude::*;
+
+use crate::{
+    image::fit_into_buffer,
+    simulation::{
+        simulation_active, BufferInit, ComputeSimulationPlugin, Pass, Simulation, SimulationFrame,
+    },
+};
+
+/// Sources beyond this are ignored, the shader has a fixed size array.
+const MAX_SOURCES: usize = 16;
+
+/// The 2D wave equation on a grid, integrated with a leapfrog step.
+///
+/// The state images hold the height in red, its velocity in green and the
+/// walls in blue. Sources force the height to oscillate, all in phase so
+/// they interfere cleanly. The left mouse button places a point source,
+/// drags out a line source or paints walls depending on the tool, the
+/// right one erases walls and removes sources under the brush.
+pub(crate) struct WavePlugin;
+impl Plugin for WavePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(ComputeSimulationPlugin::<Wave>::default())
+            .add_system(place_sources.run_if(simulation_active(Wave::NAME)));
+    }
+}
+
+/// Values match the tool ids in `wave.wgsl`.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Reflect, FromReflect)]
+enum WaveTool {
+    #[default]
+    PointSource = 0,
+    /// Drag from one end to the other.
+    LineSource = 1,
+    Wall = 2,
+}
+
+/// Values match the scene ids in `wave.wgsl`.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Debug, Reflect, FromReflect)]
+enum WaveScene {
+    /// No walls.
+    Empty = 0,
+    /// A wall with two slits a third of the way in.
+    #[default]
+    DoubleSlit = 1,
+    /// Walls from the dark pixels of the mask image, stretched to the window.
+    Mask = 2,
+}
+
+#[derive(Resource, ExtractResource, Clone, Reflect, InspectorOptions)]
+#[reflect(Resource, InspectorOptions)]
+pub(crate) struct Wave {
+    /// Pixels a wave travels per step, above `1 / √2` it blows up.
+    #[inspector(min = 0.01, max = 0.7, speed = 0.01)]
+    speed: f32,
+    /// Fraction of the velocity lost per step.
+    #[inspector(min = 0.0, max = 0.1, speed = 0.0001)]
+    damping: f32,
+    /// Oscillations of the sources per step.
+    #[inspector(min = 0.001, max = 0.2, speed = 0.001)]
+    frequency: f32,
+    #[inspector(min = 0.0, max = 10.0, speed = 0.01)]
+    amplitude: f32,
+    /// Width in pixels of a border that swallows waves instead of
+    /// reflecting them, 0 for reflecting edges.
+    #[inspector(min = 0.0, max = 200.0)]
+    absorbing_border: f32,
+    /// Start in `xy` and end in `zw` as fractions of the window, the same
+    /// for a point source.
+    sources: Vec<Vec4>,
+    tool: WaveTool,
+    #[inspector(min = 1.0, max = 100.0)]
+    brush_radius: f32,
+    /// Walls the simulation starts with, painted walls are lost on a reset.
+    scene: WaveScene,
+    /// Image loaded on every reset while `scene` is `Mask`.
+    mask: String,
+    #[reflect(ignore)]
+    mask_pixels: Option<Mask>,
+}
+
+impl Default for Wave {
+    fn default() -> Self {
+        Self {
+            speed: 0.5,
+            damping: 0.0005,
+            frequency: 0.04,
+            amplitude: 1.0,
+            absorbing_border: 40.0,
+            // a plane wave towards the slits
+            sources: vec![Vec4::new(0.1, 0.1, 0.1, 0.9)],
+            tool: WaveTool::PointSource,
+            brush_radius: 8.0,
+            scene: WaveScene::DoubleSlit,
+            mask: "patterns/parabola.png".to_owned(),
+            mask_pixels: None,
+        }
+    }
+}
+
+impl Wave {
+    /// The source closest to a pixel, if the brush touches it.
+    fn source_at(&self, pixel: Vec2, size: Vec2) -> Option<usize> {
+        self.sources
+            .iter()
+            .take(MAX_SOURCES)
+            .enumerate()
+            .map(|(i, source)| {
+                let start = source.truncate().truncate() * size;
+                let end = Vec2::new(source.z, source.w) * size;
+                (i, segment_distance(pixel, start, end))
+            })
+            .filter(|&(_, distance)| distance < self.brush_radius)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, _)| i)
+    }
+}
+
+fn segment_distance(point: Vec2, start: Vec2, end: Vec2) -> f32 {
+    let line = end - start;
+    let t = if line == Vec2::ZERO {
+        0.0
+    } else {
+        ((point - start).dot(line) / line.length_squared()).clamp(0.0, 1.0)
+    };
+    point.distance(start + line * t)
+}
+
+#[derive(ShaderType)]
+pub(crate) struct WaveParams {
+    sources: [Vec4; MAX_SOURCES],
+    source_count: u32,
+    speed: f32,
+    damping: f32,
+    frequency: f32,
+    amplitude: f32,
+    absorbing_border: f32,
+    tool: u32,
+    brush_radius: f32,
+    scene: u32,
+}
+
+impl Simulation for Wave {
+    const NAME: &'static str = "wave";
+    const SHADER: &'static str = "shaders/wave.wgsl";
+    const ENTRY_POINTS: &'static [&'static str] = &["init", "update"];
+    /// Small amplitudes far from the sources vanish at half precision.
+    const STATE_FORMAT: TextureFormat = TextureFormat::Rgba32Float;
+    const STEPS_PER_FRAME: u32 = 4;
+    const BUFFERS: &'static [&'static str] = &["wave_mask"];
+
+    type Params = WaveParams;
+
+    fn params(&self) -> WaveParams {
+        let mut sources = [Vec4::ZERO; MAX_SOURCES];
+        for (slot, source) in sources.iter_mut().zip(&self.sources) {
+            *slot = *source;
+        }
+        let scene = match (self.scene, &self.mask_pixels) {
+            (WaveScene::Mask, None) => WaveScene::Empty,
+            (scene, _) => scene,
+        };
+        WaveParams {
+            sources,
+            source_count: self.sources.len().min(MAX_SOURCES) as u32,
+            speed: self.speed,
+            damping: self.damping,
+            frequency: self.frequency,
+            amplitude: self.amplitude,
+            absorbing_border: self.absorbing_border,
+            tool: self.tool as u32,
+            brush_radius: self.brush_radius,
+            scene: scene as u32,
+        }
+    }
+
+    fn restart_key(&self) -> impl Hash {
+        self.scene
+    }
+
+    /// Reloads the mask, so edits to the image show up.
+    fn reload(&mut self, _seed: u32) {
+        if self.scene != WaveScene::Mask {
+            return;
+        }
+        match Mask::load(&self.mask) {
+            Ok(mask) => self.mask_pixels = Some(mask),
+            Err(err) => {
+                warn!("loading {} failed: {err}", self.mask);
+                self.mask_pixels = None;
+            }
+        }
+    }
+
+    /// The mask as its width and height followed by one `u32` per pixel,
+    /// 1 for a wall.
+    fn buffers(&self, _size: UVec2) -> Vec<BufferInit> {
+        let (size, walls) = match &self.mask_pixels {
+            Some(mask) => (mask.size, mask.walls.as_slice()),
+            None => (UVec2::ZERO, &[][..]),
+        };
+        let words: Vec<u32> = [size.x, size.y]
+            .into_iter()
+            .chain(walls.iter().map(|&wall| wall as u32))
+            .collect();
+        vec![BufferInit::Data(bytemuck::cast_slice(&words).to_vec())]
+    }
+
+    fn init_passes(&self) -> Vec<Pass> {
+        vec![Pass::pixels("init").swap()]
+    }
+
+    fn passes(&self) -> Vec<Pass> {
+        vec![Pass::pixels("update").swap()]
+    }
+}
+
+/// Walls from an image, row by row from the top.
+#[derive(Clone, Debug, PartialEq)]
+struct Mask {
+    size: UVec2,
+    walls: Vec<bool>,
+}
+
+impl Mask {
+    /// Pixels darker than half gray are walls.
+    fn load(path: &str) -> Result<Self, String> {
+        let bytes = std::fs::read(path).map_err(|err| err.to_string())?;
+        let extension = std::path::Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or("png");
+        let image = Image::from_buffer(
+            &bytes,
+            ImageType::Extension(extension),
+            CompressedImageFormats::NONE,
+            false,
+        )
+        .map_err(|err| err.to_string())?;
+        let image = image.try_into_dynamic().map_err(|err| err.to_string())?;
+        let luma = fit_into_buffer(image, path).to_luma8();
+        Ok(Mask {
+            size: UVec2::new(luma.width(), luma.height()),
+            walls: luma.pixels().map(|pixel| pixel.0[0] < 128).collect(),
+        })
+    }
+}
+
+/// Adds and removes sources, the line source being dragged out is
+/// remembered by its index.
+fn place_sources(
+    mut settings: ResMut<Wave>,
+    frame: Res<SimulationFrame>,
+    mut previous_buttons: Local<u32>,
+    mut dragged: Local<Option<usize>>,
+) {
+    let size = frame.size.as_vec2();
+    let pointer = frame.pointer;
+    let pressed = pointer.buttons & !*previous_buttons;
+    *previous_buttons = pointer.buttons;
+    if pointer.buttons & 1 == 0 {
+        *dragged = None;
+    }
+    let Some(position) = pointer.position else {
+        return;
+    };
+    if size.min_element() < 1.0 {
+        return;
+    }
+    let point = position / size;
+
+    if pressed & 1 != 0 && settings.tool != WaveTool::Wall && settings.sources.len() < MAX_SOURCES {
+        settings.sources.push(point.extend(point.x).extend(point.y));
+        if settings.tool == WaveTool::LineSource {
+            *dragged = Some(settings.sources.len() - 1);
+        }
+    }
+    if let Some(index) = *dragged {
+        if pointer.delta != Vec2::ZERO {
+            if let Some(source) = settings.sources.get_mut(index) {
+                source.z = point.x;
+                source.w = point.y;
+            }
+        }
+    }
+
+    if pressed & 2 != 0 {
+        if let Some(index) = settings.source_at(position, size) {
+            settings.sources.remove(index);
+            *dragged = None;
+        }
+    }
+}